@@ -0,0 +1,259 @@
+//! Export/import of manifests, coverage and gaps for backup or migration
+//! between databases.
+//!
+//! The document is plain serde data (serialize it with `serde_json`, TOML,
+//! whatever), keyed by the *natural* manifest key — (symbol, asset_class,
+//! provider, timeframe) — so ids are free to differ between source and
+//! target DBs. Coverage bitmaps travel as base64 roaring bytes.
+
+use base64::Engine as _;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use chrono::{DateTime, Utc};
+use roaring::RoaringBitmap;
+use rusqlite::{Connection, params};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::repo::{NewManifest, RepoError, SqliteRepo};
+use crate::timeframe::{Timeframe, TimeframeError};
+
+/// Bump when the document layout changes incompatibly.
+pub const BACKUP_FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug, Error)]
+pub enum BackupError {
+    #[error(transparent)]
+    Repo(#[from] RepoError),
+    #[error("sqlite error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+    #[error("unsupported backup format version {0} (supported: {BACKUP_FORMAT_VERSION})")]
+    UnsupportedVersion(u32),
+    #[error("invalid base64 coverage payload for {symbol} {timeframe}: {source}")]
+    InvalidCoverage {
+        symbol: String,
+        timeframe: String,
+        source: base64::DecodeError,
+    },
+    #[error("corrupt roaring bytes for {symbol} {timeframe}")]
+    CorruptBitmap { symbol: String, timeframe: String },
+    #[error("invalid timeframe for {symbol}: {source}")]
+    InvalidTimeframe {
+        symbol: String,
+        source: TimeframeError,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestDoc {
+    pub symbol: String,
+    pub asset_class: String,
+    pub provider: String,
+    pub tf_amount: u32,
+    pub tf_unit: String,
+    pub desired_start: DateTime<Utc>,
+    pub desired_end: Option<DateTime<Utc>>,
+    pub status: String,
+    /// Coverage version, bucket base and base64 roaring bytes; absent if
+    /// never covered.
+    pub coverage: Option<CoverageDoc>,
+    pub gaps: Vec<GapDoc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CoverageDoc {
+    pub version: i64,
+    #[serde(default)]
+    pub bucket_base: u64,
+    pub roaring_b64: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GapDoc {
+    pub start_bucket: u32,
+    pub end_bucket: u32,
+    pub state: String,
+    pub attempts: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BackupDoc {
+    pub format_version: u32,
+    pub manifests: Vec<ManifestDoc>,
+}
+
+/// Snapshot every manifest with its coverage bitmap and gap queue.
+pub fn export(conn: &Connection) -> Result<BackupDoc, BackupError> {
+    let mut manifests = Vec::new();
+    for m in SqliteRepo::manifests_all(conn)? {
+        let snap = SqliteRepo::coverage_get(conn, m.manifest_id)?;
+        let coverage = if snap.version == 0 {
+            None
+        } else {
+            let mut bytes = Vec::with_capacity(snap.bitmap.serialized_size());
+            snap.bitmap
+                .serialize_into(&mut bytes)
+                .expect("serializing into a Vec cannot fail");
+            Some(CoverageDoc {
+                version: snap.version,
+                bucket_base: snap.bucket_base,
+                roaring_b64: BASE64.encode(bytes),
+            })
+        };
+        let gaps = SqliteRepo::gaps_for_manifest(conn, m.manifest_id)?
+            .into_iter()
+            .map(|g| GapDoc {
+                start_bucket: g.start_bucket,
+                end_bucket: g.end_bucket,
+                state: g.state.as_str().to_string(),
+                attempts: g.attempts,
+            })
+            .collect();
+        manifests.push(ManifestDoc {
+            symbol: m.symbol,
+            asset_class: m.asset_class,
+            provider: m.provider,
+            tf_amount: m.timeframe.amount(),
+            tf_unit: m.timeframe.unit().as_str().to_string(),
+            desired_start: m.desired_start,
+            desired_end: m.desired_end,
+            status: m.status.as_str().to_string(),
+            coverage,
+            gaps,
+        });
+    }
+    Ok(BackupDoc {
+        format_version: BACKUP_FORMAT_VERSION,
+        manifests,
+    })
+}
+
+/// Restore a backup into `conn`. Idempotent: manifests are matched by
+/// natural key, coverage rows are replaced wholesale, and gap rows are
+/// only inserted when no identical row exists.
+pub fn import(conn: &Connection, doc: &BackupDoc) -> Result<(), BackupError> {
+    if doc.format_version != BACKUP_FORMAT_VERSION {
+        return Err(BackupError::UnsupportedVersion(doc.format_version));
+    }
+    let tx = conn.unchecked_transaction()?;
+    for m in &doc.manifests {
+        let timeframe =
+            Timeframe::from_db_row(i64::from(m.tf_amount), &m.tf_unit).map_err(|source| {
+                BackupError::InvalidTimeframe {
+                    symbol: m.symbol.clone(),
+                    source,
+                }
+            })?;
+        let asset_id = SqliteRepo::upsert_asset(&tx, &m.symbol, &m.asset_class)?;
+        let manifest_id = SqliteRepo::upsert_manifest(
+            &tx,
+            &NewManifest {
+                asset_id,
+                provider: m.provider.clone(),
+                timeframe,
+                desired_start: m.desired_start,
+                desired_end: m.desired_end,
+            },
+        )?;
+        if m.status == "closed" {
+            SqliteRepo::close_manifest(&tx, manifest_id)?;
+        }
+
+        if let Some(cov) = &m.coverage {
+            let bytes =
+                BASE64
+                    .decode(&cov.roaring_b64)
+                    .map_err(|source| BackupError::InvalidCoverage {
+                        symbol: m.symbol.clone(),
+                        timeframe: timeframe.to_string(),
+                        source,
+                    })?;
+            // Validate before writing so a truncated backup fails loudly.
+            RoaringBitmap::deserialize_from(&bytes[..]).map_err(|_| {
+                BackupError::CorruptBitmap {
+                    symbol: m.symbol.clone(),
+                    timeframe: timeframe.to_string(),
+                }
+            })?;
+            tx.execute(
+                "INSERT OR REPLACE INTO coverage (manifest_id, version, bucket_base, roaring)
+                 VALUES (?1, ?2, ?3, ?4)",
+                params![manifest_id, cov.version, cov.bucket_base as i64, bytes],
+            )?;
+        }
+
+        for gap in &m.gaps {
+            let exists: bool = tx.query_row(
+                "SELECT EXISTS (
+                     SELECT 1 FROM gaps
+                     WHERE manifest_id = ?1 AND start_bucket = ?2 AND end_bucket = ?3
+                 )",
+                params![manifest_id, gap.start_bucket, gap.end_bucket],
+                |r| r.get(0),
+            )?;
+            if !exists {
+                tx.execute(
+                    "INSERT INTO gaps (manifest_id, start_bucket, end_bucket, state, attempts)
+                     VALUES (?1, ?2, ?3, ?4, ?5)",
+                    params![
+                        manifest_id,
+                        gap.start_bucket,
+                        gap.end_bucket,
+                        gap.state,
+                        gap.attempts
+                    ],
+                )?;
+            }
+        }
+    }
+    tx.commit()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repo::test_support::*;
+
+    #[test]
+    fn round_trip_restores_manifests_coverage_and_gaps() {
+        let src = mem_conn();
+        let tf = minute_tf();
+        let id = insert_manifest(&src, "AAPL", "alpaca", tf, utc(2024, 1, 1, 0, 0), None);
+        let mut bm = RoaringBitmap::new();
+        bm.insert_range(100..200);
+        SqliteRepo::coverage_put(&src, id, 0, 512, &bm).unwrap();
+        SqliteRepo::gaps_insert(&src, id, 200, 300).unwrap();
+
+        let doc = export(&src).unwrap();
+        assert_eq!(doc.format_version, BACKUP_FORMAT_VERSION);
+
+        let dst = mem_conn();
+        import(&dst, &doc).unwrap();
+        // Importing twice must not duplicate anything.
+        import(&dst, &doc).unwrap();
+
+        let manifests = SqliteRepo::manifests_all(&dst).unwrap();
+        assert_eq!(manifests.len(), 1);
+        let restored_id = manifests[0].manifest_id;
+        let snap = SqliteRepo::coverage_get(&dst, restored_id).unwrap();
+        assert_eq!(snap.version, 1);
+        assert_eq!(snap.bucket_base, 512);
+        assert_eq!(snap.bitmap, bm);
+        let gaps = SqliteRepo::gaps_for_manifest(&dst, restored_id).unwrap();
+        assert_eq!(gaps.len(), 1);
+        assert_eq!((gaps[0].start_bucket, gaps[0].end_bucket), (200, 300));
+    }
+
+    #[test]
+    fn unknown_format_version_rejected() {
+        let doc = BackupDoc {
+            format_version: 99,
+            manifests: Vec::new(),
+        };
+        let conn = mem_conn();
+        assert!(matches!(
+            import(&conn, &doc),
+            Err(BackupError::UnsupportedVersion(99))
+        ));
+    }
+}
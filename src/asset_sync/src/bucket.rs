@@ -0,0 +1,368 @@
+//! Mapping between UTC timestamps and coverage bucket ids.
+//!
+//! A bucket is one bar interval of a [`Timeframe`], counted from the Unix
+//! epoch. Bucket `b` covers the half-open range
+//! `[b * tf.minutes(), (b + 1) * tf.minutes())` in epoch minutes. Ids are
+//! `u32` so they can live in a roaring bitmap; for minute bars that is
+//! enough until well past year 8000.
+
+use chrono::{DateTime, Datelike, Duration, Utc};
+use chrono_tz::Tz;
+use roaring::RoaringBitmap;
+use thiserror::Error;
+
+use crate::session::{HolidayCalendar, SessionCalendar};
+use crate::timeframe::Timeframe;
+use crate::tz::{DstPolicy, local_to_utc};
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum BucketError {
+    #[error("timestamp {0} predates the Unix epoch")]
+    PreEpoch(DateTime<Utc>),
+    #[error("bucket id {id} does not fit u32 relative to base {base}")]
+    RelativeOverflow { id: u64, base: u64 },
+    #[error("range start {start} is not before end {end}")]
+    EmptyRange {
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    },
+}
+
+/// Absolute id of the bucket containing `ts`. Absolute ids are `u64`;
+/// bitmaps store them relative to a per-manifest base via [`rel`].
+pub fn bucket_of(ts: DateTime<Utc>, tf: &Timeframe) -> Result<u64, BucketError> {
+    let secs = ts.timestamp();
+    if secs < 0 {
+        return Err(BucketError::PreEpoch(ts));
+    }
+    Ok((secs / (i64::from(tf.minutes()) * 60)) as u64)
+}
+
+/// UTC start of absolute bucket `id`.
+pub fn bucket_start(id: u64, tf: &Timeframe) -> DateTime<Utc> {
+    let secs = id as i64 * i64::from(tf.minutes()) * 60;
+    DateTime::from_timestamp(secs, 0).expect("bucket id within chrono range")
+}
+
+/// Snap `ts` down to the start of its bucket. `09:33` on a 5-minute
+/// timeframe becomes `09:30`; already-aligned timestamps are unchanged.
+/// Use this to normalize human-entered window starts before they reach
+/// the repo, so no partial bucket is created.
+pub fn align_to_bucket_start(
+    ts: DateTime<Utc>,
+    tf: &Timeframe,
+) -> Result<DateTime<Utc>, BucketError> {
+    Ok(bucket_start(bucket_of(ts, tf)?, tf))
+}
+
+/// Snap `ts` up to the next bucket boundary, suitable as an exclusive
+/// window end. Already-aligned timestamps are unchanged; `09:33` on a
+/// 5-minute timeframe becomes `09:35`.
+pub fn align_to_bucket_end_exclusive(
+    ts: DateTime<Utc>,
+    tf: &Timeframe,
+) -> Result<DateTime<Utc>, BucketError> {
+    let id = bucket_of(ts, tf)?;
+    if bucket_start(id, tf) == ts {
+        Ok(ts)
+    } else {
+        Ok(bucket_start(id + 1, tf))
+    }
+}
+
+/// How many buckets a `[start, end)` window spans, from arithmetic alone —
+/// no bitmap or repo work. Callers use it to refuse absurd windows (a
+/// decade of minute bars is ~5.3 million buckets) before allocating for
+/// them. Inverted windows estimate zero.
+pub fn estimate_bucket_count(window: (DateTime<Utc>, DateTime<Utc>), tf: &Timeframe) -> u64 {
+    let width = i64::from(tf.minutes()) * 60;
+    let span = window
+        .1
+        .timestamp()
+        .saturating_sub(window.0.timestamp())
+        .max(0);
+    ceil_div(span, width) as u64
+}
+
+/// Half-open absolute id range `[first, end)` of buckets whose start lies
+/// in `[start, end)`. This is the "desired" window for a manifest.
+pub fn bucket_range(
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    tf: &Timeframe,
+) -> Result<(u64, u64), BucketError> {
+    if start >= end {
+        return Err(BucketError::EmptyRange { start, end });
+    }
+    let width = i64::from(tf.minutes()) * 60;
+    let first = ceil_div(start.timestamp(), width);
+    let end_ex = ceil_div(end.timestamp(), width);
+    if first < 0 {
+        return Err(BucketError::PreEpoch(start));
+    }
+    Ok((first as u64, end_ex as u64))
+}
+
+/// Bitmap id of absolute bucket `id` relative to `base`. Fails only when
+/// the window spans more than `u32::MAX` buckets from its base, which no
+/// realistic manifest does.
+pub fn rel(id: u64, base: u64) -> Result<u32, BucketError> {
+    id.checked_sub(base)
+        .and_then(|d| u32::try_from(d).ok())
+        .ok_or(BucketError::RelativeOverflow { id, base })
+}
+
+/// Absolute bucket id of bitmap id `rel` under `base`.
+pub fn abs(rel: u32, base: u64) -> u64 {
+    base + u64::from(rel)
+}
+
+/// Bucket ids within `window` whose bars fall inside trading sessions.
+///
+/// Intraday buckets are in-session when their start lies in
+/// `[calendar.open, calendar.close)` local exchange time on a weekday
+/// that is not a holiday; session boundaries are resolved through the
+/// DST helpers so transition days are handled exactly. Buckets of a day
+/// or coarser are in-session on non-holiday weekdays, judged by the UTC
+/// date of the bucket start.
+pub fn session_filter_bitmap(
+    window: (u64, u64),
+    tf: &Timeframe,
+    calendar: &SessionCalendar,
+    holidays: &HolidayCalendar,
+    tz: Tz,
+    base: u64,
+) -> Result<RoaringBitmap, BucketError> {
+    let mut bitmap = RoaringBitmap::new();
+    let (first, end_ex) = window;
+    if first >= end_ex {
+        return Ok(bitmap);
+    }
+
+    if tf.minutes() >= 24 * 60 {
+        for id in first..end_ex {
+            let date = bucket_start(id, tf).date_naive();
+            if date.weekday().number_from_monday() <= 5 && !holidays.is_holiday(date) {
+                bitmap.insert(rel(id, base)?);
+            }
+        }
+        return Ok(bitmap);
+    }
+
+    // Walk local exchange dates overlapping the window and mark each
+    // day's session span.
+    let window_start = bucket_start(first, tf);
+    let window_end = bucket_start(end_ex, tf);
+    let mut date = window_start.with_timezone(&tz).date_naive();
+    let last_date = window_end.with_timezone(&tz).date_naive();
+    while date <= last_date {
+        if date.weekday().number_from_monday() <= 5 && !holidays.is_holiday(date) {
+            let open = local_to_utc(date.and_time(calendar.open), tz, DstPolicy::Earliest);
+            let close = local_to_utc(date.and_time(calendar.close), tz, DstPolicy::Earliest);
+            if let (Some(open), Some(close)) = (open, close)
+                && let Ok((session_first, session_end)) = bucket_range(open, close, tf)
+            {
+                let lo = session_first.max(first);
+                let hi = session_end.min(end_ex);
+                if lo < hi {
+                    bitmap.insert_range(rel(lo, base)?..rel(hi, base)?);
+                }
+            }
+        }
+        date += Duration::days(1);
+    }
+    Ok(bitmap)
+}
+
+fn ceil_div(a: i64, b: i64) -> i64 {
+    a.div_euclid(b) + i64::from(a.rem_euclid(b) != 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+    use crate::timeframe::TimeframeUnit;
+
+    fn utc(y: i32, mo: u32, d: u32, h: u32, mi: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, mo, d, h, mi, 0).unwrap()
+    }
+
+    #[test]
+    fn bucket_round_trip() {
+        let tf = Timeframe::new(5, TimeframeUnit::Minute).unwrap();
+        let ts = utc(2024, 1, 2, 14, 35);
+        let id = bucket_of(ts, &tf).unwrap();
+        assert_eq!(bucket_start(id, &tf), ts);
+        // Mid-bucket timestamps map to the same bucket.
+        let mid = ts + chrono::Duration::minutes(3);
+        assert_eq!(bucket_of(mid, &tf).unwrap(), id);
+    }
+
+    #[test]
+    fn alignment_snaps_mid_bucket_timestamps_across_units() {
+        let five_min = Timeframe::new(5, TimeframeUnit::Minute).unwrap();
+        let ts = utc(2024, 1, 2, 9, 33);
+        assert_eq!(
+            align_to_bucket_start(ts, &five_min).unwrap(),
+            utc(2024, 1, 2, 9, 30)
+        );
+        assert_eq!(
+            align_to_bucket_end_exclusive(ts, &five_min).unwrap(),
+            utc(2024, 1, 2, 9, 35)
+        );
+
+        let hour = Timeframe::new(1, TimeframeUnit::Hour).unwrap();
+        assert_eq!(
+            align_to_bucket_start(ts, &hour).unwrap(),
+            utc(2024, 1, 2, 9, 0)
+        );
+        assert_eq!(
+            align_to_bucket_end_exclusive(ts, &hour).unwrap(),
+            utc(2024, 1, 2, 10, 0)
+        );
+
+        let day = Timeframe::new(1, TimeframeUnit::Day).unwrap();
+        assert_eq!(
+            align_to_bucket_start(ts, &day).unwrap(),
+            utc(2024, 1, 2, 0, 0)
+        );
+        assert_eq!(
+            align_to_bucket_end_exclusive(ts, &day).unwrap(),
+            utc(2024, 1, 3, 0, 0)
+        );
+    }
+
+    #[test]
+    fn alignment_leaves_bucket_boundaries_alone() {
+        let tf = Timeframe::new(5, TimeframeUnit::Minute).unwrap();
+        let aligned = utc(2024, 1, 2, 9, 30);
+        assert_eq!(align_to_bucket_start(aligned, &tf).unwrap(), aligned);
+        assert_eq!(
+            align_to_bucket_end_exclusive(aligned, &tf).unwrap(),
+            aligned
+        );
+    }
+
+    #[test]
+    fn bucket_estimates_match_across_units() {
+        let start = utc(2024, 1, 1, 0, 0);
+        let end = utc(2024, 1, 8, 0, 0); // one week
+
+        let minute = Timeframe::new(1, TimeframeUnit::Minute).unwrap();
+        assert_eq!(estimate_bucket_count((start, end), &minute), 7 * 24 * 60);
+        let five_min = Timeframe::new(5, TimeframeUnit::Minute).unwrap();
+        assert_eq!(estimate_bucket_count((start, end), &five_min), 7 * 24 * 12);
+        let hour = Timeframe::new(1, TimeframeUnit::Hour).unwrap();
+        assert_eq!(estimate_bucket_count((start, end), &hour), 7 * 24);
+        let day = Timeframe::new(1, TimeframeUnit::Day).unwrap();
+        assert_eq!(estimate_bucket_count((start, end), &day), 7);
+
+        // Partial buckets round up; inverted windows estimate zero.
+        let sliver = utc(2024, 1, 1, 0, 1);
+        assert_eq!(estimate_bucket_count((start, sliver), &hour), 1);
+        assert_eq!(estimate_bucket_count((end, start), &hour), 0);
+    }
+
+    #[test]
+    fn range_is_half_open() {
+        let tf = Timeframe::new(1, TimeframeUnit::Hour).unwrap();
+        let (first, end_ex) =
+            bucket_range(utc(2024, 1, 2, 9, 0), utc(2024, 1, 2, 12, 0), &tf).unwrap();
+        assert_eq!(end_ex - first, 3);
+        assert_eq!(bucket_start(first, &tf), utc(2024, 1, 2, 9, 0));
+    }
+
+    #[test]
+    fn unaligned_start_rounds_up() {
+        let tf = Timeframe::new(1, TimeframeUnit::Hour).unwrap();
+        let (first, _) = bucket_range(utc(2024, 1, 2, 9, 30), utc(2024, 1, 2, 12, 0), &tf).unwrap();
+        assert_eq!(bucket_start(first, &tf), utc(2024, 1, 2, 10, 0));
+    }
+
+    #[test]
+    fn regular_session_has_390_minute_buckets() {
+        // Tuesday 2024-01-02, a normal trading day.
+        let tf = Timeframe::new(1, TimeframeUnit::Minute).unwrap();
+        let window = bucket_range(utc(2024, 1, 2, 0, 0), utc(2024, 1, 3, 0, 0), &tf).unwrap();
+        let bm = session_filter_bitmap(
+            window,
+            &tf,
+            &SessionCalendar::regular(),
+            &HolidayCalendar::empty(),
+            chrono_tz::America::New_York,
+            window.0,
+        )
+        .unwrap();
+        assert_eq!(bm.len(), 390);
+        // First in-session bucket is 09:30 ET = 14:30 UTC.
+        assert_eq!(
+            bucket_start(abs(bm.min().unwrap(), window.0), &tf),
+            utc(2024, 1, 2, 14, 30)
+        );
+    }
+
+    #[test]
+    fn weekends_are_out_of_session() {
+        // Saturday 2024-01-06. (The regular calendar: Friday's extended
+        // session would legitimately spill into Saturday 00:00-01:00 UTC.)
+        let tf = Timeframe::new(1, TimeframeUnit::Minute).unwrap();
+        let window = bucket_range(utc(2024, 1, 6, 0, 0), utc(2024, 1, 7, 0, 0), &tf).unwrap();
+        let bm = session_filter_bitmap(
+            window,
+            &tf,
+            &SessionCalendar::regular(),
+            &HolidayCalendar::empty(),
+            chrono_tz::America::New_York,
+            window.0,
+        )
+        .unwrap();
+        assert!(bm.is_empty());
+    }
+
+    #[test]
+    fn daily_buckets_keep_weekdays_only() {
+        let tf = Timeframe::new(1, TimeframeUnit::Day).unwrap();
+        // Mon 2024-01-08 .. Mon 2024-01-15: 5 weekdays + weekend.
+        let window = bucket_range(utc(2024, 1, 8, 0, 0), utc(2024, 1, 15, 0, 0), &tf).unwrap();
+        let bm = session_filter_bitmap(
+            window,
+            &tf,
+            &SessionCalendar::regular(),
+            &HolidayCalendar::empty(),
+            chrono_tz::America::New_York,
+            window.0,
+        )
+        .unwrap();
+        assert_eq!(bm.len(), 5);
+    }
+
+    #[test]
+    fn relative_ids_work_past_the_u32_boundary() {
+        // Minute buckets pass u32::MAX around year 10136; a base offset
+        // keeps the bitmap ids small.
+        let tf = Timeframe::new(1, TimeframeUnit::Minute).unwrap();
+        let ts = utc(10500, 1, 2, 12, 0);
+        let id = bucket_of(ts, &tf).unwrap();
+        assert!(id > u64::from(u32::MAX));
+        let base = id - 10;
+        assert_eq!(rel(id, base).unwrap(), 10);
+        assert_eq!(abs(10, base), id);
+        assert_eq!(bucket_start(abs(rel(id, base).unwrap(), base), &tf), ts);
+        // A base later than the id cannot be represented.
+        assert!(matches!(
+            rel(id, id + 1),
+            Err(BucketError::RelativeOverflow { .. })
+        ));
+    }
+
+    #[test]
+    fn pre_epoch_rejected() {
+        let tf = Timeframe::new(1, TimeframeUnit::Minute).unwrap();
+        assert!(matches!(
+            bucket_of(utc(1960, 1, 1, 0, 0), &tf),
+            Err(BucketError::PreEpoch(_))
+        ));
+    }
+}
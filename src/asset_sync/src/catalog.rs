@@ -0,0 +1,1643 @@
+//! The declarative TOML catalog and its reconciliation against the DB.
+//!
+//! A catalog file looks like:
+//!
+//! ```toml
+//! [[assets]]
+//! symbol = "AAPL"
+//! asset_class = "us_equity"
+//! provider = "alpaca"
+//! start = "2024-01-01T00:00:00Z"
+//! timeframes = [{ amount = 1, unit = "minute" }, { amount = 1, unit = "day" }]
+//! ```
+
+use chrono::{DateTime, Utc};
+use rusqlite::{Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::repo::{Manifest, NewManifest, RepoError, SqliteRepo};
+use crate::timeframe::{Timeframe, TimeframeError, TimeframeUnit};
+
+#[derive(Debug, Error)]
+pub enum CatalogError {
+    #[error("cannot read catalog file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid catalog TOML: {0}")]
+    Toml(#[from] toml::de::Error),
+    #[error("cannot render catalog TOML: {0}")]
+    TomlSer(#[from] toml::ser::Error),
+    #[error("symbol CSV line {line}: {message}")]
+    Csv { line: usize, message: String },
+    #[error("asset {symbol}: {source}")]
+    InvalidTimeframe {
+        symbol: String,
+        source: TimeframeError,
+    },
+    #[error("asset {symbol}: unknown timeframe unit {unit:?}")]
+    UnknownUnit { symbol: String, unit: String },
+    #[error("asset {symbol}: timeframe rejected by provider rules: {source}")]
+    BadTimeframe {
+        symbol: String,
+        source: market_data_ingestor::models::timeframe::TimeFrameError,
+    },
+    #[error("asset {symbol}: desired_start must precede desired_end")]
+    InvertedWindow { symbol: String },
+    #[error("invalid symbol: {0}")]
+    InvalidSymbol(#[from] market_data_ingestor::models::symbol::SymbolError),
+    #[error("duplicate spec for ({symbol}, {asset_class}, {provider})")]
+    DuplicateSpec {
+        symbol: String,
+        asset_class: String,
+        provider: String,
+    },
+    #[error(
+        "cannot replace: manifest {manifest_id} ({symbol} {timeframe}) \
+         has leased gaps; wait for or revoke its workers first"
+    )]
+    ReplaceInUse {
+        manifest_id: i64,
+        symbol: String,
+        timeframe: String,
+    },
+    #[error(transparent)]
+    Repo(#[from] RepoError),
+}
+
+/// Timeframe as written in the catalog; validated into a [`Timeframe`]
+/// before use.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TimeframeCfg {
+    pub amount: u32,
+    pub unit: String,
+}
+
+impl TimeframeCfg {
+    /// Parse the compact `<amount><unit>` form (`5Minute`, `1day`) used
+    /// by CLI flags and CSV cells. Unit casing is forgiving; whether the
+    /// unit itself is valid is for [`TimeframeCfg::to_timeframe`] and the
+    /// catalog validators to say.
+    pub fn parse_compact(s: &str) -> Option<TimeframeCfg> {
+        let unit_at = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+        let (amount, unit) = s.split_at(unit_at);
+        Some(TimeframeCfg {
+            amount: amount.parse().ok()?,
+            unit: unit.to_ascii_lowercase(),
+        })
+    }
+
+    pub fn to_timeframe(&self) -> Result<Timeframe, TimeframeCfgError> {
+        let unit = match self.unit.as_str() {
+            "minute" => TimeframeUnit::Minute,
+            "hour" => TimeframeUnit::Hour,
+            "day" => TimeframeUnit::Day,
+            other => {
+                return Err(TimeframeCfgError::UnknownUnit {
+                    unit: other.to_string(),
+                });
+            }
+        };
+        Timeframe::new(self.amount, unit).map_err(TimeframeCfgError::Invalid)
+    }
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum TimeframeCfgError {
+    #[error("unknown timeframe unit {unit:?}")]
+    UnknownUnit { unit: String },
+    #[error(transparent)]
+    Invalid(TimeframeError),
+    #[error(transparent)]
+    Provider(market_data_ingestor::models::timeframe::TimeFrameError),
+}
+
+/// The provider-facing timeframe for a catalog entry. This is the one
+/// place the catalog's unit strings map onto
+/// [`market_data_ingestor::models::timeframe::TimeFrameUnit`]; week and
+/// month exist only on the provider side, so they convert here even though
+/// coverage bookkeeping ([`TimeframeCfg::to_timeframe`]) rejects them.
+impl TryFrom<&TimeframeCfg> for market_data_ingestor::models::timeframe::TimeFrame {
+    type Error = TimeframeCfgError;
+
+    fn try_from(cfg: &TimeframeCfg) -> Result<Self, TimeframeCfgError> {
+        use market_data_ingestor::models::timeframe::{TimeFrame, TimeFrameUnit};
+        let unit = match cfg.unit.as_str() {
+            "minute" => TimeFrameUnit::Minute,
+            "hour" => TimeFrameUnit::Hour,
+            "day" => TimeFrameUnit::Day,
+            "week" => TimeFrameUnit::Week,
+            "month" => TimeFrameUnit::Month,
+            other => {
+                return Err(TimeframeCfgError::UnknownUnit {
+                    unit: other.to_string(),
+                });
+            }
+        };
+        TimeFrame::new(cfg.amount, unit).map_err(TimeframeCfgError::Provider)
+    }
+}
+
+impl From<&market_data_ingestor::models::timeframe::TimeFrame> for TimeframeCfg {
+    fn from(tf: &market_data_ingestor::models::timeframe::TimeFrame) -> Self {
+        use market_data_ingestor::models::timeframe::TimeFrameUnit;
+        let unit = match tf.unit() {
+            TimeFrameUnit::Minute => "minute",
+            TimeFrameUnit::Hour => "hour",
+            TimeFrameUnit::Day => "day",
+            TimeFrameUnit::Week => "week",
+            TimeFrameUnit::Month => "month",
+        };
+        TimeframeCfg {
+            amount: tf.amount(),
+            unit: unit.to_string(),
+        }
+    }
+}
+
+/// One declared asset: which symbol to sync from which provider, over which
+/// window, at which timeframes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AssetSpec {
+    pub symbol: String,
+    pub asset_class: String,
+    pub provider: String,
+    pub start: DateTime<Utc>,
+    #[serde(default)]
+    pub end: Option<DateTime<Utc>>,
+    pub timeframes: Vec<TimeframeCfg>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Catalog {
+    #[serde(default)]
+    pub assets: Vec<AssetSpec>,
+}
+
+/// The sync window in [`AssetSpecJson`]: internally tagged so JSON
+/// clients say whether the window is open-ended instead of silently
+/// omitting `end` — `{"type":"open","start":...}` or
+/// `{"type":"closed","start":...,"end":...}`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RangeJson {
+    Open {
+        start: DateTime<Utc>,
+    },
+    Closed {
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+    },
+}
+
+/// JSON wire form of [`AssetSpec`] for HTTP APIs. The TOML catalog keeps
+/// its flat `start`/`end` fields; this DTO exists so REST endpoints
+/// (manifest creation on the status server) take the window as a tagged
+/// [`RangeJson`] object. Conversion is lossless in both directions.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AssetSpecJson {
+    pub symbol: String,
+    pub asset_class: String,
+    pub provider: String,
+    pub range: RangeJson,
+    pub timeframes: Vec<TimeframeCfg>,
+}
+
+impl From<AssetSpec> for AssetSpecJson {
+    fn from(spec: AssetSpec) -> Self {
+        let range = match spec.end {
+            None => RangeJson::Open { start: spec.start },
+            Some(end) => RangeJson::Closed {
+                start: spec.start,
+                end,
+            },
+        };
+        AssetSpecJson {
+            symbol: spec.symbol,
+            asset_class: spec.asset_class,
+            provider: spec.provider,
+            range,
+            timeframes: spec.timeframes,
+        }
+    }
+}
+
+impl From<AssetSpecJson> for AssetSpec {
+    fn from(spec: AssetSpecJson) -> Self {
+        let (start, end) = match spec.range {
+            RangeJson::Open { start } => (start, None),
+            RangeJson::Closed { start, end } => (start, Some(end)),
+        };
+        AssetSpec {
+            symbol: spec.symbol,
+            asset_class: spec.asset_class,
+            provider: spec.provider,
+            start,
+            end,
+            timeframes: spec.timeframes,
+        }
+    }
+}
+
+/// What normalization changed while loading a catalog.
+#[derive(Debug, Default, Clone, Serialize)]
+pub struct NormalizationReport {
+    /// Symbols rewritten to their canonical form, as (raw, canonical).
+    pub renamed: Vec<(String, String)>,
+    /// Duplicate (symbol, asset_class, provider) entries dropped.
+    pub deduped: Vec<String>,
+}
+
+impl NormalizationReport {
+    pub fn is_clean(&self) -> bool {
+        self.renamed.is_empty() && self.deduped.is_empty()
+    }
+
+    /// Human-readable line per finding, for CLI output or sync warnings.
+    pub fn warning_lines(&self) -> Vec<String> {
+        let mut lines = Vec::with_capacity(self.renamed.len() + self.deduped.len());
+        for (raw, canonical) in &self.renamed {
+            lines.push(format!("renamed: {raw:?} -> {canonical}"));
+        }
+        for symbol in &self.deduped {
+            lines.push(format!("dropped duplicate: {symbol}"));
+        }
+        lines
+    }
+}
+
+/// Parse and validate a catalog from TOML text. Symbols are trimmed and
+/// uppercased; exact duplicate specs are dropped.
+pub fn load_catalog_str(s: &str) -> Result<Catalog, CatalogError> {
+    load_catalog_str_reported(s).map(|(catalog, _report)| catalog)
+}
+
+/// Like [`load_catalog_str`], but also returns what normalization changed,
+/// so callers can show users which symbols were rewritten or dropped.
+pub fn load_catalog_str_reported(s: &str) -> Result<(Catalog, NormalizationReport), CatalogError> {
+    load_catalog_str_with_policy(s, DuplicatePolicy::KeepFirst)
+}
+
+/// What to do when two specs canonicalize to the same
+/// `(symbol, asset_class, provider)` key. Duplicates make any reverse
+/// lookup from that key ambiguous, so strict callers error out instead of
+/// trusting file order.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DuplicatePolicy {
+    /// Keep the first spec, drop the rest, and count them in the
+    /// normalization report. The default, matching [`load_catalog_str`].
+    #[default]
+    KeepFirst,
+    /// Refuse the catalog with [`CatalogError::DuplicateSpec`].
+    Error,
+}
+
+/// [`load_catalog_str_reported`] with an explicit duplicate policy.
+pub fn load_catalog_str_with_policy(
+    s: &str,
+    policy: DuplicatePolicy,
+) -> Result<(Catalog, NormalizationReport), CatalogError> {
+    let mut catalog: Catalog = toml::from_str(s)?;
+    let report = normalize(&mut catalog, policy)?;
+    validate(&catalog)?;
+    Ok((catalog, report))
+}
+
+/// Read and parse a catalog file from disk.
+pub fn load_catalog_path(path: &std::path::Path) -> Result<Catalog, CatalogError> {
+    load_catalog_path_reported(path).map(|(catalog, _report)| catalog)
+}
+
+/// [`load_catalog_path`] with the normalization report attached.
+pub fn load_catalog_path_reported(
+    path: &std::path::Path,
+) -> Result<(Catalog, NormalizationReport), CatalogError> {
+    load_catalog_str_reported(&std::fs::read_to_string(path)?)
+}
+
+/// Load `path` if it exists, else fall back to the built-in default
+/// catalog — zero-config startup for new users.
+pub fn load_catalog_or_default(path: &std::path::Path) -> Result<Catalog, CatalogError> {
+    match std::fs::read_to_string(path) {
+        Ok(text) => load_catalog_str(&text),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(config::default_catalog()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Render a one-asset catalog for `spec` as TOML text, validated through
+/// the same path [`load_catalog_str`] uses. The output is a
+/// copy-pasteable starting file: whatever this returns is guaranteed to
+/// load back, so new users do not have to guess the inline-table
+/// `timeframes` shape by hand.
+pub fn scaffold_catalog_toml(spec: AssetSpec) -> Result<String, CatalogError> {
+    let catalog = Catalog { assets: vec![spec] };
+    let rendered = toml::to_string_pretty(&catalog)?;
+    // Round-trip through the loader so validation errors (bad unit, zero
+    // amount, inverted window, malformed symbol) surface here, not when
+    // the user later applies the file.
+    load_catalog_str(&rendered)?;
+    Ok(rendered)
+}
+
+/// Per-spec values a symbol CSV falls back on when a row leaves the
+/// column empty (or the file omits the column entirely).
+#[derive(Debug, Clone)]
+pub struct SpecDefaults {
+    pub provider: String,
+    pub asset_class: String,
+    pub timeframes: Vec<TimeframeCfg>,
+    pub start: DateTime<Utc>,
+    pub end: Option<DateTime<Utc>>,
+}
+
+/// Build validated specs from a symbol CSV — the low-friction way to
+/// onboard a spreadsheet-maintained universe without writing one TOML
+/// block per symbol.
+///
+/// The file needs a header with a `symbol` column; `provider`,
+/// `asset_class`, `timeframe` (compact form, e.g. `5minute`), `start` and
+/// `end` columns are optional and override `defaults` row by row when
+/// non-empty. Dates accept RFC 3339 or a plain `YYYY-MM-DD` (midnight
+/// UTC). The result passes the same normalization and validation as a
+/// loaded catalog; duplicate (symbol, asset_class, provider) rows are an
+/// error rather than silently collapsed, since in a generated list they
+/// are almost certainly typos.
+pub fn specs_from_csv(
+    path: &std::path::Path,
+    defaults: &SpecDefaults,
+) -> Result<Vec<AssetSpec>, CatalogError> {
+    specs_from_csv_str(&std::fs::read_to_string(path)?, defaults)
+}
+
+/// [`specs_from_csv`] on in-memory CSV text.
+pub fn specs_from_csv_str(
+    csv: &str,
+    defaults: &SpecDefaults,
+) -> Result<Vec<AssetSpec>, CatalogError> {
+    let err = |line: usize, message: String| CatalogError::Csv { line, message };
+    let mut lines = csv
+        .lines()
+        .enumerate()
+        .map(|(i, l)| (i + 1, l.trim()))
+        .filter(|(_, l)| !l.is_empty());
+
+    let (header_line, header) = lines.next().ok_or_else(|| err(1, "empty file".into()))?;
+    let columns: Vec<&str> = header.split(',').map(str::trim).collect();
+    for column in &columns {
+        if !matches!(
+            *column,
+            "symbol" | "provider" | "asset_class" | "timeframe" | "start" | "end"
+        ) {
+            return Err(err(header_line, format!("unknown column {column:?}")));
+        }
+    }
+    let index_of = |name: &str| columns.iter().position(|c| *c == name);
+    let symbol_col = index_of("symbol")
+        .ok_or_else(|| err(header_line, "header has no `symbol` column".into()))?;
+
+    let mut assets = Vec::new();
+    for (line, row) in lines {
+        let cells: Vec<&str> = row.split(',').map(str::trim).collect();
+        if cells.len() != columns.len() {
+            return Err(err(
+                line,
+                format!("expected {} cells, found {}", columns.len(), cells.len()),
+            ));
+        }
+        let cell = |col: Option<usize>| col.map(|i| cells[i]).filter(|c| !c.is_empty());
+        let symbol = cell(Some(symbol_col)).ok_or_else(|| err(line, "empty symbol".into()))?;
+        let timeframes =
+            match cell(index_of("timeframe")) {
+                None => defaults.timeframes.clone(),
+                Some(s) => vec![TimeframeCfg::parse_compact(s).ok_or_else(|| {
+                    err(line, format!("timeframe {s:?}: expected <amount><unit>"))
+                })?],
+            };
+        let parse_date = |s: &str| {
+            parse_csv_date(s)
+                .ok_or_else(|| err(line, format!("date {s:?}: expected RFC 3339 or YYYY-MM-DD")))
+        };
+        assets.push(AssetSpec {
+            symbol: symbol.to_string(),
+            asset_class: cell(index_of("asset_class"))
+                .map_or_else(|| defaults.asset_class.clone(), str::to_string),
+            provider: cell(index_of("provider"))
+                .map_or_else(|| defaults.provider.clone(), str::to_string),
+            start: cell(index_of("start"))
+                .map(parse_date)
+                .transpose()?
+                .unwrap_or(defaults.start),
+            end: cell(index_of("end"))
+                .map(parse_date)
+                .transpose()?
+                .or(defaults.end),
+            timeframes,
+        });
+    }
+    if assets.is_empty() {
+        return Err(err(header_line, "header but no data rows".into()));
+    }
+
+    let mut catalog = Catalog { assets };
+    normalize(&mut catalog, DuplicatePolicy::Error)?;
+    validate(&catalog)?;
+    Ok(catalog.assets)
+}
+
+fn parse_csv_date(s: &str) -> Option<DateTime<Utc>> {
+    if let Ok(dt) = DateTime::parse_from_rfc3339(s) {
+        return Some(dt.with_timezone(&Utc));
+    }
+    chrono::NaiveDate::parse_from_str(s, "%Y-%m-%d")
+        .ok()
+        .map(|d| d.and_hms_opt(0, 0, 0).expect("midnight exists").and_utc())
+}
+
+/// Built-in catalog defaults.
+pub mod config {
+    use super::{Catalog, load_catalog_str};
+
+    /// A deliberately small starting point — one liquid equity and one
+    /// crypto pair on Alpaca — not a comprehensive universe. Users are
+    /// expected to replace it with their own TOML.
+    const DEFAULT_CATALOG_TOML: &str = r#"
+        [[assets]]
+        symbol = "SPY"
+        asset_class = "us_equity"
+        provider = "alpaca"
+        start = "2024-01-01T00:00:00Z"
+        timeframes = [{ amount = 1, unit = "minute" }, { amount = 1, unit = "day" }]
+
+        [[assets]]
+        symbol = "BTC/USD"
+        asset_class = "crypto"
+        provider = "alpaca"
+        start = "2024-01-01T00:00:00Z"
+        timeframes = [{ amount = 1, unit = "hour" }]
+    "#;
+
+    /// The normalized built-in default catalog.
+    pub fn default_catalog() -> Catalog {
+        load_catalog_str(DEFAULT_CATALOG_TOML).expect("embedded default catalog is valid")
+    }
+}
+
+fn normalize(
+    catalog: &mut Catalog,
+    policy: DuplicatePolicy,
+) -> Result<NormalizationReport, CatalogError> {
+    let mut report = NormalizationReport::default();
+    let mut seen = std::collections::HashSet::new();
+    let mut kept = Vec::with_capacity(catalog.assets.len());
+    for mut spec in catalog.assets.drain(..) {
+        let canonical = market_data_ingestor::models::symbol::canonicalize(&spec.symbol)?;
+        if canonical != spec.symbol {
+            report
+                .renamed
+                .push((spec.symbol.clone(), canonical.clone()));
+            spec.symbol = canonical;
+        }
+        let key = (
+            spec.symbol.clone(),
+            spec.asset_class.clone(),
+            spec.provider.clone(),
+        );
+        if !seen.insert(key) {
+            if policy == DuplicatePolicy::Error {
+                return Err(CatalogError::DuplicateSpec {
+                    symbol: spec.symbol,
+                    asset_class: spec.asset_class,
+                    provider: spec.provider,
+                });
+            }
+            report.deduped.push(spec.symbol.clone());
+            continue;
+        }
+        kept.push(spec);
+    }
+    catalog.assets = kept;
+    Ok(report)
+}
+
+fn validate(catalog: &Catalog) -> Result<(), CatalogError> {
+    for spec in &catalog.assets {
+        for tf in &spec.timeframes {
+            tf.to_timeframe().map_err(|e| match e {
+                TimeframeCfgError::UnknownUnit { unit } => CatalogError::UnknownUnit {
+                    symbol: spec.symbol.clone(),
+                    unit,
+                },
+                TimeframeCfgError::Invalid(source) => CatalogError::InvalidTimeframe {
+                    symbol: spec.symbol.clone(),
+                    source,
+                },
+                TimeframeCfgError::Provider(_) => {
+                    unreachable!("to_timeframe never consults the provider")
+                }
+            })?;
+            // Also hold specs to the provider-side amount rules (e.g. at
+            // most 59 minutes), so a catalog cannot declare an interval
+            // every fetch would then be rejected for.
+            match market_data_ingestor::models::timeframe::TimeFrame::try_from(tf) {
+                Ok(_) | Err(TimeframeCfgError::UnknownUnit { .. }) => {}
+                Err(TimeframeCfgError::Provider(source)) => {
+                    return Err(CatalogError::BadTimeframe {
+                        symbol: spec.symbol.clone(),
+                        source,
+                    });
+                }
+                Err(TimeframeCfgError::Invalid(_)) => {
+                    unreachable!("TryFrom never returns the coverage-side error")
+                }
+            }
+        }
+        if let Some(end) = spec.end
+            && spec.start >= end
+        {
+            return Err(CatalogError::InvertedWindow {
+                symbol: spec.symbol.clone(),
+            });
+        }
+    }
+    Ok(())
+}
+
+/// Outcome of reconciling a catalog against the DB.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct SyncDiff {
+    pub assets_created: usize,
+    pub manifests_upserted: usize,
+    /// Declared manifests whose stored row already matched; no write was
+    /// issued for them.
+    pub manifests_unchanged: usize,
+    pub manifests_closed: usize,
+    /// Undeclared manifests deleted outright; only [`SyncMode::Replace`]
+    /// produces these.
+    pub manifests_deleted: usize,
+    /// Assets left with no manifests and deleted; [`SyncMode::Replace`]
+    /// only.
+    pub assets_deleted: usize,
+    /// Non-fatal findings, e.g. timeframes the declared provider's live
+    /// API would reject.
+    pub warnings: Vec<String>,
+}
+
+/// Check every declared (provider, timeframe) pair against that
+/// provider's own timeframe validator, so the catalog cannot quietly
+/// advertise intervals the live API rejects. Providers without a known
+/// validator are skipped. Returns one warning string per mismatch.
+pub fn verify_against_providers(catalog: &Catalog) -> Vec<String> {
+    use market_data_ingestor::models::timeframe as ingestor_tf;
+
+    let mut warnings = Vec::new();
+    for spec in &catalog.assets {
+        if spec.provider != "alpaca" {
+            continue;
+        }
+        for tf_cfg in &spec.timeframes {
+            match ingestor_tf::TimeFrame::try_from(tf_cfg) {
+                Ok(_) => {}
+                // Unknown units are caught by catalog validation.
+                Err(TimeframeCfgError::UnknownUnit { .. }) => {}
+                Err(e) => warnings.push(format!(
+                    "{}: timeframe {}{} not accepted by provider alpaca: {e}",
+                    spec.symbol, tf_cfg.amount, tf_cfg.unit
+                )),
+            }
+        }
+    }
+    warnings
+}
+
+/// Severity of a [`LintFinding`]. Errors describe catalogs that will
+/// misbehave once synced — silently dropped specs, timeframes every
+/// fetch would reject, specs that declare no work. Warnings are things
+/// the loader fixes or tolerates on its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LintLevel {
+    Warning,
+    Error,
+}
+
+/// One finding from [`lint`].
+#[derive(Debug, Clone)]
+pub struct LintFinding {
+    pub level: LintLevel,
+    pub message: String,
+}
+
+impl LintFinding {
+    fn error(message: String) -> Self {
+        LintFinding {
+            level: LintLevel::Error,
+            message,
+        }
+    }
+
+    fn warning(message: String) -> Self {
+        LintFinding {
+            level: LintLevel::Warning,
+            message,
+        }
+    }
+}
+
+/// Inspect a catalog for mistakes the loader tolerates. Run this on the
+/// catalog as parsed (before [`load_catalog_str`]'s normalization), so
+/// the duplicates that loading would silently drop are still visible.
+///
+/// Errors: duplicate specs (the later one is dropped on load, losing its
+/// window and timeframes), specs with no timeframes (sync creates the
+/// asset but no manifests — a silent no-op), validation failures, and
+/// timeframes the declared provider's API rejects. Warnings: symbols the
+/// loader will rewrite to canonical form, providers whose timeframe
+/// rules are unknown, and an empty catalog.
+pub fn lint(catalog: &Catalog) -> Vec<LintFinding> {
+    let mut findings = Vec::new();
+    if catalog.assets.is_empty() {
+        findings.push(LintFinding::warning(
+            "catalog declares no assets; syncing it closes every open manifest".to_string(),
+        ));
+    }
+
+    // Replay normalization on a copy: renames are cosmetic, but each
+    // dropped duplicate silently loses whichever spec came later.
+    let mut copy = catalog.clone();
+    match normalize(&mut copy, DuplicatePolicy::KeepFirst) {
+        Ok(report) => {
+            for (raw, canonical) in &report.renamed {
+                findings.push(LintFinding::warning(format!(
+                    "symbol {raw:?} will be rewritten to {canonical} on load"
+                )));
+            }
+            for symbol in &report.deduped {
+                findings.push(LintFinding::error(format!(
+                    "duplicate spec for {symbol}: only the first is kept, \
+                     the rest are dropped on load"
+                )));
+            }
+            if let Err(e) = validate(&copy) {
+                findings.push(LintFinding::error(e.to_string()));
+            }
+        }
+        Err(e) => findings.push(LintFinding::error(e.to_string())),
+    }
+
+    for spec in &catalog.assets {
+        if spec.timeframes.is_empty() {
+            findings.push(LintFinding::error(format!(
+                "{}: no timeframes declared, so sync creates no manifests for it",
+                spec.symbol
+            )));
+        }
+        if spec.provider != "alpaca" {
+            findings.push(LintFinding::warning(format!(
+                "{}: no timeframe rules known for provider {:?}; cannot check its intervals",
+                spec.symbol, spec.provider
+            )));
+        }
+    }
+
+    // Sync only warns about these, but a timeframe the provider rejects
+    // means every one of its fetches fails — for a lint that is an error.
+    for warning in verify_against_providers(catalog) {
+        findings.push(LintFinding::error(warning));
+    }
+    findings
+}
+
+/// One manifest a catalog declares should exist, in DB terms: the conflict
+/// key plus the desired window. The loaded-catalog side of a diff, usable
+/// by tools (an admin UI, a linter) that never apply anything.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WantedManifest {
+    pub symbol: String,
+    pub asset_class: String,
+    pub provider: String,
+    pub timeframe: Timeframe,
+    pub desired_start: DateTime<Utc>,
+    pub desired_end: Option<DateTime<Utc>>,
+}
+
+/// Flatten a validated catalog into the manifests it wants, one per
+/// declared (asset, provider, timeframe).
+pub fn wanted_manifests(catalog: &Catalog) -> Vec<WantedManifest> {
+    let mut wanted = Vec::new();
+    for spec in &catalog.assets {
+        for tf_cfg in &spec.timeframes {
+            wanted.push(WantedManifest {
+                symbol: spec.symbol.clone(),
+                asset_class: spec.asset_class.clone(),
+                provider: spec.provider.clone(),
+                timeframe: tf_cfg
+                    .to_timeframe()
+                    .expect("validated by load_catalog_str"),
+                desired_start: spec.start,
+                desired_end: spec.end,
+            });
+        }
+    }
+    wanted
+}
+
+/// The DB side of a diff: every manifest row, whatever its status. Closed
+/// rows matter because re-declaring one is an update (reopen), not a
+/// create.
+pub fn current_manifests(conn: &Connection) -> Result<Vec<Manifest>, CatalogError> {
+    Ok(SqliteRepo::manifests_all(conn)?)
+}
+
+impl From<&Timeframe> for TimeframeCfg {
+    fn from(tf: &Timeframe) -> Self {
+        TimeframeCfg {
+            amount: tf.amount(),
+            unit: tf.unit().as_str().to_string(),
+        }
+    }
+}
+
+/// Rebuild the catalog declaration a manifest row came from — the inverse
+/// of what [`sync_catalog`] writes. A NULL `desired_end` is an open-ended
+/// window, anything else closed, the same distinction [`RangeJson`] keeps
+/// on the wire; `From<AssetSpec>` then yields the tagged JSON form, so a
+/// backup or `list` tool can emit one spec per row. Sync flattens a spec
+/// into one manifest per timeframe, so the returned spec declares just
+/// this row's; regrouping rows by asset is the exporter's call.
+pub fn manifest_to_spec(manifest: &Manifest) -> AssetSpec {
+    AssetSpec {
+        symbol: manifest.symbol.clone(),
+        asset_class: manifest.asset_class.clone(),
+        provider: manifest.provider.clone(),
+        start: manifest.desired_start,
+        end: manifest.desired_end,
+        timeframes: vec![TimeframeCfg::from(&manifest.timeframe)],
+    }
+}
+
+/// Pure diff of wanted against current — no database access, so callers
+/// can compute it from any snapshot.
+pub fn diff_manifests(current: &[Manifest], wanted: &[WantedManifest]) -> SyncPreview {
+    let mut preview = SyncPreview::default();
+    let mut matched = std::collections::HashSet::new();
+    for want in wanted {
+        let label = format!("{} {} {}", want.symbol, want.provider, want.timeframe);
+        let existing = current.iter().find(|m| {
+            m.symbol == want.symbol
+                && m.asset_class == want.asset_class
+                && m.provider == want.provider
+                && m.timeframe == want.timeframe
+        });
+        match existing {
+            Some(m) => {
+                matched.insert(m.manifest_id);
+                preview.updated.push(label);
+            }
+            None => preview.created.push(label),
+        }
+    }
+    for m in current {
+        if m.status == crate::repo::ManifestStatus::Open && !matched.contains(&m.manifest_id) {
+            preview
+                .orphaned
+                .push(format!("{} {} {}", m.symbol, m.provider, m.timeframe));
+        }
+    }
+    preview
+}
+
+/// What [`sync_catalog`] would do, computed without writing. Each entry is
+/// a `"SYMBOL provider timeframe"` label.
+#[derive(Debug, Default, PartialEq, Eq, Serialize)]
+pub struct SyncPreview {
+    /// Declared manifests with no row under their conflict key yet.
+    pub created: Vec<String>,
+    /// Declared manifests that already exist and would be refreshed.
+    pub updated: Vec<String>,
+    /// Open manifests the catalog no longer declares; sync would close them.
+    pub orphaned: Vec<String>,
+}
+
+/// Dry-run counterpart of [`sync_catalog`]: report which manifests would be
+/// created, updated, or closed, without touching the database. Lets an
+/// operator preview the effect of a catalog edit before applying it.
+pub fn preview_sync(conn: &Connection, catalog: &Catalog) -> Result<SyncPreview, CatalogError> {
+    Ok(diff_manifests(
+        &current_manifests(conn)?,
+        &wanted_manifests(catalog),
+    ))
+}
+
+/// What [`sync_catalog_with`] does about rows the catalog no longer
+/// declares.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncMode {
+    /// Upsert what the catalog declares and touch nothing else; undeclared
+    /// manifests keep fetching.
+    Additive,
+    /// Additive, plus close open manifests the catalog dropped — the
+    /// long-standing [`sync_catalog`] behavior. Closed rows keep their
+    /// coverage and gaps.
+    Prune,
+    /// Make the DB mirror the catalog exactly: undeclared manifests are
+    /// deleted outright with their coverage, gaps, and profiles, then
+    /// assets and symbol-map rows nothing references anymore. Refuses
+    /// with [`CatalogError::ReplaceInUse`] — rolling the whole sync back
+    /// — if a doomed manifest still has leased gaps, since a worker is
+    /// mid-fetch against it.
+    Replace,
+}
+
+/// Reconcile the catalog with the `manifests` table: upsert a manifest per
+/// declared (asset, provider, timeframe), and close open manifests no
+/// longer declared. Coverage and gaps of closed manifests are retained.
+/// Equivalent to [`sync_catalog_with`] under [`SyncMode::Prune`].
+pub fn sync_catalog(conn: &Connection, catalog: &Catalog) -> Result<SyncDiff, CatalogError> {
+    sync_catalog_with(conn, catalog, SyncMode::Prune)
+}
+
+/// [`sync_catalog`] with the orphan policy chosen by the caller; see
+/// [`SyncMode`]. The whole reconciliation runs in one immediate
+/// transaction, so a refused replace leaves the DB untouched.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(skip(conn, catalog), fields(assets = catalog.assets.len()))
+)]
+pub fn sync_catalog_with(
+    conn: &Connection,
+    catalog: &Catalog,
+    mode: SyncMode,
+) -> Result<SyncDiff, CatalogError> {
+    let mut diff = SyncDiff {
+        warnings: verify_against_providers(catalog),
+        ..SyncDiff::default()
+    };
+    let mut wanted = std::collections::HashSet::new();
+
+    let tx = conn.unchecked_transaction().map_err(RepoError::from)?;
+    for spec in &catalog.assets {
+        let before: i64 = tx
+            .query_row("SELECT count(*) FROM assets", [], |r| r.get(0))
+            .map_err(RepoError::from)?;
+        let asset_id = SqliteRepo::upsert_asset(&tx, &spec.symbol, &spec.asset_class)?;
+        let after: i64 = tx
+            .query_row("SELECT count(*) FROM assets", [], |r| r.get(0))
+            .map_err(RepoError::from)?;
+        if after > before {
+            diff.assets_created += 1;
+            crate::audit::record(
+                &tx,
+                &crate::audit::AuditEntry {
+                    actor: &crate::audit::current_actor(),
+                    action: "asset.create",
+                    entity: format!("asset:{}", spec.symbol),
+                    detail: serde_json::json!({ "asset_class": spec.asset_class }),
+                },
+            )?;
+        }
+
+        for tf_cfg in &spec.timeframes {
+            let timeframe = tf_cfg
+                .to_timeframe()
+                .expect("validated by load_catalog_str");
+            // Re-upserting an unchanged row rewrites it (and appends an
+            // audit entry) for nothing; on a large catalog that is pure
+            // WAL churn. Skip rows the DB already has in the desired state.
+            let existing: Option<(i64, String, Option<String>, String)> = tx
+                .query_row(
+                    "SELECT manifest_id, desired_start, desired_end, status FROM manifests
+                     WHERE asset_id = ?1 AND provider = ?2 AND tf_amount = ?3 AND tf_unit = ?4",
+                    rusqlite::params![
+                        asset_id,
+                        spec.provider,
+                        timeframe.amount(),
+                        timeframe.unit().as_str(),
+                    ],
+                    |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?, r.get(3)?)),
+                )
+                .optional()
+                .map_err(RepoError::from)?;
+            if let Some((id, start, end, status)) = existing
+                && start == spec.start.to_rfc3339()
+                && end == spec.end.map(|t| t.to_rfc3339())
+                && status == "open"
+            {
+                wanted.insert(id);
+                diff.manifests_unchanged += 1;
+                continue;
+            }
+            let id = SqliteRepo::upsert_manifest(
+                &tx,
+                &NewManifest {
+                    asset_id,
+                    provider: spec.provider.clone(),
+                    timeframe,
+                    desired_start: spec.start,
+                    desired_end: spec.end,
+                },
+            )?;
+            wanted.insert(id);
+            diff.manifests_upserted += 1;
+        }
+    }
+
+    match mode {
+        SyncMode::Additive => {}
+        SyncMode::Prune => {
+            for manifest in SqliteRepo::manifests_open(&tx)? {
+                if !wanted.contains(&manifest.manifest_id) {
+                    SqliteRepo::close_manifest(&tx, manifest.manifest_id)?;
+                    crate::audit::record(
+                        &tx,
+                        &crate::audit::AuditEntry {
+                            actor: &crate::audit::current_actor(),
+                            action: "manifest.close",
+                            entity: format!("manifest:{}", manifest.manifest_id),
+                            detail: serde_json::json!({
+                                "symbol": manifest.symbol,
+                                "timeframe": manifest.timeframe.to_string(),
+                            }),
+                        },
+                    )?;
+                    diff.manifests_closed += 1;
+                }
+            }
+        }
+        SyncMode::Replace => {
+            // Children before parents: coverage/gaps/profiles hang off the
+            // manifest, manifests off the asset. Undeclared closed rows go
+            // too — replace means the catalog is the whole truth.
+            for manifest in SqliteRepo::manifests_all(&tx)? {
+                if wanted.contains(&manifest.manifest_id) {
+                    continue;
+                }
+                let leased: i64 = tx
+                    .query_row(
+                        "SELECT count(*) FROM gaps WHERE manifest_id = ?1 AND state = 'leased'",
+                        [manifest.manifest_id],
+                        |r| r.get(0),
+                    )
+                    .map_err(RepoError::from)?;
+                if leased > 0 {
+                    return Err(CatalogError::ReplaceInUse {
+                        manifest_id: manifest.manifest_id,
+                        symbol: manifest.symbol,
+                        timeframe: manifest.timeframe.to_string(),
+                    });
+                }
+                for table in ["coverage", "gaps", "asset_profile"] {
+                    tx.execute(
+                        &format!("DELETE FROM {table} WHERE manifest_id = ?1"),
+                        [manifest.manifest_id],
+                    )
+                    .map_err(RepoError::from)?;
+                }
+                tx.execute(
+                    "DELETE FROM manifests WHERE manifest_id = ?1",
+                    [manifest.manifest_id],
+                )
+                .map_err(RepoError::from)?;
+                crate::audit::record(
+                    &tx,
+                    &crate::audit::AuditEntry {
+                        actor: &crate::audit::current_actor(),
+                        action: "manifest.delete",
+                        entity: format!("manifest:{}", manifest.manifest_id),
+                        detail: serde_json::json!({
+                            "symbol": manifest.symbol,
+                            "timeframe": manifest.timeframe.to_string(),
+                        }),
+                    },
+                )?;
+                diff.manifests_deleted += 1;
+            }
+            diff.assets_deleted = tx
+                .execute(
+                    "DELETE FROM assets
+                     WHERE asset_id NOT IN (SELECT asset_id FROM manifests)",
+                    [],
+                )
+                .map_err(RepoError::from)?;
+            let declared: std::collections::HashSet<(String, String)> = catalog
+                .assets
+                .iter()
+                .map(|spec| (spec.provider.clone(), spec.symbol.clone()))
+                .collect();
+            let mut stmt = tx
+                .prepare("SELECT provider, canonical FROM symbol_map")
+                .map_err(RepoError::from)?;
+            let mappings = stmt
+                .query_map([], |r| Ok((r.get::<_, String>(0)?, r.get::<_, String>(1)?)))
+                .map_err(RepoError::from)?
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(RepoError::from)?;
+            drop(stmt);
+            for (provider, canonical) in mappings {
+                if !declared.contains(&(provider.clone(), canonical.clone())) {
+                    tx.execute(
+                        "DELETE FROM symbol_map WHERE provider = ?1 AND canonical = ?2",
+                        rusqlite::params![provider, canonical],
+                    )
+                    .map_err(RepoError::from)?;
+                }
+            }
+        }
+    }
+    tx.commit().map_err(RepoError::from)?;
+    #[cfg(feature = "tracing")]
+    tracing::info!(
+        assets_created = diff.assets_created,
+        manifests_upserted = diff.manifests_upserted,
+        manifests_unchanged = diff.manifests_unchanged,
+        manifests_closed = diff.manifests_closed,
+        "catalog synced"
+    );
+    Ok(diff)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repo::ManifestStatus;
+    use crate::repo::test_support::mem_conn;
+
+    const CATALOG: &str = r#"
+        [[assets]]
+        symbol = " aapl "
+        asset_class = "us_equity"
+        provider = "alpaca"
+        start = "2024-01-01T00:00:00Z"
+        timeframes = [{ amount = 1, unit = "minute" }, { amount = 1, unit = "day" }]
+    "#;
+
+    #[test]
+    fn load_normalizes_symbols() {
+        let catalog = load_catalog_str(CATALOG).unwrap();
+        assert_eq!(catalog.assets[0].symbol, "AAPL");
+    }
+
+    #[test]
+    fn default_catalog_is_valid_and_used_as_fallback() {
+        let default = config::default_catalog();
+        assert!(!default.assets.is_empty());
+        assert!(default.assets.iter().all(|a| a.provider == "alpaca"));
+
+        let missing = std::path::Path::new("/nonexistent/catalog.toml");
+        let catalog = load_catalog_or_default(missing).unwrap();
+        assert_eq!(catalog.assets.len(), default.assets.len());
+    }
+
+    #[test]
+    fn reported_load_surfaces_normalization() {
+        let doubled = format!("{CATALOG}\n{}", CATALOG.replace(" aapl ", "AAPL"));
+        let (catalog, report) = load_catalog_str_reported(&doubled).unwrap();
+        assert_eq!(catalog.assets.len(), 1);
+        assert!(!report.is_clean());
+        let lines = report.warning_lines();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].contains("renamed"), "{lines:?}");
+        assert!(lines[1].contains("duplicate"), "{lines:?}");
+    }
+
+    #[test]
+    fn strict_policy_rejects_duplicate_specs() {
+        let doubled = format!("{CATALOG}\n{}", CATALOG.replace(" aapl ", "AAPL"));
+        let err = load_catalog_str_with_policy(&doubled, DuplicatePolicy::Error).unwrap_err();
+        match err {
+            CatalogError::DuplicateSpec {
+                symbol,
+                asset_class,
+                provider,
+            } => {
+                assert_eq!(symbol, "AAPL");
+                assert_eq!(asset_class, "us_equity");
+                assert_eq!(provider, "alpaca");
+            }
+            other => panic!("expected DuplicateSpec, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn scaffolded_toml_round_trips_through_the_loader() {
+        let spec = AssetSpec {
+            symbol: "AAPL".to_string(),
+            asset_class: "us_equity".to_string(),
+            provider: "alpaca".to_string(),
+            start: "2024-01-01T00:00:00Z".parse().unwrap(),
+            end: None,
+            timeframes: vec![TimeframeCfg {
+                amount: 5,
+                unit: "minute".to_string(),
+            }],
+        };
+        let rendered = scaffold_catalog_toml(spec).unwrap();
+
+        let loaded = load_catalog_str(&rendered).unwrap();
+        assert_eq!(loaded.assets.len(), 1);
+        let asset = &loaded.assets[0];
+        assert_eq!(asset.symbol, "AAPL");
+        assert_eq!(asset.provider, "alpaca");
+        assert_eq!(asset.start.to_rfc3339(), "2024-01-01T00:00:00+00:00");
+        assert_eq!(asset.timeframes[0].to_timeframe().unwrap().minutes(), 5);
+    }
+
+    #[test]
+    fn scaffold_rejects_invalid_specs_up_front() {
+        let spec = AssetSpec {
+            symbol: "AAPL".to_string(),
+            asset_class: "us_equity".to_string(),
+            provider: "alpaca".to_string(),
+            start: "2024-01-01T00:00:00Z".parse().unwrap(),
+            end: None,
+            timeframes: vec![TimeframeCfg {
+                amount: 1,
+                unit: "fortnight".to_string(),
+            }],
+        };
+        assert!(matches!(
+            scaffold_catalog_toml(spec),
+            Err(CatalogError::UnknownUnit { .. })
+        ));
+    }
+
+    fn csv_defaults() -> SpecDefaults {
+        SpecDefaults {
+            provider: "alpaca".to_string(),
+            asset_class: "us_equity".to_string(),
+            timeframes: vec![TimeframeCfg {
+                amount: 1,
+                unit: "day".to_string(),
+            }],
+            start: "2024-01-01T00:00:00Z".parse().unwrap(),
+            end: None,
+        }
+    }
+
+    #[test]
+    fn csv_rows_inherit_defaults_and_override_per_cell() {
+        let csv = "symbol, provider, asset_class, timeframe, start\n\
+                   aapl,,,,\n\
+                   MSFT,,,5minute,2024-03-01\n\
+                   BTC/USD,alpaca,crypto,1hour,2023-06-01T12:00:00Z\n";
+        let specs = specs_from_csv_str(csv, &csv_defaults()).unwrap();
+        assert_eq!(specs.len(), 3);
+
+        // Row 1: everything from the defaults, symbol canonicalized.
+        assert_eq!(specs[0].symbol, "AAPL");
+        assert_eq!(specs[0].provider, "alpaca");
+        assert_eq!(specs[0].timeframes, csv_defaults().timeframes);
+        assert_eq!(specs[0].start, csv_defaults().start);
+
+        // Row 2: timeframe and plain-date start override.
+        assert_eq!(
+            specs[1].timeframes,
+            vec![TimeframeCfg {
+                amount: 5,
+                unit: "minute".to_string(),
+            }]
+        );
+        assert_eq!(
+            specs[1].start,
+            "2024-03-01T00:00:00Z".parse::<DateTime<Utc>>().unwrap()
+        );
+
+        // Row 3: full per-row override.
+        assert_eq!(specs[2].asset_class, "crypto");
+        assert_eq!(
+            specs[2].start,
+            "2023-06-01T12:00:00Z".parse::<DateTime<Utc>>().unwrap()
+        );
+    }
+
+    #[test]
+    fn csv_with_no_data_rows_is_an_error() {
+        let err = specs_from_csv_str("symbol,provider\n", &csv_defaults()).unwrap_err();
+        match err {
+            CatalogError::Csv { line, message } => {
+                assert_eq!(line, 1);
+                assert!(message.contains("no data rows"), "{message}");
+            }
+            other => panic!("expected Csv error, got {other}"),
+        }
+        // Bad rows are located by line number too.
+        let err = specs_from_csv_str("symbol\nAAPL\n\nAAPL,extra\n", &csv_defaults()).unwrap_err();
+        assert!(matches!(err, CatalogError::Csv { line: 4, .. }), "{err}");
+    }
+
+    fn raw_catalog(toml_text: &str) -> Catalog {
+        toml::from_str(toml_text).unwrap()
+    }
+
+    #[test]
+    fn lint_flags_silent_drops_as_errors_and_renames_as_warnings() {
+        let doubled = format!("{CATALOG}\n{}", CATALOG.replace(" aapl ", "AAPL"));
+        let findings = lint(&raw_catalog(&doubled));
+        let errors: Vec<_> = findings
+            .iter()
+            .filter(|f| f.level == LintLevel::Error)
+            .collect();
+        let warnings: Vec<_> = findings
+            .iter()
+            .filter(|f| f.level == LintLevel::Warning)
+            .collect();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].message.contains("duplicate spec for AAPL"));
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].message.contains("rewritten to AAPL"));
+    }
+
+    #[test]
+    fn lint_flags_specs_that_declare_no_work() {
+        let empty_tf = CATALOG.replace(
+            "timeframes = [{ amount = 1, unit = \"minute\" }, { amount = 1, unit = \"day\" }]",
+            "timeframes = []",
+        );
+        let findings = lint(&raw_catalog(&empty_tf));
+        assert!(
+            findings
+                .iter()
+                .any(|f| f.level == LintLevel::Error && f.message.contains("no timeframes")),
+            "{findings:?}"
+        );
+    }
+
+    #[test]
+    fn lint_escalates_provider_rejected_timeframes_to_errors() {
+        let wide = CATALOG.replace(
+            "amount = 1, unit = \"minute\"",
+            "amount = 120, unit = \"minute\"",
+        );
+        let findings = lint(&raw_catalog(&wide));
+        assert!(
+            findings.iter().any(|f| f.level == LintLevel::Error
+                && f.message.contains("not accepted by provider alpaca")),
+            "{findings:?}"
+        );
+    }
+
+    #[test]
+    fn lint_warns_on_unknown_providers_and_empty_catalogs() {
+        let other = CATALOG.replace("alpaca", "polygon");
+        let findings = lint(&raw_catalog(&other));
+        assert!(
+            findings.iter().any(
+                |f| f.level == LintLevel::Warning && f.message.contains("provider \"polygon\"")
+            ),
+            "{findings:?}"
+        );
+
+        let findings = lint(&Catalog::default());
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].level, LintLevel::Warning);
+        assert!(findings[0].message.contains("no assets"));
+
+        // A clean catalog lints clean.
+        assert!(lint(&load_catalog_str(CATALOG).unwrap()).is_empty());
+    }
+
+    #[test]
+    fn load_rejects_invalid_symbol() {
+        let bad = CATALOG.replace(" aapl ", "AA PL");
+        assert!(matches!(
+            load_catalog_str(&bad),
+            Err(CatalogError::InvalidSymbol(_))
+        ));
+    }
+
+    #[test]
+    fn load_rejects_unknown_unit() {
+        let bad = CATALOG.replace("minute", "fortnight");
+        assert!(matches!(
+            load_catalog_str(&bad),
+            Err(CatalogError::UnknownUnit { .. })
+        ));
+    }
+
+    #[test]
+    fn provider_timeframe_round_trips_every_unit() {
+        use market_data_ingestor::models::timeframe::TimeFrame;
+
+        for unit in ["minute", "hour", "day", "week", "month"] {
+            let cfg = TimeframeCfg {
+                amount: 1,
+                unit: unit.to_string(),
+            };
+            let tf = TimeFrame::try_from(&cfg).unwrap();
+            assert_eq!(TimeframeCfg::from(&tf), cfg, "unit {unit}");
+        }
+    }
+
+    #[test]
+    fn provider_timeframe_rejects_unknown_units() {
+        use market_data_ingestor::models::timeframe::TimeFrame;
+
+        let cfg = TimeframeCfg {
+            amount: 1,
+            unit: "fortnight".to_string(),
+        };
+        let err = TimeFrame::try_from(&cfg).unwrap_err();
+        assert_eq!(err.to_string(), "unknown timeframe unit \"fortnight\"");
+    }
+
+    #[test]
+    fn load_rejects_provider_invalid_timeframe_amounts() {
+        // 61-minute bars are fine for coverage bookkeeping but Alpaca
+        // only accepts 1..=59 minutes; loading must fail, not warn.
+        let bad = CATALOG.replace(
+            "amount = 1, unit = \"minute\"",
+            "amount = 61, unit = \"minute\"",
+        );
+        let err = load_catalog_str(&bad).unwrap_err();
+        assert!(matches!(err, CatalogError::BadTimeframe { .. }), "{err}");
+        assert!(err.to_string().contains("1..=59"), "{err}");
+    }
+
+    #[test]
+    fn provider_rejected_timeframes_surface_as_warnings() {
+        // Load-time validation rejects these outright; the sync-time check
+        // remains the safety net for catalogs assembled in code.
+        let mut catalog = load_catalog_str(CATALOG).unwrap();
+        catalog.assets[0].timeframes[0].amount = 120;
+        let warnings = verify_against_providers(&catalog);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("AAPL"), "{warnings:?}");
+        assert!(warnings[0].contains("120minute"), "{warnings:?}");
+
+        let conn = mem_conn();
+        let diff = sync_catalog(&conn, &catalog).unwrap();
+        assert_eq!(diff.warnings, warnings);
+    }
+
+    #[test]
+    fn sync_upserts_then_closes_removed_manifests() {
+        let conn = mem_conn();
+        let catalog = load_catalog_str(CATALOG).unwrap();
+        let diff = sync_catalog(&conn, &catalog).unwrap();
+        assert_eq!(diff.assets_created, 1);
+        assert_eq!(diff.manifests_upserted, 2);
+        assert_eq!(diff.manifests_closed, 0);
+
+        // Drop the daily timeframe: its manifest should close.
+        let mut smaller = catalog.clone();
+        smaller.assets[0].timeframes.truncate(1);
+        let diff2 = sync_catalog(&conn, &smaller).unwrap();
+        assert_eq!(diff2.manifests_upserted, 0);
+        assert_eq!(diff2.manifests_unchanged, 1);
+        assert_eq!(diff2.manifests_closed, 1);
+
+        let open: Vec<_> = SqliteRepo::manifests_all(&conn)
+            .unwrap()
+            .into_iter()
+            .filter(|m| m.status == ManifestStatus::Open)
+            .collect();
+        assert_eq!(open.len(), 1);
+    }
+
+    const TWO_ASSET_CATALOG: &str = r#"
+        [[assets]]
+        symbol = "AAPL"
+        asset_class = "us_equity"
+        provider = "alpaca"
+        start = "2024-01-01T00:00:00Z"
+        timeframes = [{ amount = 1, unit = "minute" }]
+
+        [[assets]]
+        symbol = "MSFT"
+        asset_class = "us_equity"
+        provider = "alpaca"
+        start = "2024-01-01T00:00:00Z"
+        timeframes = [{ amount = 1, unit = "minute" }]
+    "#;
+
+    #[test]
+    fn replace_deletes_undeclared_rows_outright() {
+        let conn = mem_conn();
+        let catalog = load_catalog_str(TWO_ASSET_CATALOG).unwrap();
+        sync_catalog(&conn, &catalog).unwrap();
+        SqliteRepo::symbol_map_set(&conn, "alpaca", "AAPL", "AAPL.US").unwrap();
+        SqliteRepo::symbol_map_set(&conn, "alpaca", "MSFT", "MSFT.US").unwrap();
+
+        // Re-declare only AAPL: replace must remove MSFT's manifest, its
+        // asset row, and its symbol mapping, while AAPL's survive.
+        let mut only_aapl = catalog.clone();
+        only_aapl.assets.truncate(1);
+        let diff = sync_catalog_with(&conn, &only_aapl, SyncMode::Replace).unwrap();
+        assert_eq!(diff.manifests_deleted, 1);
+        assert_eq!(diff.assets_deleted, 1);
+
+        let remaining = SqliteRepo::manifests_all(&conn).unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].symbol, "AAPL");
+        let assets: i64 = conn
+            .query_row("SELECT count(*) FROM assets", [], |r| r.get(0))
+            .unwrap();
+        assert_eq!(assets, 1);
+        let mapped: Vec<String> = conn
+            .prepare("SELECT canonical FROM symbol_map")
+            .unwrap()
+            .query_map([], |r| r.get(0))
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(mapped, vec!["AAPL".to_string()]);
+    }
+
+    #[test]
+    fn replace_refuses_and_rolls_back_while_a_doomed_manifest_is_leased() {
+        let conn = mem_conn();
+        let catalog = load_catalog_str(TWO_ASSET_CATALOG).unwrap();
+        sync_catalog(&conn, &catalog).unwrap();
+        let msft_id = SqliteRepo::manifests_all(&conn)
+            .unwrap()
+            .into_iter()
+            .find(|m| m.symbol == "MSFT")
+            .unwrap()
+            .manifest_id;
+        conn.execute(
+            "INSERT INTO gaps (manifest_id, start_bucket, end_bucket, state)
+             VALUES (?1, 0, 10, 'leased')",
+            [msft_id],
+        )
+        .unwrap();
+
+        let mut only_aapl = catalog.clone();
+        only_aapl.assets.truncate(1);
+        let err = sync_catalog_with(&conn, &only_aapl, SyncMode::Replace).unwrap_err();
+        assert!(matches!(err, CatalogError::ReplaceInUse { .. }), "{err}");
+        assert!(err.to_string().contains("MSFT"), "{err}");
+
+        // The refusal rolls the whole sync back: MSFT is still there.
+        assert_eq!(SqliteRepo::manifests_all(&conn).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn preview_matches_what_sync_then_does() {
+        let conn = mem_conn();
+        let catalog = load_catalog_str(CATALOG).unwrap();
+
+        // Fresh DB: everything would be created, nothing touched.
+        let before = preview_sync(&conn, &catalog).unwrap();
+        assert_eq!(before.created.len(), 2);
+        assert!(before.updated.is_empty() && before.orphaned.is_empty());
+        assert_eq!(SqliteRepo::manifests_all(&conn).unwrap().len(), 0);
+
+        let diff = sync_catalog(&conn, &catalog).unwrap();
+        assert_eq!(diff.manifests_upserted, before.created.len());
+
+        // Drop the daily timeframe: preview must predict one update and
+        // one orphan, and the subsequent sync must agree.
+        let mut smaller = catalog.clone();
+        smaller.assets[0].timeframes.truncate(1);
+        let preview = preview_sync(&conn, &smaller).unwrap();
+        assert_eq!(preview.created.len(), 0);
+        assert_eq!(preview.updated, vec!["AAPL alpaca 1minute".to_string()]);
+        assert_eq!(preview.orphaned, vec!["AAPL alpaca 1day".to_string()]);
+
+        let diff2 = sync_catalog(&conn, &smaller).unwrap();
+        assert_eq!(
+            diff2.manifests_upserted + diff2.manifests_unchanged,
+            preview.updated.len()
+        );
+        assert_eq!(diff2.manifests_closed, preview.orphaned.len());
+    }
+
+    #[test]
+    fn diff_runs_on_a_snapshot_without_touching_the_db() {
+        let conn = mem_conn();
+        let catalog = load_catalog_str(CATALOG).unwrap();
+        sync_catalog(&conn, &catalog).unwrap();
+
+        // Snapshot once, then diff a modified catalog against it offline.
+        let current = current_manifests(&conn).unwrap();
+        let mut wider = catalog.clone();
+        wider.assets[0].timeframes = vec![TimeframeCfg {
+            amount: 1,
+            unit: "hour".to_string(),
+        }];
+        let writes_before = conn.total_changes();
+        let preview = diff_manifests(&current, &wanted_manifests(&wider));
+        assert_eq!(conn.total_changes(), writes_before);
+
+        assert_eq!(preview.created, vec!["AAPL alpaca 1hour".to_string()]);
+        assert!(preview.updated.is_empty());
+        assert_eq!(
+            preview.orphaned,
+            vec![
+                "AAPL alpaca 1minute".to_string(),
+                "AAPL alpaca 1day".to_string()
+            ]
+        );
+    }
+
+    #[test]
+    fn unchanged_catalog_syncs_without_writing() {
+        let conn = mem_conn();
+        let catalog = load_catalog_str(CATALOG).unwrap();
+        sync_catalog(&conn, &catalog).unwrap();
+
+        // Second run: same catalog, so not a single row may change.
+        let writes_before = conn.total_changes();
+        let diff = sync_catalog(&conn, &catalog).unwrap();
+        assert_eq!(diff.manifests_upserted, 0);
+        assert_eq!(diff.manifests_unchanged, 2);
+        assert_eq!(diff.manifests_closed, 0);
+        assert_eq!(conn.total_changes(), writes_before);
+    }
+
+    #[test]
+    fn manifest_rows_round_trip_back_into_specs() {
+        let conn = mem_conn();
+        // One open-ended asset (no `end`), one closed.
+        let catalog = load_catalog_str(
+            r#"
+            [[assets]]
+            symbol = "AAPL"
+            asset_class = "us_equity"
+            provider = "alpaca"
+            start = "2024-01-01T00:00:00Z"
+            timeframes = [{ amount = 1, unit = "minute" }]
+
+            [[assets]]
+            symbol = "MSFT"
+            asset_class = "us_equity"
+            provider = "alpaca"
+            start = "2024-01-01T00:00:00Z"
+            end = "2024-06-01T00:00:00Z"
+            timeframes = [{ amount = 1, unit = "day" }]
+        "#,
+        )
+        .unwrap();
+        sync_catalog(&conn, &catalog).unwrap();
+
+        for manifest in SqliteRepo::manifests_all(&conn).unwrap() {
+            let spec = manifest_to_spec(&manifest);
+            let declared = catalog
+                .assets
+                .iter()
+                .find(|a| a.symbol == spec.symbol)
+                .unwrap();
+            assert_eq!(spec.provider, declared.provider);
+            assert_eq!(spec.start, declared.start);
+            assert_eq!(spec.end, declared.end);
+            assert_eq!(spec.timeframes, declared.timeframes);
+
+            // NULL `desired_end` must come back as the open wire form,
+            // a stored end as the closed one.
+            let json = AssetSpecJson::from(spec.clone());
+            match (&json.range, spec.end) {
+                (RangeJson::Open { start }, None) => assert_eq!(*start, spec.start),
+                (RangeJson::Closed { start, end }, Some(spec_end)) => {
+                    assert_eq!(*start, spec.start);
+                    assert_eq!(*end, spec_end);
+                }
+                (range, end) => panic!("range {range:?} does not match end {end:?}"),
+            }
+            assert_eq!(AssetSpec::from(json).end, spec.end);
+        }
+    }
+
+    #[test]
+    fn json_spec_round_trips_an_open_range() {
+        let spec = AssetSpec {
+            symbol: "AAPL".to_string(),
+            asset_class: "us_equity".to_string(),
+            provider: "alpaca".to_string(),
+            start: "2024-01-01T00:00:00Z".parse().unwrap(),
+            end: None,
+            timeframes: vec![TimeframeCfg {
+                amount: 1,
+                unit: "day".to_string(),
+            }],
+        };
+        let json = serde_json::to_value(AssetSpecJson::from(spec.clone())).unwrap();
+        assert_eq!(json["range"]["type"], "open");
+        assert_eq!(json["range"]["start"], "2024-01-01T00:00:00Z");
+        assert!(json["range"].get("end").is_none());
+
+        let parsed: AssetSpecJson = serde_json::from_value(json).unwrap();
+        let back = AssetSpec::from(parsed);
+        assert_eq!(back.symbol, spec.symbol);
+        assert_eq!(back.start, spec.start);
+        assert_eq!(back.end, None);
+        assert_eq!(back.timeframes, spec.timeframes);
+    }
+
+    #[test]
+    fn json_spec_round_trips_a_closed_range() {
+        let text = r#"{
+            "symbol": "BTC/USD",
+            "asset_class": "crypto",
+            "provider": "alpaca",
+            "range": {
+                "type": "closed",
+                "start": "2024-01-01T00:00:00Z",
+                "end": "2024-06-01T00:00:00Z"
+            },
+            "timeframes": [{ "amount": 5, "unit": "minute" }]
+        }"#;
+        let parsed: AssetSpecJson = serde_json::from_str(text).unwrap();
+        let spec = AssetSpec::from(parsed.clone());
+        assert_eq!(spec.end, Some("2024-06-01T00:00:00Z".parse().unwrap()));
+        // Converting back reproduces the same wire form.
+        assert_eq!(AssetSpecJson::from(spec), parsed);
+    }
+}
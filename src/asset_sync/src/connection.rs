@@ -0,0 +1,106 @@
+//! WAL housekeeping for long-running SQLite writers.
+//!
+//! A process that backfills for hours under WAL mode can grow the `-wal`
+//! file without bound if no reader ever lets a checkpoint complete. These
+//! helpers expose `PRAGMA wal_checkpoint` so a maintenance task can force
+//! one after a backfill burst, plus the auto-checkpoint threshold.
+
+use rusqlite::Connection;
+
+/// How aggressively a checkpoint may wait on or reset the WAL; mirrors
+/// SQLite's `wal_checkpoint` modes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckpointMode {
+    /// Checkpoint what can be done without blocking anyone.
+    Passive,
+    /// Wait for writers, then checkpoint the whole WAL.
+    Full,
+    /// Like `Full`, and also block new writers until the WAL restarts.
+    Restart,
+    /// Like `Restart`, and truncate the WAL file to zero bytes.
+    Truncate,
+}
+
+impl CheckpointMode {
+    fn as_str(self) -> &'static str {
+        match self {
+            CheckpointMode::Passive => "PASSIVE",
+            CheckpointMode::Full => "FULL",
+            CheckpointMode::Restart => "RESTART",
+            CheckpointMode::Truncate => "TRUNCATE",
+        }
+    }
+}
+
+/// What `PRAGMA wal_checkpoint` reported.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CheckpointOutcome {
+    /// A lock prevented the checkpoint from completing.
+    pub busy: bool,
+    /// Total pages in the WAL before the checkpoint.
+    pub wal_pages: i64,
+    /// Pages successfully moved back into the database.
+    pub checkpointed_pages: i64,
+}
+
+/// Run a manual WAL checkpoint. No-op (all counters `-1` per SQLite)
+/// when the connection is not in WAL mode.
+pub fn wal_checkpoint(
+    conn: &Connection,
+    mode: CheckpointMode,
+) -> Result<CheckpointOutcome, rusqlite::Error> {
+    conn.query_row(
+        &format!("PRAGMA wal_checkpoint({})", mode.as_str()),
+        [],
+        |r| {
+            Ok(CheckpointOutcome {
+                busy: r.get::<_, i64>(0)? != 0,
+                wal_pages: r.get(1)?,
+                checkpointed_pages: r.get(2)?,
+            })
+        },
+    )
+}
+
+/// Set the automatic checkpoint threshold in WAL pages (SQLite defaults
+/// to 1000); `0` disables auto-checkpointing so only manual
+/// [`wal_checkpoint`] calls shrink the WAL.
+pub fn set_wal_autocheckpoint(conn: &Connection, pages: u32) -> Result<(), rusqlite::Error> {
+    conn.pragma_update(None, "wal_autocheckpoint", pages)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repo::SqliteRepo;
+
+    #[test]
+    fn truncate_checkpoint_shrinks_the_wal_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("wal_test.db");
+        let conn = Connection::open(&path).unwrap();
+        conn.pragma_update(None, "journal_mode", "WAL").unwrap();
+        // Only explicit checkpoints in this test.
+        set_wal_autocheckpoint(&conn, 0).unwrap();
+        SqliteRepo::init(&conn).unwrap();
+
+        for i in 0..500 {
+            SqliteRepo::upsert_asset(&conn, &format!("SYM{i}"), "us_equity").unwrap();
+        }
+        let wal = path.with_extension("db-wal");
+        let before = std::fs::metadata(&wal).unwrap().len();
+        assert!(before > 0, "writes should have grown the WAL");
+
+        let outcome = wal_checkpoint(&conn, CheckpointMode::Truncate).unwrap();
+        assert!(!outcome.busy);
+        assert_eq!(std::fs::metadata(&wal).unwrap().len(), 0);
+    }
+
+    #[test]
+    fn checkpoint_outside_wal_mode_reports_nothing_to_do() {
+        let conn = Connection::open_in_memory().unwrap();
+        let outcome = wal_checkpoint(&conn, CheckpointMode::Passive).unwrap();
+        assert_eq!(outcome.wal_pages, -1);
+        assert_eq!(outcome.checkpointed_pages, -1);
+    }
+}
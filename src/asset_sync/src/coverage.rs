@@ -0,0 +1,791 @@
+//! Turning coverage bitmaps into missing UTC ranges.
+
+use chrono::{DateTime, Utc};
+use roaring::RoaringBitmap;
+use thiserror::Error;
+
+use crate::bucket::{self, BucketError};
+use crate::repo::{Manifest, RepoError, SqliteRepo};
+use crate::session::{HolidayCalendar, SessionCalendar};
+use crate::timeframe::Timeframe;
+
+/// Asset classes with session hours; everything else trades around the
+/// clock. The exchange zone is the fallback for manifests whose `venue_tz`
+/// is still the `"UTC"` column default.
+const EQUITY_ASSET_CLASS: &str = "us_equity";
+const EQUITY_TZ: chrono_tz::Tz = chrono_tz::America::New_York;
+
+/// Half-open `[start, end)` range in UTC.
+pub type UtcRange = (DateTime<Utc>, DateTime<Utc>);
+
+#[derive(Debug, Error)]
+pub enum CoverageError {
+    #[error(transparent)]
+    Repo(#[from] RepoError),
+    #[error(transparent)]
+    Bucket(#[from] BucketError),
+}
+
+/// Missing UTC ranges for a manifest as of `now`.
+///
+/// The desired window is `[desired_start, desired_end)`, with open-ended
+/// manifests clamped to `now`. For session-bound asset classes the window
+/// is intersected with the trading-session bitmap, so closed-market hours
+/// are never "missing". Every remaining desired bucket not present in the
+/// coverage bitmap contributes to a missing range; adjacent buckets are
+/// coalesced into half-open `(start, end)` pairs.
+pub fn compute_missing(
+    conn: &rusqlite::Connection,
+    manifest_id: i64,
+    now: DateTime<Utc>,
+) -> Result<Vec<UtcRange>, CoverageError> {
+    compute_missing_with_holidays(conn, manifest_id, now, &HolidayCalendar::empty())
+}
+
+/// [`compute_missing`] with an explicit holiday calendar: full-day venue
+/// closures are removed from the desired window alongside out-of-session
+/// hours.
+pub fn compute_missing_with_holidays(
+    conn: &rusqlite::Connection,
+    manifest_id: i64,
+    now: DateTime<Utc>,
+    holidays: &HolidayCalendar,
+) -> Result<Vec<UtcRange>, CoverageError> {
+    let manifest = SqliteRepo::manifest_by_id(conn, manifest_id)?;
+    compute_missing_with_manifest(conn, &manifest, now, holidays)
+}
+
+/// [`compute_missing_with_holidays`] for a manifest the caller has already
+/// loaded, skipping the per-manifest lookup. The planner iterates every
+/// open manifest it just read, so re-querying each one here would double
+/// the row reads for nothing.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(
+        skip(conn, manifest, holidays),
+        fields(manifest_id = manifest.manifest_id, buckets_missing = tracing::field::Empty)
+    )
+)]
+pub fn compute_missing_with_manifest(
+    conn: &rusqlite::Connection,
+    manifest: &Manifest,
+    now: DateTime<Utc>,
+    holidays: &HolidayCalendar,
+) -> Result<Vec<UtcRange>, CoverageError> {
+    let manifest_id = manifest.manifest_id;
+    let end = manifest.desired_end.unwrap_or(now).min(now);
+    if manifest.desired_start >= end {
+        return Ok(Vec::new());
+    }
+    let tf = manifest.timeframe;
+    let (first, end_ex) = bucket::bucket_range(manifest.desired_start, end, &tf)?;
+
+    let snap = SqliteRepo::coverage_get(conn, manifest_id)?;
+    // Work relative to the stored base, extending it backwards if the
+    // desired window now starts earlier.
+    let base = if snap.version == 0 {
+        first
+    } else {
+        snap.bucket_base.min(first)
+    };
+    let covered = rebase(&snap.bitmap, snap.bucket_base, base)?;
+
+    let mut desired = RoaringBitmap::new();
+    desired.insert_range(bucket::rel(first, base)?..bucket::rel(end_ex, base)?);
+    if let Some(session) = session_bitmap(manifest, (first, end_ex), &tf, holidays, base)? {
+        desired &= session;
+    }
+    let missing = desired - covered;
+    #[cfg(feature = "tracing")]
+    tracing::Span::current().record("buckets_missing", missing.len());
+    Ok(coalesce_runs_to_utc_ranges(
+        missing.iter().map(|id| bucket::abs(id, base)),
+        &tf,
+    ))
+}
+
+/// The trading-session bitmap for `manifest` over `[range.0, range.1)`
+/// relative to `base`, or `None` for asset classes that trade around the
+/// clock. "UTC" in `venue_tz` is the schema default, meaning "no venue
+/// override"; the session stays on the exchange's own clock in that case.
+fn session_bitmap(
+    manifest: &Manifest,
+    range: (u64, u64),
+    tf: &Timeframe,
+    holidays: &HolidayCalendar,
+    base: u64,
+) -> Result<Option<RoaringBitmap>, CoverageError> {
+    if manifest.asset_class != EQUITY_ASSET_CLASS {
+        return Ok(None);
+    }
+    let tz = match manifest.venue_tz.as_str() {
+        "UTC" => EQUITY_TZ,
+        other => other.parse().unwrap_or(EQUITY_TZ),
+    };
+    Ok(Some(bucket::session_filter_bitmap(
+        range,
+        tf,
+        &SessionCalendar::regular(),
+        holidays,
+        tz,
+        base,
+    )?))
+}
+
+/// Why an uncovered bucket range is not in the fetch plan.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MissingReason {
+    /// Desired, in session, uncovered: the planner will fetch it.
+    Missing,
+    /// Inside the desired window but the venue is closed — out-of-session
+    /// hours or a holiday. Ignored by design, never fetched.
+    OutOfSession,
+    /// Outside the manifest's desired window: before `desired_start`, past
+    /// `desired_end`, or beyond `now`.
+    NotDesired,
+}
+
+/// Classify every uncovered bucket of `window` — covered buckets are
+/// omitted — coalescing same-reason runs into half-open UTC ranges.
+///
+/// [`compute_missing`] answers "what should I fetch"; this answers the
+/// operator's follow-up, "why is that hole not being filled": a range
+/// tagged [`MissingReason::OutOfSession`] or [`MissingReason::NotDesired`]
+/// is empty on purpose, not stuck.
+pub fn explain_missing(
+    conn: &rusqlite::Connection,
+    manifest_id: i64,
+    window: UtcRange,
+    now: DateTime<Utc>,
+    holidays: &HolidayCalendar,
+) -> Result<Vec<(UtcRange, MissingReason)>, CoverageError> {
+    let manifest = SqliteRepo::manifest_by_id(conn, manifest_id)?;
+    let tf = manifest.timeframe;
+    if window.0 >= window.1 {
+        return Ok(Vec::new());
+    }
+    let (q_first, q_end) = bucket::bucket_range(window.0, window.1, &tf)?;
+
+    let snap = SqliteRepo::coverage_get(conn, manifest_id)?;
+    let base = if snap.version == 0 {
+        q_first
+    } else {
+        snap.bucket_base.min(q_first)
+    };
+    let covered = rebase(&snap.bitmap, snap.bucket_base, base)?;
+    let desired_end = manifest.desired_end.unwrap_or(now).min(now);
+    let desired = (manifest.desired_start < desired_end)
+        .then(|| bucket::bucket_range(manifest.desired_start, desired_end, &tf))
+        .transpose()?;
+    let session = session_bitmap(&manifest, (q_first, q_end), &tf, holidays, base)?;
+
+    let mut runs: Vec<(u64, u64, MissingReason)> = Vec::new();
+    for id in q_first..q_end {
+        let rel = bucket::rel(id, base)?;
+        if covered.contains(rel) {
+            continue;
+        }
+        let reason = match desired {
+            Some((d_first, d_end)) if id >= d_first && id < d_end => match &session {
+                Some(s) if !s.contains(rel) => MissingReason::OutOfSession,
+                _ => MissingReason::Missing,
+            },
+            _ => MissingReason::NotDesired,
+        };
+        match runs.last_mut() {
+            Some((_, last, r)) if *r == reason && id == *last + 1 => *last = id,
+            _ => runs.push((id, id, reason)),
+        }
+    }
+    Ok(runs
+        .into_iter()
+        .map(|(first, last, reason)| {
+            (
+                (
+                    bucket::bucket_start(first, &tf),
+                    bucket::bucket_start(last + 1, &tf),
+                ),
+                reason,
+            )
+        })
+        .collect())
+}
+
+/// The window a refresh cycle should look at for an open-ended manifest.
+///
+/// With no watermark (cold start) the whole desired range back to
+/// `desired_start` is checked. Once a cycle has completed through
+/// `watermark`, later cycles only re-examine `watermark - lookback`
+/// onwards — the lookback absorbs late-arriving bars near the watermark —
+/// clamped so the window never starts before `desired_start` or after
+/// `now`. This is the one tested home of the keep-fresh policy; workers
+/// pass the result straight to the missing-range computation.
+pub fn refresh_window(
+    desired_start: DateTime<Utc>,
+    watermark: Option<DateTime<Utc>>,
+    now: DateTime<Utc>,
+    lookback: chrono::Duration,
+) -> UtcRange {
+    let start = match watermark {
+        Some(mark) => (mark - lookback).max(desired_start),
+        None => desired_start,
+    };
+    (start.min(now), now)
+}
+
+/// Re-express `bitmap` (relative to `from` base) relative to `to`.
+/// Cheap when the bases already agree.
+fn rebase(bitmap: &RoaringBitmap, from: u64, to: u64) -> Result<RoaringBitmap, BucketError> {
+    if from == to {
+        return Ok(bitmap.clone());
+    }
+    bitmap
+        .iter()
+        .map(|id| bucket::rel(bucket::abs(id, from), to))
+        .collect::<Result<RoaringBitmap, _>>()
+}
+
+/// Half-open UTC range tagged `true` if covered, `false` if missing.
+pub type TaggedRange = (DateTime<Utc>, DateTime<Utc>, bool);
+
+impl SqliteRepo {
+    /// Human-readable coverage timeline: contiguous runs of
+    /// covered/missing buckets over `window`, coalesced into tagged UTC
+    /// ranges. Adjacent runs always alternate tags, so the output reads
+    /// directly as "covered until X, missing until Y, ...".
+    pub fn coverage_ranges(
+        conn: &rusqlite::Connection,
+        manifest_id: i64,
+        window: UtcRange,
+    ) -> Result<Vec<TaggedRange>, CoverageError> {
+        let manifest = SqliteRepo::manifest_by_id(conn, manifest_id)?;
+        let tf = manifest.timeframe;
+        let (first, end_ex) = bucket::bucket_range(window.0, window.1, &tf)?;
+        let snap = SqliteRepo::coverage_get(conn, manifest_id)?;
+        let covered = |id: u64| {
+            id.checked_sub(snap.bucket_base)
+                .and_then(|d| u32::try_from(d).ok())
+                .is_some_and(|rel| snap.bitmap.contains(rel))
+        };
+
+        let mut ranges: Vec<TaggedRange> = Vec::new();
+        let mut run: Option<(u64, u64, bool)> = None;
+        for id in first..end_ex {
+            let tag = covered(id);
+            run = match run {
+                Some((start, _, t)) if t == tag => Some((start, id, t)),
+                Some((start, last, t)) => {
+                    ranges.push((
+                        bucket::bucket_start(start, &tf),
+                        bucket::bucket_start(last + 1, &tf),
+                        t,
+                    ));
+                    Some((id, id, tag))
+                }
+                None => Some((id, id, tag)),
+            };
+        }
+        if let Some((start, last, t)) = run {
+            ranges.push((
+                bucket::bucket_start(start, &tf),
+                bucket::bucket_start(last + 1, &tf),
+                t,
+            ));
+        }
+        Ok(ranges)
+    }
+
+    /// Fraction of `window`'s buckets the manifest has covered, in
+    /// `0.0..=1.0`. A manifest with no stored bitmap scores zero.
+    pub fn coverage_fraction(
+        conn: &rusqlite::Connection,
+        manifest_id: i64,
+        window: UtcRange,
+    ) -> Result<f64, CoverageError> {
+        let manifest = SqliteRepo::manifest_by_id(conn, manifest_id)?;
+        let (first, end_ex) = bucket::bucket_range(window.0, window.1, &manifest.timeframe)?;
+        let snap = SqliteRepo::coverage_get(conn, manifest_id)?;
+        // Ids below the stored base are by definition uncovered.
+        let lo = first.max(snap.bucket_base);
+        let covered = if lo >= end_ex {
+            0
+        } else {
+            snap.bitmap.range_cardinality(
+                bucket::rel(lo, snap.bucket_base)?..bucket::rel(end_ex, snap.bucket_base)?,
+            )
+        };
+        Ok(covered as f64 / (end_ex - first) as f64)
+    }
+
+    /// Archive a closed-range manifest whose desired window is fully
+    /// covered: flip its status to `complete` and trim the stored bitmap
+    /// to exactly the desired window. Roaring run-length encodes a full
+    /// contiguous range in a few bytes, so the trim *is* the compact
+    /// form — no sentinel value is needed. Returns whether the manifest
+    /// was marked; open-ended, already-archived or incompletely covered
+    /// manifests come back `false` untouched. The planner only walks
+    /// `open` manifests, so a completed one drops out of planning.
+    pub fn mark_complete_if_full(
+        conn: &rusqlite::Connection,
+        manifest_id: i64,
+    ) -> Result<bool, CoverageError> {
+        let manifest = SqliteRepo::manifest_by_id(conn, manifest_id)?;
+        let Some(desired_end) = manifest.desired_end else {
+            return Ok(false);
+        };
+        if manifest.status != crate::repo::ManifestStatus::Open {
+            return Ok(false);
+        }
+        let (first, end_ex) =
+            bucket::bucket_range(manifest.desired_start, desired_end, &manifest.timeframe)?;
+        let snap = SqliteRepo::coverage_get(conn, manifest_id)?;
+        if first < snap.bucket_base {
+            // Buckets below the stored base are by definition uncovered.
+            return Ok(false);
+        }
+        let lo = bucket::rel(first, snap.bucket_base)?;
+        let hi = bucket::rel(end_ex, snap.bucket_base)?;
+        if snap.bitmap.range_cardinality(lo..hi) != end_ex - first {
+            return Ok(false);
+        }
+
+        let tx = conn.unchecked_transaction().map_err(RepoError::from)?;
+        let mut trimmed = snap.bitmap;
+        trimmed.remove_range(..lo);
+        trimmed.remove_range(hi..);
+        SqliteRepo::coverage_put(&tx, manifest_id, snap.version, snap.bucket_base, &trimmed)?;
+        tx.execute(
+            "UPDATE manifests SET status = 'complete' WHERE manifest_id = ?1",
+            [manifest_id],
+        )
+        .map_err(RepoError::from)?;
+        tx.commit().map_err(RepoError::from)?;
+        Ok(true)
+    }
+
+    /// One dashboard row for a symbol: the coverage fraction over `window`
+    /// of each of its open manifests under (provider, asset_class), keyed
+    /// by timeframe and ordered finest to coarsest — "AAPL: 1m 87%,
+    /// 5m 99%, 1d 100%".
+    pub fn symbol_coverage_summary(
+        conn: &rusqlite::Connection,
+        provider: &str,
+        asset_class: &str,
+        symbol: &str,
+        window: UtcRange,
+    ) -> Result<Vec<(Timeframe, f64)>, CoverageError> {
+        let mut manifests: Vec<_> = SqliteRepo::manifests_open(conn)?
+            .into_iter()
+            .filter(|m| {
+                m.provider == provider && m.asset_class == asset_class && m.symbol == symbol
+            })
+            .collect();
+        manifests.sort_by_key(|m| m.timeframe.minutes());
+
+        let mut summary = Vec::with_capacity(manifests.len());
+        for manifest in manifests {
+            let fraction = SqliteRepo::coverage_fraction(conn, manifest.manifest_id, window)?;
+            summary.push((manifest.timeframe, fraction));
+        }
+        Ok(summary)
+    }
+}
+
+/// Coalesce an ascending iterator of bucket ids into half-open UTC ranges.
+/// Consecutive ids merge; each range ends at the start of the bucket after
+/// its last id.
+pub fn coalesce_runs_to_utc_ranges(
+    ids: impl Iterator<Item = u64>,
+    tf: &Timeframe,
+) -> Vec<UtcRange> {
+    let mut ranges = Vec::new();
+    let mut run: Option<(u64, u64)> = None;
+    for id in ids {
+        run = match run {
+            Some((first, last)) if id == last + 1 => Some((first, id)),
+            Some((first, last)) => {
+                ranges.push((first, last));
+                debug_assert!(id > last, "bucket ids must be ascending");
+                Some((id, id))
+            }
+            None => Some((id, id)),
+        };
+    }
+    if let Some(r) = run {
+        ranges.push(r);
+    }
+    ranges
+        .into_iter()
+        .map(|(first, last)| {
+            (
+                bucket::bucket_start(first, tf),
+                bucket::bucket_start(last + 1, tf),
+            )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repo::test_support::*;
+    use crate::timeframe::TimeframeUnit;
+
+    #[test]
+    fn coalesce_merges_adjacent_ids() {
+        let tf = Timeframe::new(1, TimeframeUnit::Hour).unwrap();
+        let ids = [10u64, 11, 12, 20, 30, 31];
+        let ranges = coalesce_runs_to_utc_ranges(ids.into_iter(), &tf);
+        assert_eq!(ranges.len(), 3);
+        assert_eq!(ranges[0].0, crate::bucket::bucket_start(10, &tf));
+        assert_eq!(ranges[0].1, crate::bucket::bucket_start(13, &tf));
+        assert_eq!(ranges[1].0, crate::bucket::bucket_start(20, &tf));
+        assert_eq!(ranges[2].1, crate::bucket::bucket_start(32, &tf));
+    }
+
+    #[test]
+    fn fully_uncovered_manifest_is_one_range() {
+        let conn = mem_conn();
+        let tf = Timeframe::new(1, TimeframeUnit::Hour).unwrap();
+        let start = utc(2024, 1, 1, 0, 0);
+        let end = utc(2024, 1, 1, 6, 0);
+        let id = insert_manifest(&conn, "AAPL", "alpaca", tf, start, Some(end));
+        let missing = compute_missing(&conn, id, utc(2024, 6, 1, 0, 0)).unwrap();
+        assert_eq!(missing, vec![(start, end)]);
+    }
+
+    #[test]
+    fn covered_buckets_split_the_window() {
+        let conn = mem_conn();
+        let tf = Timeframe::new(1, TimeframeUnit::Hour).unwrap();
+        let start = utc(2024, 1, 1, 0, 0);
+        let end = utc(2024, 1, 1, 6, 0);
+        let id = insert_manifest(&conn, "AAPL", "alpaca", tf, start, Some(end));
+
+        // Cover hours 2 and 3, stored relative to the window start.
+        let (first, _) = crate::bucket::bucket_range(start, end, &tf).unwrap();
+        let mut bm = RoaringBitmap::new();
+        bm.insert(2);
+        bm.insert(3);
+        SqliteRepo::coverage_put(&conn, id, 0, first, &bm).unwrap();
+
+        let missing = compute_missing(&conn, id, utc(2024, 6, 1, 0, 0)).unwrap();
+        assert_eq!(
+            missing,
+            vec![(start, utc(2024, 1, 1, 2, 0)), (utc(2024, 1, 1, 4, 0), end),]
+        );
+    }
+
+    #[test]
+    fn preloaded_manifest_variant_matches_the_lookup_path() {
+        let conn = mem_conn();
+        let tf = Timeframe::new(1, TimeframeUnit::Hour).unwrap();
+        let start = utc(2024, 1, 1, 0, 0);
+        let end = utc(2024, 1, 1, 6, 0);
+        let id = insert_manifest(&conn, "AAPL", "alpaca", tf, start, Some(end));
+
+        let (first, _) = crate::bucket::bucket_range(start, end, &tf).unwrap();
+        let mut bm = RoaringBitmap::new();
+        bm.insert(2);
+        SqliteRepo::coverage_put(&conn, id, 0, first, &bm).unwrap();
+
+        let now = utc(2024, 6, 1, 0, 0);
+        let via_lookup = compute_missing(&conn, id, now).unwrap();
+        let manifest = SqliteRepo::manifest_by_id(&conn, id).unwrap();
+        let via_manifest =
+            compute_missing_with_manifest(&conn, &manifest, now, &HolidayCalendar::empty())
+                .unwrap();
+        assert_eq!(via_lookup, via_manifest);
+    }
+
+    #[test]
+    fn full_closed_ranges_archive_as_complete_and_leave_planning() {
+        let conn = mem_conn();
+        let tf = Timeframe::new(1, TimeframeUnit::Hour).unwrap();
+        let start = utc(2024, 1, 1, 0, 0);
+        let end = utc(2024, 1, 1, 6, 0);
+        let id = insert_manifest(&conn, "BTC/USD", "mock", tf, start, Some(end));
+
+        // Five of six hours covered: not complete yet.
+        let (first, _) = crate::bucket::bucket_range(start, end, &tf).unwrap();
+        let bm: RoaringBitmap = (0..5).collect();
+        SqliteRepo::coverage_put(&conn, id, 0, first, &bm).unwrap();
+        assert!(!SqliteRepo::mark_complete_if_full(&conn, id).unwrap());
+
+        // The last hour lands, plus a stray bit past the window that the
+        // archive trims away.
+        let snap = SqliteRepo::coverage_get(&conn, id).unwrap();
+        let mut bm = snap.bitmap;
+        bm.insert(5);
+        bm.insert(99);
+        SqliteRepo::coverage_put(&conn, id, snap.version, snap.bucket_base, &bm).unwrap();
+        assert!(SqliteRepo::mark_complete_if_full(&conn, id).unwrap());
+
+        let manifest = SqliteRepo::manifest_by_id(&conn, id).unwrap();
+        assert_eq!(manifest.status, crate::repo::ManifestStatus::Complete);
+        let snap = SqliteRepo::coverage_get(&conn, id).unwrap();
+        assert_eq!(snap.bitmap, (0..6).collect());
+
+        // Completed manifests get no planned work, and re-running the
+        // archival is a no-op.
+        let caps = std::collections::HashMap::from([(
+            "mock".to_string(),
+            crate::planner::ProviderCaps {
+                max_symbols_per_request: 10,
+            },
+        )]);
+        let fetches = crate::planner::plan(&conn, &caps, utc(2024, 6, 1, 0, 0)).unwrap();
+        assert!(fetches.is_empty());
+        assert!(!SqliteRepo::mark_complete_if_full(&conn, id).unwrap());
+    }
+
+    #[test]
+    fn open_ended_manifests_never_archive() {
+        let conn = mem_conn();
+        let tf = Timeframe::new(1, TimeframeUnit::Hour).unwrap();
+        let start = utc(2024, 1, 1, 0, 0);
+        let id = insert_manifest(&conn, "BTC/USD", "mock", tf, start, None);
+        let bm: RoaringBitmap = (0..1000).collect();
+        SqliteRepo::coverage_put(&conn, id, 0, 0, &bm).unwrap();
+        assert!(!SqliteRepo::mark_complete_if_full(&conn, id).unwrap());
+    }
+
+    #[test]
+    fn coverage_ranges_alternate_covered_and_missing() {
+        let conn = mem_conn();
+        let tf = Timeframe::new(1, TimeframeUnit::Hour).unwrap();
+        let start = utc(2024, 1, 1, 0, 0);
+        let end = utc(2024, 1, 1, 6, 0);
+        let id = insert_manifest(&conn, "AAPL", "alpaca", tf, start, Some(end));
+
+        // Cover hours 1 and 4-5, leaving gaps around them.
+        let (first, _) = crate::bucket::bucket_range(start, end, &tf).unwrap();
+        let mut bm = RoaringBitmap::new();
+        bm.insert(1);
+        bm.insert(4);
+        bm.insert(5);
+        SqliteRepo::coverage_put(&conn, id, 0, first, &bm).unwrap();
+
+        let ranges = SqliteRepo::coverage_ranges(&conn, id, (start, end)).unwrap();
+        assert_eq!(
+            ranges,
+            vec![
+                (start, utc(2024, 1, 1, 1, 0), false),
+                (utc(2024, 1, 1, 1, 0), utc(2024, 1, 1, 2, 0), true),
+                (utc(2024, 1, 1, 2, 0), utc(2024, 1, 1, 4, 0), false),
+                (utc(2024, 1, 1, 4, 0), end, true),
+            ]
+        );
+    }
+
+    #[test]
+    fn symbol_summary_reports_fraction_per_timeframe() {
+        let conn = mem_conn();
+        let start = utc(2024, 1, 1, 0, 0);
+        let end = utc(2024, 1, 2, 0, 0);
+        let hour = Timeframe::new(1, TimeframeUnit::Hour).unwrap();
+        let day = Timeframe::new(1, TimeframeUnit::Day).unwrap();
+        let hourly = insert_manifest(&conn, "BTC/USD", "alpaca", hour, start, Some(end));
+        let daily = insert_manifest(&conn, "BTC/USD", "alpaca", day, start, Some(end));
+        // A different symbol must not leak into the summary.
+        insert_manifest(&conn, "ETH/USD", "alpaca", hour, start, Some(end));
+
+        // Cover 12 of 24 hourly buckets and the full daily bucket.
+        let (hour_first, _) = bucket::bucket_range(start, end, &hour).unwrap();
+        let mut bm = RoaringBitmap::new();
+        bm.insert_range(0..12);
+        SqliteRepo::coverage_put(&conn, hourly, 0, hour_first, &bm).unwrap();
+        let (day_first, _) = bucket::bucket_range(start, end, &day).unwrap();
+        let mut bm = RoaringBitmap::new();
+        bm.insert(0);
+        SqliteRepo::coverage_put(&conn, daily, 0, day_first, &bm).unwrap();
+
+        let summary =
+            SqliteRepo::symbol_coverage_summary(&conn, "alpaca", "crypto", "BTC/USD", (start, end))
+                .unwrap();
+        assert_eq!(summary, vec![(hour, 0.5), (day, 1.0)]);
+    }
+
+    #[test]
+    fn equity_manifest_only_wants_session_buckets() {
+        let conn = mem_conn();
+        let tf = Timeframe::new(1, TimeframeUnit::Minute).unwrap();
+        // Tuesday 2024-01-02, full UTC day.
+        let start = utc(2024, 1, 2, 0, 0);
+        let end = utc(2024, 1, 3, 0, 0);
+        let id = insert_manifest_class(&conn, "AAPL", "us_equity", "alpaca", tf, start, Some(end));
+        let missing = compute_missing(&conn, id, utc(2024, 6, 1, 0, 0)).unwrap();
+        // Exactly the regular session, 09:30-16:00 ET.
+        assert_eq!(
+            missing,
+            vec![(utc(2024, 1, 2, 14, 30), utc(2024, 1, 2, 21, 0))]
+        );
+    }
+
+    #[test]
+    fn explain_missing_tags_each_hole_with_its_reason() {
+        let conn = mem_conn();
+        let tf = Timeframe::new(1, TimeframeUnit::Minute).unwrap();
+        // Tuesday 2024-01-02: desired 14:00-15:00 UTC straddles the 14:30
+        // UTC (09:30 ET) open.
+        let start = utc(2024, 1, 2, 14, 0);
+        let end = utc(2024, 1, 2, 15, 0);
+        let id = insert_manifest_class(&conn, "AAPL", "us_equity", "alpaca", tf, start, Some(end));
+
+        // Cover 14:40-14:50.
+        let (d_first, _) = crate::bucket::bucket_range(start, end, &tf).unwrap();
+        let mut bm = RoaringBitmap::new();
+        bm.insert_range(40..50);
+        SqliteRepo::coverage_put(&conn, id, 0, d_first, &bm).unwrap();
+
+        // Query a wider window so both flavors of "ignore" appear.
+        let explained = explain_missing(
+            &conn,
+            id,
+            (utc(2024, 1, 2, 13, 30), utc(2024, 1, 2, 15, 30)),
+            utc(2024, 6, 1, 0, 0),
+            &HolidayCalendar::empty(),
+        )
+        .unwrap();
+        assert_eq!(
+            explained,
+            vec![
+                (
+                    (utc(2024, 1, 2, 13, 30), utc(2024, 1, 2, 14, 0)),
+                    MissingReason::NotDesired
+                ),
+                (
+                    (utc(2024, 1, 2, 14, 0), utc(2024, 1, 2, 14, 30)),
+                    MissingReason::OutOfSession
+                ),
+                (
+                    (utc(2024, 1, 2, 14, 30), utc(2024, 1, 2, 14, 40)),
+                    MissingReason::Missing
+                ),
+                // 14:40-14:50 is covered and therefore absent.
+                (
+                    (utc(2024, 1, 2, 14, 50), utc(2024, 1, 2, 15, 0)),
+                    MissingReason::Missing
+                ),
+                (
+                    (utc(2024, 1, 2, 15, 0), utc(2024, 1, 2, 15, 30)),
+                    MissingReason::NotDesired
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn venue_tz_override_moves_the_session() {
+        let conn = mem_conn();
+        let tf = Timeframe::new(1, TimeframeUnit::Minute).unwrap();
+        // Tuesday 2024-01-02, full UTC day.
+        let start = utc(2024, 1, 2, 0, 0);
+        let end = utc(2024, 1, 3, 0, 0);
+        let id = insert_manifest_class(&conn, "AAPL", "us_equity", "alpaca", tf, start, Some(end));
+        SqliteRepo::manifest_set_venue_tz(&conn, id, "America/Chicago").unwrap();
+        let missing = compute_missing(&conn, id, utc(2024, 6, 1, 0, 0)).unwrap();
+        // The 09:30-16:00 session, read on the Chicago clock (UTC-6).
+        assert_eq!(
+            missing,
+            vec![(utc(2024, 1, 2, 15, 30), utc(2024, 1, 2, 22, 0))]
+        );
+    }
+
+    #[test]
+    fn holidays_are_not_reported_missing() {
+        let conn = mem_conn();
+        let tf = Timeframe::new(1, TimeframeUnit::Minute).unwrap();
+        // Mon 2024-01-01 (New Year's Day) through Tue 2024-01-02.
+        let start = utc(2024, 1, 1, 0, 0);
+        let end = utc(2024, 1, 3, 0, 0);
+        let id = insert_manifest_class(&conn, "AAPL", "us_equity", "alpaca", tf, start, Some(end));
+        let nyse =
+            HolidayCalendar::from_dates([chrono::NaiveDate::from_ymd_opt(2024, 1, 1).unwrap()]);
+        let missing =
+            compute_missing_with_holidays(&conn, id, utc(2024, 6, 1, 0, 0), &nyse).unwrap();
+        // Only Tuesday's session remains desired.
+        assert_eq!(
+            missing,
+            vec![(utc(2024, 1, 2, 14, 30), utc(2024, 1, 2, 21, 0))]
+        );
+    }
+
+    #[test]
+    fn far_future_windows_use_relative_bitmap_ids() {
+        // Year 9999 is the farthest our RFC 3339 columns round-trip; its
+        // minute ids sit ~4.22e9, within 2% of u32::MAX. The stored base
+        // keeps bitmap ids small regardless of where the window lies.
+        let conn = mem_conn();
+        let tf = Timeframe::new(1, TimeframeUnit::Minute).unwrap();
+        let start = utc(9999, 1, 1, 0, 0);
+        let end = utc(9999, 1, 1, 2, 0);
+        let id = insert_manifest(&conn, "BTC/USD", "alpaca", tf, start, Some(end));
+
+        let missing = compute_missing(&conn, id, utc(9999, 6, 1, 0, 0)).unwrap();
+        assert_eq!(missing, vec![(start, end)]);
+
+        // Cover the first hour, stored relative to the window base.
+        let (first, _) = crate::bucket::bucket_range(start, end, &tf).unwrap();
+        let mut bm = RoaringBitmap::new();
+        bm.insert_range(0..60);
+        SqliteRepo::coverage_put(&conn, id, 0, first, &bm).unwrap();
+        let snap = SqliteRepo::coverage_get(&conn, id).unwrap();
+        assert_eq!(snap.bucket_base, first);
+        assert_eq!(snap.bitmap.max(), Some(59));
+        let missing = compute_missing(&conn, id, utc(9999, 6, 1, 0, 0)).unwrap();
+        assert_eq!(missing, vec![(utc(9999, 1, 1, 1, 0), end)]);
+    }
+
+    #[test]
+    fn refresh_window_covers_everything_on_cold_start() {
+        let start = utc(2024, 1, 1, 0, 0);
+        let now = utc(2024, 3, 1, 12, 0);
+        assert_eq!(
+            refresh_window(start, None, now, chrono::Duration::hours(6)),
+            (start, now)
+        );
+    }
+
+    #[test]
+    fn refresh_window_looks_back_from_the_watermark() {
+        let start = utc(2024, 1, 1, 0, 0);
+        let now = utc(2024, 3, 1, 12, 0);
+        let mark = utc(2024, 3, 1, 9, 0);
+        assert_eq!(
+            refresh_window(start, Some(mark), now, chrono::Duration::hours(6)),
+            (utc(2024, 3, 1, 3, 0), now)
+        );
+        // The lookback never reaches before the desired start...
+        assert_eq!(
+            refresh_window(
+                utc(2024, 3, 1, 8, 0),
+                Some(mark),
+                now,
+                chrono::Duration::hours(6)
+            ),
+            (utc(2024, 3, 1, 8, 0), now)
+        );
+        // ...and a watermark ahead of `now` still yields a sane window.
+        assert_eq!(
+            refresh_window(
+                start,
+                Some(now + chrono::Duration::hours(2)),
+                now,
+                chrono::Duration::hours(1)
+            ),
+            (now, now)
+        );
+    }
+
+    #[test]
+    fn open_ended_manifest_clamps_to_now() {
+        let conn = mem_conn();
+        let tf = Timeframe::new(1, TimeframeUnit::Hour).unwrap();
+        let start = utc(2024, 1, 1, 0, 0);
+        let id = insert_manifest(&conn, "AAPL", "alpaca", tf, start, None);
+        let now = utc(2024, 1, 1, 3, 0);
+        let missing = compute_missing(&conn, id, now).unwrap();
+        assert_eq!(missing, vec![(start, now)]);
+    }
+}
@@ -0,0 +1,100 @@
+//! One error type for the CLI/worker boundary.
+//!
+//! Library functions keep returning their module-specific enums — those
+//! are the precise contracts callers match on. [`Error`] exists for the
+//! layer above, where a command touches several modules and wants one
+//! `?`-friendly type with the source chains intact instead of collapsing
+//! everything into `anyhow`.
+
+use thiserror::Error;
+
+use crate::backup::BackupError;
+use crate::bucket::BucketError;
+use crate::catalog::CatalogError;
+use crate::coverage::CoverageError;
+use crate::fetch::FetchError;
+use crate::planner::PlanError;
+use crate::repo::RepoError;
+use crate::session::HolidayError;
+use crate::timeframe::TimeframeError;
+use market_data_ingestor::providers::ProviderError;
+
+/// Union of the crate's module errors. Every variant is transparent, so
+/// display and source chains are exactly the wrapped error's.
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error(transparent)]
+    Repo(#[from] RepoError),
+    #[error(transparent)]
+    Catalog(#[from] CatalogError),
+    #[error(transparent)]
+    Coverage(#[from] CoverageError),
+    #[error(transparent)]
+    Bucket(#[from] BucketError),
+    #[error(transparent)]
+    Fetch(#[from] FetchError),
+    #[error(transparent)]
+    Plan(#[from] PlanError),
+    #[error(transparent)]
+    Backup(#[from] BackupError),
+    #[error(transparent)]
+    Holiday(#[from] HolidayError),
+    #[error(transparent)]
+    Timeframe(#[from] TimeframeError),
+    #[error(transparent)]
+    Provider(#[from] ProviderError),
+    #[cfg(feature = "delta")]
+    #[error(transparent)]
+    Verify(#[from] crate::verify::VerifyError),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn displays_pass_the_underlying_message_through() {
+        let cases: Vec<(Error, &str)> = vec![
+            (
+                RepoError::ManifestNotFound(7).into(),
+                "manifest 7 not found",
+            ),
+            (
+                CatalogError::InvertedWindow {
+                    symbol: "AAPL".to_string(),
+                }
+                .into(),
+                "desired_start must precede desired_end",
+            ),
+            (
+                ProviderError::InvalidRequest("no such symbol".to_string()).into(),
+                "no such symbol",
+            ),
+            (
+                FetchError::Repo(RepoError::CorruptBitmap(3)).into(),
+                "corrupt coverage bitmap for manifest 3",
+            ),
+            (
+                PlanError::UnknownProvider("alpaca".to_string()).into(),
+                "no capabilities registered for provider",
+            ),
+        ];
+        for (error, needle) in cases {
+            let shown = error.to_string();
+            assert!(shown.contains(needle), "{shown:?} missing {needle:?}");
+        }
+    }
+
+    #[test]
+    fn source_chain_survives_the_wrapping() {
+        use std::error::Error as _;
+        let error: Error = RepoError::Sqlite(rusqlite::Error::QueryReturnedNoRows).into();
+        // Transparent wrapping adds no link of its own: the source is
+        // still the sqlite error underneath the module error.
+        let source = error.source().expect("sqlite cause preserved");
+        assert_eq!(
+            source.to_string(),
+            rusqlite::Error::QueryReturnedNoRows.to_string()
+        );
+    }
+}
@@ -0,0 +1,52 @@
+//! The provider-call step of the worker loop.
+//!
+//! Workers hold canonical symbols (the ones in `assets` and the catalog),
+//! but a provider may know an asset under a different wire symbol — a
+//! futures root like `ES` trades as a dated contract like `ESZ5`. The
+//! `symbol_map` table records those pairs; this module applies them
+//! around [`DataProvider::fetch_bars`] so everything above the provider
+//! call keeps speaking canonical symbols.
+
+use market_data_ingestor::models::bar::BarSeries;
+use market_data_ingestor::models::request_params::BarsRequestParams;
+use market_data_ingestor::providers::{DataProvider, ProviderError};
+use rusqlite::Connection;
+use thiserror::Error;
+
+use crate::repo::{RepoError, SqliteRepo};
+
+#[derive(Debug, Error)]
+pub enum FetchError {
+    #[error(transparent)]
+    Repo(#[from] RepoError),
+    #[error(transparent)]
+    Provider(#[from] ProviderError),
+}
+
+/// Fetch `params` from `provider`, translating canonical symbols to the
+/// provider's remote ones on the way out and back to canonical on the
+/// way in. Symbols without a `symbol_map` row pass through unchanged, so
+/// this is a no-op wrapper for equities and crypto pairs.
+pub fn fetch_bars_translated<P: DataProvider>(
+    conn: &Connection,
+    provider: &P,
+    provider_name: &str,
+    params: &BarsRequestParams,
+) -> Result<Vec<BarSeries>, FetchError> {
+    let remote_symbols = params
+        .symbols
+        .iter()
+        .map(|s| SqliteRepo::resolve_remote(conn, provider_name, s))
+        .collect::<Result<Vec<_>, _>>()?;
+    let remote_params = BarsRequestParams {
+        symbols: remote_symbols,
+        timeframe: params.timeframe,
+        start: params.start,
+        end: params.end,
+    };
+    let mut series = provider.fetch_bars(&remote_params)?;
+    for s in &mut series {
+        s.symbol = SqliteRepo::resolve_canonical(conn, provider_name, &s.symbol)?;
+    }
+    Ok(series)
+}
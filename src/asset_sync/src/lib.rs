@@ -0,0 +1,37 @@
+//! Declarative catalog of assets/timeframes we want bars for, backed by SQLite.
+//!
+//! The flow is:
+//!
+//! 1. A TOML catalog ([`catalog`]) declares which symbols, providers and
+//!    timeframes should be kept in sync.
+//! 2. [`catalog::sync_catalog`] reconciles the declaration with the
+//!    `manifests` table — one manifest per (asset, provider, timeframe).
+//! 3. Per-manifest coverage is a roaring bitmap of bucket ids ([`bucket`]);
+//!    [`coverage::compute_missing`] turns the complement into UTC ranges.
+//! 4. Missing ranges become `gaps` rows that workers lease, fill and
+//!    complete ([`repo`]).
+//! 5. The [`planner`] turns manifests + provider capabilities into an
+//!    ordered fetch schedule.
+
+pub mod audit;
+pub mod backup;
+pub mod bucket;
+pub mod catalog;
+pub mod connection;
+pub mod coverage;
+pub mod doctor;
+pub mod error;
+pub mod fetch;
+pub mod planner;
+pub mod profile;
+pub mod repo;
+pub mod roaring_bytes;
+#[cfg(feature = "server")]
+pub mod server;
+pub mod session;
+pub mod timeframe;
+pub mod tz;
+#[cfg(feature = "delta")]
+pub mod verify;
+
+pub use error::Error;
@@ -0,0 +1,604 @@
+//! `asset-sync` — operator CLI for the catalog/coverage database.
+
+use std::path::PathBuf;
+
+use anyhow::{Context, bail};
+use chrono::Utc;
+use clap::{Parser, Subcommand, ValueEnum};
+use rusqlite::Connection;
+
+use asset_sync::catalog::load_catalog_path_reported;
+use asset_sync::profile::NewAssetProfile;
+use asset_sync::repo::SqliteRepo;
+use asset_sync::session::SessionCalendar;
+
+#[derive(Parser)]
+#[command(
+    name = "asset-sync",
+    about = "Manage the asset catalog and coverage DB"
+)]
+struct Cli {
+    /// Path to the SQLite database.
+    #[arg(long, global = true, default_value = "asset_sync.db")]
+    db: PathBuf,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Catalog file operations (no database needed).
+    Catalog {
+        #[command(subcommand)]
+        command: CatalogCommand,
+    },
+    /// Reconcile a catalog file against the database's manifests.
+    Apply {
+        /// Catalog TOML file.
+        #[arg(long)]
+        file: PathBuf,
+        /// Report what would be created/updated/closed without writing.
+        #[arg(long)]
+        dry_run: bool,
+        /// What happens to manifests the catalog no longer declares.
+        #[arg(long, value_enum, default_value_t = SyncModeChoice::Prune)]
+        mode: SyncModeChoice,
+    },
+    /// Gap queue operations.
+    Gaps {
+        #[command(subcommand)]
+        command: GapsCommand,
+    },
+    /// Venue/session profile operations.
+    Profile {
+        #[command(subcommand)]
+        command: ProfileCommand,
+    },
+    /// Pre-flight health checks: migrations current, WAL mode, foreign
+    /// keys consistent, coverage blobs intact. Non-zero exit on failure.
+    Doctor,
+    /// Data-provider API operations (no database needed).
+    Provider {
+        #[command(subcommand)]
+        command: ProviderCommand,
+    },
+    /// Serve read-only coverage/gap status over HTTP for dashboards
+    /// (requires a `server`-enabled build).
+    #[cfg(feature = "server")]
+    Serve {
+        /// Address to bind.
+        #[arg(long, default_value = "127.0.0.1:8383")]
+        addr: std::net::SocketAddr,
+    },
+    /// Cross-check a manifest's coverage bitmap against the bars actually
+    /// stored in a Delta table (requires a `delta`-enabled build).
+    #[cfg(feature = "delta")]
+    Verify {
+        /// Delta table location (URL or local directory).
+        #[arg(long)]
+        table: String,
+        /// Manifest to check.
+        #[arg(long)]
+        manifest: i64,
+    },
+    /// Rebuild coverage from the bars stored in a Delta table — after a
+    /// backup restore or an external import — without refetching anything
+    /// (requires a `delta`-enabled build).
+    #[cfg(feature = "delta")]
+    Reconcile {
+        /// Delta table location (URL or local directory).
+        #[arg(long)]
+        table: String,
+    },
+}
+
+#[derive(Subcommand)]
+enum CatalogCommand {
+    /// Parse a catalog file and print it back normalized, plus what
+    /// normalization changed (renames, dropped duplicates).
+    Show {
+        /// Catalog TOML file.
+        #[arg(long)]
+        file: PathBuf,
+        /// Output format for the normalized catalog.
+        #[arg(long, value_enum, default_value_t = OutputFormat::Toml)]
+        format: OutputFormat,
+    },
+    /// Check a catalog file for mistakes syncing would hide: silently
+    /// dropped duplicates, specs with no timeframes, intervals the
+    /// provider rejects. Exits non-zero if any finding is an error.
+    Lint {
+        /// Catalog TOML file.
+        #[arg(long)]
+        file: PathBuf,
+    },
+    /// Print a valid one-asset catalog TOML as a copy-pasteable template.
+    Scaffold {
+        /// Symbol, e.g. AAPL or BTC/USD.
+        #[arg(long)]
+        symbol: String,
+        /// Provider name, e.g. alpaca.
+        #[arg(long)]
+        provider: String,
+        /// Asset class, e.g. us_equity or crypto.
+        #[arg(long)]
+        asset_class: String,
+        /// Bar interval as <amount><unit>, e.g. 5Minute, 1Hour, 1Day.
+        /// Repeat for multiple timeframes.
+        #[arg(long = "timeframe", required = true)]
+        timeframes: Vec<String>,
+        /// Desired window start (RFC 3339).
+        #[arg(long)]
+        start: chrono::DateTime<Utc>,
+        /// Desired window end (RFC 3339); omit for an open-ended window.
+        #[arg(long)]
+        end: Option<chrono::DateTime<Utc>>,
+    },
+}
+
+#[derive(Subcommand)]
+enum GapsCommand {
+    /// List dead-lettered gaps, or put one back in the queue.
+    Dead {
+        /// Only show gaps of this manifest.
+        #[arg(long)]
+        manifest: Option<i64>,
+        /// Reset this dead gap to open with a clean attempt count.
+        #[arg(long)]
+        requeue: Option<i64>,
+    },
+    /// Count gaps per state — how much work is queued, leased, finished
+    /// or dead-lettered.
+    Summary {
+        /// Only count gaps of this manifest.
+        #[arg(long)]
+        manifest: Option<i64>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ProviderCommand {
+    /// Probe the provider with a tiny request to confirm credentials work
+    /// before a backfill run.
+    Check {
+        /// Alpaca config TOML (api_key_id / api_secret_key).
+        #[arg(long)]
+        config: PathBuf,
+    },
+    /// Confirm a symbol is listed before declaring it in the catalog,
+    /// printing its name, exchange and listing status.
+    Lookup {
+        /// Alpaca config TOML (api_key_id / api_secret_key).
+        #[arg(long)]
+        config: PathBuf,
+        /// Symbol to look up, e.g. AAPL or BTC/USD.
+        symbol: String,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum OutputFormat {
+    Toml,
+    Json,
+}
+
+/// CLI face of [`asset_sync::catalog::SyncMode`].
+#[derive(Clone, Copy, ValueEnum)]
+enum SyncModeChoice {
+    /// Only add or refresh declared manifests.
+    Additive,
+    /// Also close open manifests the catalog dropped (default).
+    Prune,
+    /// Delete everything undeclared so the DB mirrors the file exactly.
+    Replace,
+}
+
+impl From<SyncModeChoice> for asset_sync::catalog::SyncMode {
+    fn from(choice: SyncModeChoice) -> Self {
+        match choice {
+            SyncModeChoice::Additive => asset_sync::catalog::SyncMode::Additive,
+            SyncModeChoice::Prune => asset_sync::catalog::SyncMode::Prune,
+            SyncModeChoice::Replace => asset_sync::catalog::SyncMode::Replace,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+enum ProfileCommand {
+    /// Snapshot a new session profile for a manifest, closing the
+    /// previous one.
+    Upsert {
+        /// Manifest the profile applies to.
+        #[arg(long)]
+        manifest: i64,
+        /// Venue code, e.g. XNYS.
+        #[arg(long)]
+        venue: String,
+        /// IANA timezone of the venue, e.g. America/New_York.
+        #[arg(long)]
+        tz: String,
+        /// Use the extended session (04:00-20:00 ET) instead of regular.
+        #[arg(long)]
+        extended: bool,
+        /// Holiday calendar id, e.g. nyse.
+        #[arg(long)]
+        calendar: Option<String>,
+    },
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    match cli.command {
+        Command::Catalog { command } => match command {
+            CatalogCommand::Show { file, format } => catalog_show(&file, format),
+            CatalogCommand::Lint { file } => catalog_lint(&file),
+            CatalogCommand::Scaffold {
+                symbol,
+                provider,
+                asset_class,
+                timeframes,
+                start,
+                end,
+            } => catalog_scaffold(symbol, provider, asset_class, &timeframes, start, end),
+        },
+        Command::Apply {
+            file,
+            dry_run,
+            mode,
+        } => {
+            let conn = Connection::open(&cli.db)
+                .with_context(|| format!("opening database {:?}", cli.db))?;
+            SqliteRepo::init(&conn)?;
+            run_apply(&conn, &file, dry_run, mode.into())
+        }
+        Command::Gaps { command } => {
+            let conn = Connection::open(&cli.db)
+                .with_context(|| format!("opening database {:?}", cli.db))?;
+            SqliteRepo::init(&conn)?;
+            run_gaps(&conn, command)
+        }
+        Command::Profile { command } => {
+            let conn = Connection::open(&cli.db)
+                .with_context(|| format!("opening database {:?}", cli.db))?;
+            SqliteRepo::init(&conn)?;
+            run_profile(&conn, command)
+        }
+        Command::Doctor => {
+            // Deliberately no `init`: doctor reports on the DB as it is
+            // rather than migrating it as a side effect.
+            let conn = Connection::open(&cli.db)
+                .with_context(|| format!("opening database {:?}", cli.db))?;
+            run_doctor(&conn)
+        }
+        Command::Provider { command } => run_provider(command),
+        #[cfg(feature = "server")]
+        Command::Serve { addr } => {
+            let conn = Connection::open(&cli.db)
+                .with_context(|| format!("opening database {:?}", cli.db))?;
+            SqliteRepo::init(&conn)?;
+            eprintln!("serving on http://{addr}");
+            asset_sync::server::serve(conn, addr).context("HTTP server")
+        }
+        #[cfg(feature = "delta")]
+        Command::Verify { table, manifest } => {
+            let conn = Connection::open(&cli.db)
+                .with_context(|| format!("opening database {:?}", cli.db))?;
+            SqliteRepo::init(&conn)?;
+            run_verify(&conn, &table, manifest)
+        }
+        #[cfg(feature = "delta")]
+        Command::Reconcile { table } => {
+            let conn = Connection::open(&cli.db)
+                .with_context(|| format!("opening database {:?}", cli.db))?;
+            SqliteRepo::init(&conn)?;
+            run_reconcile(&conn, &table)
+        }
+    }
+}
+
+#[cfg(feature = "delta")]
+fn run_reconcile(conn: &Connection, table: &str) -> anyhow::Result<()> {
+    let reports = asset_sync::verify::reconcile_coverage(conn, table, Utc::now())?;
+    let mut total = 0;
+    for report in &reports {
+        println!(
+            "manifest {}\t{}\t{} bucket(s) newly covered",
+            report.manifest_id, report.symbol, report.buckets_added
+        );
+        total += report.buckets_added;
+    }
+    eprintln!(
+        "{} manifest(s) reconciled, {total} bucket(s) newly covered",
+        reports.len()
+    );
+    Ok(())
+}
+
+#[cfg(feature = "delta")]
+fn run_verify(conn: &Connection, table: &str, manifest_id: i64) -> anyhow::Result<()> {
+    let report = asset_sync::verify::verify_manifest(conn, table, manifest_id)?;
+    for (start, end) in &report.phantom_ranges {
+        println!("phantom {} .. {}", start.to_rfc3339(), end.to_rfc3339());
+    }
+    eprintln!(
+        "manifest {}: {} covered buckets, {} phantom",
+        report.manifest_id, report.covered_buckets, report.phantom_buckets
+    );
+    if report.phantom_buckets > 0 {
+        bail!(
+            "{} covered buckets have no stored bars",
+            report.phantom_buckets
+        );
+    }
+    Ok(())
+}
+
+fn run_apply(
+    conn: &Connection,
+    file: &std::path::Path,
+    dry_run: bool,
+    mode: asset_sync::catalog::SyncMode,
+) -> anyhow::Result<()> {
+    let (catalog, report) =
+        load_catalog_path_reported(file).with_context(|| format!("loading catalog {file:?}"))?;
+    for line in report.warning_lines() {
+        eprintln!("{line}");
+    }
+    if dry_run {
+        let preview = asset_sync::catalog::preview_sync(conn, &catalog)?;
+        for label in &preview.created {
+            println!("create\t{label}");
+        }
+        for label in &preview.updated {
+            println!("update\t{label}");
+        }
+        for label in &preview.orphaned {
+            println!("close\t{label}");
+        }
+        eprintln!(
+            "dry run: {} created, {} updated, {} closed",
+            preview.created.len(),
+            preview.updated.len(),
+            preview.orphaned.len()
+        );
+        return Ok(());
+    }
+    let diff = asset_sync::catalog::sync_catalog_with(conn, &catalog, mode)?;
+    for warning in &diff.warnings {
+        eprintln!("warning: {warning}");
+    }
+    eprintln!(
+        "{} assets created, {} manifests upserted, {} closed, {} deleted",
+        diff.assets_created, diff.manifests_upserted, diff.manifests_closed, diff.manifests_deleted
+    );
+    Ok(())
+}
+
+fn run_gaps(conn: &Connection, command: GapsCommand) -> anyhow::Result<()> {
+    match command {
+        GapsCommand::Dead { manifest, requeue } => {
+            if let Some(gap_id) = requeue {
+                SqliteRepo::gaps_requeue_dead(conn, gap_id)?;
+                eprintln!("gap {gap_id} requeued");
+                return Ok(());
+            }
+            for gap in SqliteRepo::gaps_list_dead(conn, manifest)? {
+                println!(
+                    "{}\tmanifest {}\tbuckets {}..={}\tattempts {}",
+                    gap.gap_id, gap.manifest_id, gap.start_bucket, gap.end_bucket, gap.attempts
+                );
+            }
+            Ok(())
+        }
+        GapsCommand::Summary { manifest } => {
+            let summary = SqliteRepo::gaps_summary(conn, manifest)?;
+            println!(
+                "open {}\tleased {}\tdone {}\tdead {}",
+                summary.open, summary.leased, summary.done, summary.dead
+            );
+            Ok(())
+        }
+    }
+}
+
+fn run_doctor(conn: &Connection) -> anyhow::Result<()> {
+    let results = asset_sync::doctor::run_checks(conn)?;
+    let mut failures = 0;
+    for check in &results {
+        let verdict = if check.passed { "ok" } else { "FAIL" };
+        println!("{verdict}\t{}\t{}", check.name, check.detail);
+        if !check.passed {
+            failures += 1;
+        }
+    }
+    if failures > 0 {
+        bail!("{failures} of {} checks failed", results.len());
+    }
+    Ok(())
+}
+
+fn run_provider(command: ProviderCommand) -> anyhow::Result<()> {
+    use market_data_ingestor::providers::DataProvider;
+    use market_data_ingestor::providers::alpaca::{AlpacaConfig, AlpacaProvider};
+
+    match command {
+        ProviderCommand::Check { config } => {
+            let config = AlpacaConfig::from_toml_path(&config)?;
+            AlpacaProvider::new(config)
+                .health_check()
+                .context("provider alpaca health check failed")?;
+            eprintln!("provider alpaca: ok");
+            Ok(())
+        }
+        ProviderCommand::Lookup { config, symbol } => {
+            let config = AlpacaConfig::from_toml_path(&config)?;
+            let info = AlpacaProvider::new(config)
+                .fetch_asset(&symbol, "us_equity")
+                .context("provider alpaca asset lookup failed")?;
+            match info {
+                Some(info) => {
+                    println!(
+                        "{}\t{}\t{}\ttradable={}\t{}",
+                        info.symbol, info.name, info.exchange, info.tradable, info.status
+                    );
+                    Ok(())
+                }
+                None => bail!("symbol {symbol} is not listed on alpaca"),
+            }
+        }
+    }
+}
+
+fn run_profile(conn: &Connection, command: ProfileCommand) -> anyhow::Result<()> {
+    match command {
+        ProfileCommand::Upsert {
+            manifest,
+            venue,
+            tz,
+            extended,
+            calendar,
+        } => profile_upsert(conn, manifest, &venue, &tz, extended, calendar),
+    }
+}
+
+fn catalog_show(file: &std::path::Path, format: OutputFormat) -> anyhow::Result<()> {
+    let (catalog, report) =
+        load_catalog_path_reported(file).with_context(|| format!("loading catalog {file:?}"))?;
+
+    match format {
+        OutputFormat::Toml => {
+            // Report goes to stderr so stdout stays a valid catalog file.
+            print!("{}", toml::to_string_pretty(&catalog)?);
+            for line in report.warning_lines() {
+                eprintln!("{line}");
+            }
+            if report.is_clean() {
+                eprintln!("catalog was already normalized");
+            }
+        }
+        OutputFormat::Json => {
+            let doc = serde_json::json!({ "catalog": catalog, "report": report });
+            println!("{}", serde_json::to_string_pretty(&doc)?);
+        }
+    }
+    Ok(())
+}
+
+fn catalog_lint(file: &std::path::Path) -> anyhow::Result<()> {
+    // Parse without the loader's normalization, so lint still sees the
+    // duplicates loading would silently drop.
+    let text =
+        std::fs::read_to_string(file).with_context(|| format!("reading catalog {file:?}"))?;
+    let catalog: asset_sync::catalog::Catalog =
+        toml::from_str(&text).with_context(|| format!("parsing catalog {file:?}"))?;
+
+    let findings = asset_sync::catalog::lint(&catalog);
+    let mut errors = 0usize;
+    for finding in &findings {
+        match finding.level {
+            asset_sync::catalog::LintLevel::Error => {
+                errors += 1;
+                eprintln!("error: {}", finding.message);
+            }
+            asset_sync::catalog::LintLevel::Warning => {
+                eprintln!("warning: {}", finding.message);
+            }
+        }
+    }
+    if errors > 0 {
+        anyhow::bail!("{errors} error(s), {} warning(s)", findings.len() - errors);
+    }
+    if findings.is_empty() {
+        eprintln!("catalog is clean");
+    }
+    Ok(())
+}
+
+/// Parse a `--timeframe` flag like `5Minute` into its catalog form. The
+/// unit casing is forgiving; validation of the unit itself happens in
+/// the catalog scaffold, which knows the full error vocabulary.
+fn parse_timeframe_flag(s: &str) -> anyhow::Result<asset_sync::catalog::TimeframeCfg> {
+    asset_sync::catalog::TimeframeCfg::parse_compact(s)
+        .ok_or_else(|| anyhow::anyhow!("timeframe {s:?}: expected <amount><unit>, e.g. 5Minute"))
+}
+
+fn catalog_scaffold(
+    symbol: String,
+    provider: String,
+    asset_class: String,
+    timeframes: &[String],
+    start: chrono::DateTime<Utc>,
+    end: Option<chrono::DateTime<Utc>>,
+) -> anyhow::Result<()> {
+    let timeframes = timeframes
+        .iter()
+        .map(|s| parse_timeframe_flag(s))
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    let spec = asset_sync::catalog::AssetSpec {
+        symbol,
+        asset_class,
+        provider,
+        start,
+        end,
+        timeframes,
+    };
+    let rendered = asset_sync::catalog::scaffold_catalog_toml(spec)?;
+    print!("{rendered}");
+    Ok(())
+}
+
+fn is_venue_slug(venue: &str) -> bool {
+    !venue.is_empty()
+        && venue
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+fn profile_upsert(
+    conn: &Connection,
+    manifest_id: i64,
+    venue: &str,
+    tz: &str,
+    extended: bool,
+    calendar: Option<String>,
+) -> anyhow::Result<()> {
+    if !is_venue_slug(venue) {
+        bail!("venue {venue:?} is not a slug (alphanumeric, '-' or '_')");
+    }
+    let parsed_tz: chrono_tz::Tz = tz
+        .parse()
+        .map_err(|e| anyhow::anyhow!("invalid IANA timezone {tz:?}: {e}"))?;
+    // Ensure the manifest exists before writing a profile for it.
+    SqliteRepo::manifest_by_id(conn, manifest_id)?;
+
+    let session = if extended {
+        SessionCalendar::extended()
+    } else {
+        SessionCalendar::regular()
+    };
+    let policy_json = serde_json::json!({
+        "venue": venue,
+        "tz": parsed_tz.name(),
+        "use_extended": extended,
+        "session": { "open": session.open.to_string(), "close": session.close.to_string() },
+        "calendar": calendar,
+    })
+    .to_string();
+
+    let valid_from = Utc::now();
+    SqliteRepo::upsert_profile(
+        conn,
+        &NewAssetProfile {
+            manifest_id,
+            venue_code: venue.to_string(),
+            tz: parsed_tz.name().to_string(),
+            use_extended: extended,
+            calendar_id: calendar,
+            policy_json,
+            source: "cli".to_string(),
+        },
+        valid_from,
+    )?;
+    println!("{}", valid_from.to_rfc3339());
+    Ok(())
+}
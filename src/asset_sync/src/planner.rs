@@ -0,0 +1,252 @@
+//! Turns manifests + provider capabilities into an ordered fetch schedule.
+//!
+//! The declarative layer (catalog → manifests → coverage) says *what* is
+//! missing; providers say *how much* can be asked for at once. `plan` sits
+//! between the two: it computes missing ranges per open manifest, merges
+//! symbols that miss the identical (provider, timeframe, range), and chunks
+//! the merged symbol lists to each provider's `max_symbols_per_request`.
+
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{DateTime, Utc};
+use rusqlite::Connection;
+use thiserror::Error;
+
+use crate::coverage::{CoverageError, compute_missing_with_manifest};
+use crate::repo::{RepoError, SqliteRepo};
+use crate::session::HolidayCalendar;
+use crate::timeframe::Timeframe;
+
+#[derive(Debug, Error)]
+pub enum PlanError {
+    #[error(transparent)]
+    Repo(#[from] RepoError),
+    #[error(transparent)]
+    Coverage(#[from] CoverageError),
+    #[error("no capabilities registered for provider {0:?}")]
+    UnknownProvider(String),
+    #[error(
+        "manifest {manifest_id} ({symbol}) spans ~{buckets} buckets, over the ceiling of {ceiling}"
+    )]
+    WindowTooLarge {
+        manifest_id: i64,
+        symbol: String,
+        buckets: u64,
+        ceiling: u64,
+    },
+}
+
+/// Request-shaping limits of a provider, as known to the planner.
+#[derive(Debug, Clone)]
+pub struct ProviderCaps {
+    pub max_symbols_per_request: usize,
+}
+
+/// One executable unit of work: fetch `symbols` at `timeframe` over
+/// `[start, end)` from `provider`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedFetch {
+    pub provider: String,
+    pub timeframe: Timeframe,
+    pub symbols: Vec<String>,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+type GroupKey = (String, Timeframe, DateTime<Utc>, DateTime<Utc>);
+
+/// Build the fetch schedule for every open manifest as of `now`.
+///
+/// Output order is deterministic: by provider, then timeframe, then range
+/// start, then first symbol. Callers execute items in order and update
+/// coverage as results land.
+pub fn plan(
+    conn: &Connection,
+    provider_caps: &HashMap<String, ProviderCaps>,
+    now: DateTime<Utc>,
+) -> Result<Vec<PlannedFetch>, PlanError> {
+    plan_with_ceiling(conn, provider_caps, now, u64::MAX)
+}
+
+/// [`plan`] with a per-manifest bucket ceiling: any manifest whose desired
+/// window is estimated to span more than `max_window_buckets` fails the
+/// whole plan before its bitmap is allocated, so a typo'd `desired_start`
+/// of 1970 on minute bars surfaces as an error instead of a multi-gigabyte
+/// computation.
+pub fn plan_with_ceiling(
+    conn: &Connection,
+    provider_caps: &HashMap<String, ProviderCaps>,
+    now: DateTime<Utc>,
+    max_window_buckets: u64,
+) -> Result<Vec<PlannedFetch>, PlanError> {
+    // (provider, timeframe, start, end) -> symbols missing that exact range.
+    let mut groups: BTreeMap<GroupKey, Vec<String>> = BTreeMap::new();
+
+    for manifest in SqliteRepo::manifests_open(conn)? {
+        // Paused manifests keep their missing coverage but get no work.
+        if !manifest.enabled {
+            continue;
+        }
+        if !provider_caps.contains_key(&manifest.provider) {
+            return Err(PlanError::UnknownProvider(manifest.provider));
+        }
+        let window_end = manifest.desired_end.unwrap_or(now).min(now);
+        let buckets = crate::bucket::estimate_bucket_count(
+            (manifest.desired_start, window_end),
+            &manifest.timeframe,
+        );
+        if buckets > max_window_buckets {
+            return Err(PlanError::WindowTooLarge {
+                manifest_id: manifest.manifest_id,
+                symbol: manifest.symbol,
+                buckets,
+                ceiling: max_window_buckets,
+            });
+        }
+        // The manifest row is already in hand; don't re-query it per id.
+        for (start, end) in
+            compute_missing_with_manifest(conn, &manifest, now, &HolidayCalendar::empty())?
+        {
+            groups
+                .entry((manifest.provider.clone(), manifest.timeframe, start, end))
+                .or_default()
+                .push(manifest.symbol.clone());
+        }
+    }
+
+    let mut planned = Vec::new();
+    for ((provider, timeframe, start, end), mut symbols) in groups {
+        symbols.sort();
+        symbols.dedup();
+        let caps = &provider_caps[&provider];
+        for chunk in symbols.chunks(caps.max_symbols_per_request.max(1)) {
+            planned.push(PlannedFetch {
+                provider: provider.clone(),
+                timeframe,
+                symbols: chunk.to_vec(),
+                start,
+                end,
+            });
+        }
+    }
+    Ok(planned)
+}
+
+#[cfg(test)]
+mod tests {
+    use roaring::RoaringBitmap;
+
+    use super::*;
+    use crate::bucket;
+    use crate::repo::test_support::*;
+    use crate::timeframe::TimeframeUnit;
+
+    fn caps(max_symbols: usize) -> HashMap<String, ProviderCaps> {
+        HashMap::from([(
+            "alpaca".to_string(),
+            ProviderCaps {
+                max_symbols_per_request: max_symbols,
+            },
+        )])
+    }
+
+    #[test]
+    fn merges_identical_ranges_and_chunks_symbols() {
+        let conn = mem_conn();
+        let tf = Timeframe::new(1, TimeframeUnit::Hour).unwrap();
+        let start = utc(2024, 1, 1, 0, 0);
+        let end = utc(2024, 1, 1, 4, 0);
+        for sym in ["AAPL", "MSFT", "NVDA"] {
+            insert_manifest(&conn, sym, "alpaca", tf, start, Some(end));
+        }
+
+        let planned = plan(&conn, &caps(2), utc(2024, 6, 1, 0, 0)).unwrap();
+        // Three symbols missing one identical range, chunked 2 + 1.
+        assert_eq!(planned.len(), 2);
+        assert_eq!(planned[0].symbols, vec!["AAPL", "MSFT"]);
+        assert_eq!(planned[1].symbols, vec!["NVDA"]);
+        assert_eq!(planned[0].start, start);
+        assert_eq!(planned[0].end, end);
+    }
+
+    #[test]
+    fn disabled_manifests_are_not_planned() {
+        let conn = mem_conn();
+        let tf = Timeframe::new(1, TimeframeUnit::Hour).unwrap();
+        let start = utc(2024, 1, 1, 0, 0);
+        let end = utc(2024, 1, 1, 4, 0);
+        let paused = insert_manifest(&conn, "AAPL", "alpaca", tf, start, Some(end));
+        insert_manifest(&conn, "MSFT", "alpaca", tf, start, Some(end));
+        SqliteRepo::manifest_set_enabled(&conn, paused, false).unwrap();
+
+        // AAPL has the whole window missing, but is paused.
+        let planned = plan(&conn, &caps(10), utc(2024, 6, 1, 0, 0)).unwrap();
+        assert_eq!(planned.len(), 1);
+        assert_eq!(planned[0].symbols, vec!["MSFT"]);
+
+        SqliteRepo::manifest_set_enabled(&conn, paused, true).unwrap();
+        let planned = plan(&conn, &caps(10), utc(2024, 6, 1, 0, 0)).unwrap();
+        assert_eq!(planned[0].symbols, vec!["AAPL", "MSFT"]);
+    }
+
+    #[test]
+    fn oversized_windows_fail_before_any_bitmap_work() {
+        let conn = mem_conn();
+        let tf = Timeframe::new(1, TimeframeUnit::Hour).unwrap();
+        let start = utc(2024, 1, 1, 0, 0);
+        let end = utc(2024, 1, 2, 0, 0); // 24 hourly buckets
+        let id = insert_manifest(&conn, "AAPL", "alpaca", tf, start, Some(end));
+
+        let err = plan_with_ceiling(&conn, &caps(10), utc(2024, 6, 1, 0, 0), 10).unwrap_err();
+        match err {
+            PlanError::WindowTooLarge {
+                manifest_id,
+                buckets,
+                ceiling,
+                ..
+            } => {
+                assert_eq!(manifest_id, id);
+                assert_eq!(buckets, 24);
+                assert_eq!(ceiling, 10);
+            }
+            other => panic!("expected WindowTooLarge, got {other:?}"),
+        }
+        // A roomier ceiling plans normally.
+        assert_eq!(
+            plan_with_ceiling(&conn, &caps(10), utc(2024, 6, 1, 0, 0), 24)
+                .unwrap()
+                .len(),
+            1
+        );
+    }
+
+    #[test]
+    fn partial_coverage_splits_plans_deterministically() {
+        let conn = mem_conn();
+        let tf = Timeframe::new(1, TimeframeUnit::Hour).unwrap();
+        let start = utc(2024, 1, 1, 0, 0);
+        let end = utc(2024, 1, 1, 4, 0);
+        let m1 = insert_manifest(&conn, "AAPL", "alpaca", tf, start, Some(end));
+        insert_manifest(&conn, "MSFT", "alpaca", tf, start, Some(end));
+
+        // AAPL already has its first two hours.
+        let (first, _) = bucket::bucket_range(start, end, &tf).unwrap();
+        let mut bm = RoaringBitmap::new();
+        bm.insert(0);
+        bm.insert(1);
+        SqliteRepo::coverage_put(&conn, m1, 0, first, &bm).unwrap();
+
+        let planned = plan(&conn, &caps(10), utc(2024, 6, 1, 0, 0)).unwrap();
+        assert_eq!(planned.len(), 2);
+        // Ordered by range start: MSFT's full window first.
+        assert_eq!(planned[0].symbols, vec!["MSFT"]);
+        assert_eq!(planned[0].start, start);
+        assert_eq!(planned[1].symbols, vec!["AAPL"]);
+        assert_eq!(planned[1].start, utc(2024, 1, 1, 2, 0));
+        assert_eq!(planned[1].end, end);
+
+        // Deterministic across runs.
+        let again = plan(&conn, &caps(10), utc(2024, 6, 1, 0, 0)).unwrap();
+        assert_eq!(planned, again);
+    }
+}
@@ -0,0 +1,176 @@
+//! SCD-2 venue/session profiles per manifest.
+//!
+//! A profile snapshots the session policy (venue, tz, extended hours,
+//! calendar, plus the resolved policy as JSON) that was in force over a
+//! validity interval. Rows are never updated in place: upserting a new
+//! profile closes the previous open row at the new row's `valid_from_utc`,
+//! so the planner can reproduce historical desired-minutes from whatever
+//! profile was active at the time.
+
+use chrono::{DateTime, Utc};
+use rusqlite::{Connection, OptionalExtension, params};
+
+use crate::repo::{RepoError, SqliteRepo};
+
+#[derive(Debug, Clone)]
+pub struct AssetProfile {
+    pub id: i64,
+    pub manifest_id: i64,
+    pub venue_code: String,
+    pub tz: String,
+    pub use_extended: bool,
+    pub calendar_id: Option<String>,
+    pub policy_json: String,
+    pub source: String,
+    pub valid_from_utc: DateTime<Utc>,
+    pub valid_to_utc: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct NewAssetProfile {
+    pub manifest_id: i64,
+    pub venue_code: String,
+    pub tz: String,
+    pub use_extended: bool,
+    pub calendar_id: Option<String>,
+    pub policy_json: String,
+    pub source: String,
+}
+
+impl SqliteRepo {
+    /// Insert a new profile row valid from `valid_from`, closing the
+    /// previous open row at the same instant. One transaction; returns
+    /// the new row id.
+    pub fn upsert_profile(
+        conn: &Connection,
+        new: &NewAssetProfile,
+        valid_from: DateTime<Utc>,
+    ) -> Result<i64, RepoError> {
+        let tx = conn.unchecked_transaction()?;
+        tx.execute(
+            "UPDATE asset_profile SET valid_to_utc = ?2
+             WHERE manifest_id = ?1 AND valid_to_utc IS NULL",
+            params![new.manifest_id, valid_from.to_rfc3339()],
+        )?;
+        tx.execute(
+            "INSERT INTO asset_profile
+                 (manifest_id, venue_code, tz, use_extended, calendar_id, policy_json,
+                  source, valid_from_utc)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                new.manifest_id,
+                new.venue_code,
+                new.tz,
+                new.use_extended,
+                new.calendar_id,
+                new.policy_json,
+                new.source,
+                valid_from.to_rfc3339(),
+            ],
+        )?;
+        let id = tx.last_insert_rowid();
+        tx.commit()?;
+        Ok(id)
+    }
+
+    /// The profile in force at `as_of`: `valid_from_utc <= as_of` and
+    /// either still open or `valid_to_utc > as_of`. Validity intervals
+    /// are half-open, so at the rollover instant the new row wins.
+    pub fn active_profile(
+        conn: &Connection,
+        manifest_id: i64,
+        as_of: DateTime<Utc>,
+    ) -> Result<Option<AssetProfile>, RepoError> {
+        let as_of_s = as_of.to_rfc3339();
+        let profile = conn
+            .query_row(
+                "SELECT id, manifest_id, venue_code, tz, use_extended, calendar_id,
+                        policy_json, source, valid_from_utc, valid_to_utc
+                 FROM asset_profile
+                 WHERE manifest_id = ?1
+                   AND valid_from_utc <= ?2
+                   AND (valid_to_utc IS NULL OR valid_to_utc > ?2)
+                 ORDER BY valid_from_utc DESC
+                 LIMIT 1",
+                params![manifest_id, as_of_s],
+                profile_from_row,
+            )
+            .optional()?;
+        Ok(profile)
+    }
+}
+
+fn profile_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<AssetProfile> {
+    let valid_to: Option<String> = row.get(9)?;
+    Ok(AssetProfile {
+        id: row.get(0)?,
+        manifest_id: row.get(1)?,
+        venue_code: row.get(2)?,
+        tz: row.get(3)?,
+        use_extended: row.get(4)?,
+        calendar_id: row.get(5)?,
+        policy_json: row.get(6)?,
+        source: row.get(7)?,
+        valid_from_utc: parse(&row.get::<_, String>(8)?),
+        valid_to_utc: valid_to.as_deref().map(parse),
+    })
+}
+
+fn parse(s: &str) -> DateTime<Utc> {
+    DateTime::parse_from_rfc3339(s)
+        .expect("RFC 3339 timestamp in DB")
+        .with_timezone(&Utc)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repo::test_support::*;
+
+    fn new_profile(manifest_id: i64, venue: &str) -> NewAssetProfile {
+        NewAssetProfile {
+            manifest_id,
+            venue_code: venue.to_string(),
+            tz: "America/New_York".to_string(),
+            use_extended: false,
+            calendar_id: Some("nyse".to_string()),
+            policy_json: r#"{"open":"09:30","close":"16:00"}"#.to_string(),
+            source: "test".to_string(),
+        }
+    }
+
+    #[test]
+    fn upsert_closes_prior_open_row_at_boundary() {
+        let conn = mem_conn();
+        let m = insert_manifest(
+            &conn,
+            "AAPL",
+            "alpaca",
+            minute_tf(),
+            utc(2024, 1, 1, 0, 0),
+            None,
+        );
+
+        let t1 = utc(2024, 1, 1, 0, 0);
+        let t2 = utc(2024, 3, 1, 0, 0);
+        SqliteRepo::upsert_profile(&conn, &new_profile(m, "XNYS"), t1).unwrap();
+        SqliteRepo::upsert_profile(&conn, &new_profile(m, "XNAS"), t2).unwrap();
+
+        // Before the first row there is no profile.
+        assert!(
+            SqliteRepo::active_profile(&conn, m, t1 - chrono::Duration::seconds(1))
+                .unwrap()
+                .is_none()
+        );
+        // Just before rollover the old row is active and now closed.
+        let before = SqliteRepo::active_profile(&conn, m, t2 - chrono::Duration::seconds(1))
+            .unwrap()
+            .unwrap();
+        assert_eq!(before.venue_code, "XNYS");
+        assert_eq!(before.valid_to_utc, Some(t2));
+        // At the rollover instant the new row wins (half-open intervals).
+        let at = SqliteRepo::active_profile(&conn, m, t2).unwrap().unwrap();
+        assert_eq!(at.venue_code, "XNAS");
+        assert_eq!(at.valid_to_utc, None);
+    }
+}
@@ -0,0 +1,2042 @@
+//! SQLite persistence for the catalog, manifests, coverage bitmaps and gaps.
+//!
+//! All access goes through associated functions on [`SqliteRepo`] taking an
+//! explicit [`rusqlite::Connection`], so callers control transactions and
+//! connection lifetime. Schema changes are applied by [`SqliteRepo::init`]
+//! via a `PRAGMA user_version` migration ladder.
+
+use chrono::{DateTime, Utc};
+use roaring::RoaringBitmap;
+use rusqlite::{Connection, OptionalExtension, params};
+use thiserror::Error;
+
+use crate::timeframe::Timeframe;
+
+#[derive(Debug, Error)]
+pub enum RepoError {
+    #[error("sqlite error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+    #[error("manifest {0} not found")]
+    ManifestNotFound(i64),
+    #[error("asset {0} not found")]
+    AssetNotFound(i64),
+    #[error("coverage version conflict on manifest {manifest_id}: expected {expected}")]
+    CoverageVersionConflict { manifest_id: i64, expected: i64 },
+    #[error("corrupt coverage bitmap for manifest {0}")]
+    CorruptBitmap(i64),
+    #[error("gap {0} is not dead (or does not exist)")]
+    GapNotDead(i64),
+    #[error("gaps not found: {0:?}")]
+    GapsNotFound(Vec<i64>),
+    #[error("invalid IANA timezone {0:?}")]
+    InvalidTimezone(String),
+}
+
+/// Lifecycle of a manifest. `Open` manifests have a live desired window
+/// (open-ended or not); `Closed` ones are kept for their coverage history
+/// but no longer accrue desired buckets; `Complete` is the archived end
+/// state of a closed-range manifest whose window was fully covered
+/// ([`SqliteRepo::mark_complete_if_full`]).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ManifestStatus {
+    Open,
+    Closed,
+    Complete,
+}
+
+impl ManifestStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ManifestStatus::Open => "open",
+            ManifestStatus::Closed => "closed",
+            ManifestStatus::Complete => "complete",
+        }
+    }
+
+    fn from_db(s: &str) -> Self {
+        match s {
+            "open" => ManifestStatus::Open,
+            "closed" => ManifestStatus::Closed,
+            "complete" => ManifestStatus::Complete,
+            other => panic!("unknown manifest status in DB: {other}"),
+        }
+    }
+}
+
+/// One (asset, provider, timeframe) sync obligation.
+#[derive(Debug, Clone)]
+pub struct Manifest {
+    pub manifest_id: i64,
+    pub asset_id: i64,
+    pub symbol: String,
+    pub asset_class: String,
+    pub provider: String,
+    pub timeframe: Timeframe,
+    pub desired_start: DateTime<Utc>,
+    /// `None` means open-ended: desired up to "now" at compute time.
+    pub desired_end: Option<DateTime<Utc>>,
+    pub status: ManifestStatus,
+    /// Lease-ordering weight: higher-priority manifests' gaps lease first.
+    pub priority: i64,
+    /// Paused manifests (`false`) keep their coverage and gaps but are
+    /// skipped by the planner and the gap queue.
+    pub enabled: bool,
+    /// IANA zone for session math. `"UTC"` (the column default) means "no
+    /// venue override": session-bound asset classes keep their built-in
+    /// exchange zone.
+    pub venue_tz: String,
+}
+
+/// Fields needed to upsert a manifest; ids are assigned by the DB.
+#[derive(Debug, Clone)]
+pub struct NewManifest {
+    pub asset_id: i64,
+    pub provider: String,
+    pub timeframe: Timeframe,
+    pub desired_start: DateTime<Utc>,
+    pub desired_end: Option<DateTime<Utc>>,
+}
+
+/// State machine for a gap row: `open` → `leased` → `done`, with `leased`
+/// falling back to `open` on lease expiry or failure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GapState {
+    Open,
+    Leased,
+    Done,
+    Dead,
+}
+
+impl GapState {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            GapState::Open => "open",
+            GapState::Leased => "leased",
+            GapState::Done => "done",
+            GapState::Dead => "dead",
+        }
+    }
+
+    /// Parse a state name as stored in the DB (and as accepted from
+    /// user-facing filters); `None` for anything unrecognized.
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "open" => Some(GapState::Open),
+            "leased" => Some(GapState::Leased),
+            "done" => Some(GapState::Done),
+            "dead" => Some(GapState::Dead),
+            _ => None,
+        }
+    }
+
+    fn from_db(s: &str) -> Self {
+        GapState::parse(s).unwrap_or_else(|| panic!("unknown gap state in DB: {s}"))
+    }
+}
+
+/// Versioned coverage bitmap of one manifest; ids are relative to
+/// `bucket_base`.
+#[derive(Debug, Clone)]
+pub struct CoverageSnapshot {
+    pub version: i64,
+    pub bucket_base: u64,
+    pub bitmap: RoaringBitmap,
+}
+
+/// A contiguous run of missing buckets queued for backfill.
+#[derive(Debug, Clone)]
+pub struct Gap {
+    pub gap_id: i64,
+    pub manifest_id: i64,
+    pub start_bucket: u32,
+    pub end_bucket: u32,
+    pub state: GapState,
+    pub attempts: i64,
+    pub lease_expires_at: Option<DateTime<Utc>>,
+    /// Worker holding the current (possibly expired) lease.
+    pub leased_by: Option<String>,
+}
+
+/// Gap counts per state — the "how much work is queued?" answer for
+/// dashboards, without dumping rows ([`SqliteRepo::gaps_summary`]).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GapSummary {
+    pub open: u64,
+    pub leased: u64,
+    pub done: u64,
+    pub dead: u64,
+}
+
+/// The `PRAGMA user_version` a fully migrated database reports. Bump this
+/// together with every new rung added to [`SqliteRepo::init`]'s ladder.
+pub const SCHEMA_VERSION: i64 = 10;
+
+/// The default coverage session: one undivided bitmap per manifest.
+/// Manifests that track regular vs extended hours separately store one
+/// bitmap per session key instead.
+pub const SESSION_ALL: &str = "all";
+
+/// Whether an upsert created a fresh row or refreshed an existing one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpsertOutcome {
+    Inserted,
+    Updated,
+}
+
+/// Failed gaps move to `dead` once they have been attempted this many times.
+pub const MAX_GAP_ATTEMPTS: i64 = 5;
+
+/// How coverage-version conflict retries pace themselves. Without a pause
+/// between rounds, several workers hammering one hot manifest spin on
+/// re-read/re-apply; the jitter decorrelates them so they stop colliding
+/// on the same version repeatedly.
+#[derive(Debug, Clone, Copy)]
+pub struct ConflictBackoff {
+    /// Sleep before the first retry; doubles per round.
+    pub base: std::time::Duration,
+    /// Cap on any single sleep.
+    pub max: std::time::Duration,
+    /// Fraction (0.0..=1.0) of the sleep randomly shaved off, so
+    /// identically-timed workers drift apart.
+    pub jitter: f64,
+}
+
+impl Default for ConflictBackoff {
+    fn default() -> Self {
+        ConflictBackoff {
+            base: std::time::Duration::from_millis(5),
+            max: std::time::Duration::from_millis(200),
+            jitter: 0.5,
+        }
+    }
+}
+
+impl ConflictBackoff {
+    /// No sleeping at all — the pre-backoff behavior, for tests and
+    /// single-worker tools.
+    pub fn none() -> Self {
+        ConflictBackoff {
+            base: std::time::Duration::ZERO,
+            max: std::time::Duration::ZERO,
+            jitter: 0.0,
+        }
+    }
+
+    /// Sleep before retry number `retry` (0-based): exponential from
+    /// `base`, capped at `max`, minus a random share of `jitter`.
+    fn delay_for(&self, retry: u32) -> std::time::Duration {
+        let exp = self.base.saturating_mul(2u32.saturating_pow(retry));
+        let capped = exp.min(self.max);
+        if self.jitter <= 0.0 || capped.is_zero() {
+            return capped;
+        }
+        // Sub-second clock noise is plenty of entropy for decorrelation;
+        // no RNG dependency needed.
+        let noise = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.subsec_nanos())
+            .unwrap_or(0);
+        let share = f64::from(noise % 1_000) / 1_000.0;
+        capped.mul_f64(1.0 - self.jitter.clamp(0.0, 1.0) * share)
+    }
+}
+
+pub struct SqliteRepo;
+
+impl SqliteRepo {
+    /// Create or migrate the schema. Idempotent.
+    pub fn init(conn: &Connection) -> Result<(), RepoError> {
+        let version: i64 = conn.query_row("PRAGMA user_version", [], |r| r.get(0))?;
+        if version < 1 {
+            conn.execute_batch(
+                "CREATE TABLE assets (
+                     asset_id    INTEGER PRIMARY KEY,
+                     symbol      TEXT NOT NULL,
+                     asset_class TEXT NOT NULL,
+                     UNIQUE (symbol, asset_class)
+                 );
+                 CREATE TABLE manifests (
+                     manifest_id   INTEGER PRIMARY KEY,
+                     asset_id      INTEGER NOT NULL REFERENCES assets (asset_id),
+                     provider      TEXT NOT NULL,
+                     tf_amount     INTEGER NOT NULL,
+                     tf_unit       TEXT NOT NULL,
+                     desired_start TEXT NOT NULL,
+                     desired_end   TEXT,
+                     status        TEXT NOT NULL DEFAULT 'open',
+                     UNIQUE (asset_id, provider, tf_amount, tf_unit)
+                 );
+                 CREATE TABLE coverage (
+                     manifest_id INTEGER PRIMARY KEY REFERENCES manifests (manifest_id),
+                     version     INTEGER NOT NULL,
+                     roaring     BLOB NOT NULL
+                 );
+                 CREATE TABLE gaps (
+                     gap_id           INTEGER PRIMARY KEY,
+                     manifest_id      INTEGER NOT NULL REFERENCES manifests (manifest_id),
+                     start_bucket     INTEGER NOT NULL,
+                     end_bucket       INTEGER NOT NULL,
+                     state            TEXT NOT NULL DEFAULT 'open',
+                     attempts         INTEGER NOT NULL DEFAULT 0,
+                     lease_expires_at TEXT
+                 );
+                 CREATE INDEX gaps_state_idx ON gaps (state, manifest_id);
+                 PRAGMA user_version = 1;",
+            )?;
+        }
+        if version < 2 {
+            conn.execute_batch(
+                "CREATE TABLE asset_profile (
+                     id            INTEGER PRIMARY KEY,
+                     manifest_id   INTEGER NOT NULL REFERENCES manifests (manifest_id),
+                     venue_code    TEXT NOT NULL,
+                     tz            TEXT NOT NULL,
+                     use_extended  INTEGER NOT NULL DEFAULT 0,
+                     calendar_id   TEXT,
+                     policy_json   TEXT NOT NULL,
+                     source        TEXT NOT NULL,
+                     valid_from_utc TEXT NOT NULL,
+                     valid_to_utc   TEXT
+                 );
+                 CREATE INDEX asset_profile_manifest_idx
+                     ON asset_profile (manifest_id, valid_from_utc);
+                 PRAGMA user_version = 2;",
+            )?;
+        }
+        if version < 3 {
+            conn.execute_batch(
+                "ALTER TABLE coverage ADD COLUMN bucket_base INTEGER NOT NULL DEFAULT 0;
+                 PRAGMA user_version = 3;",
+            )?;
+        }
+        if version < 4 {
+            conn.execute_batch(
+                "CREATE TABLE audit_log (
+                     audit_id    INTEGER PRIMARY KEY,
+                     ts          TEXT NOT NULL,
+                     actor       TEXT NOT NULL,
+                     action      TEXT NOT NULL,
+                     entity      TEXT NOT NULL,
+                     detail_json TEXT NOT NULL
+                 );
+                 PRAGMA user_version = 4;",
+            )?;
+        }
+        if version < 5 {
+            conn.execute_batch(
+                "ALTER TABLE gaps ADD COLUMN leased_by TEXT;
+                 PRAGMA user_version = 5;",
+            )?;
+        }
+        if version < 6 {
+            conn.execute_batch(
+                "ALTER TABLE manifests ADD COLUMN priority INTEGER NOT NULL DEFAULT 0;
+                 PRAGMA user_version = 6;",
+            )?;
+        }
+        if version < 7 {
+            conn.execute_batch(
+                "ALTER TABLE manifests ADD COLUMN enabled INTEGER NOT NULL DEFAULT 1;
+                 PRAGMA user_version = 7;",
+            )?;
+        }
+        if version < 8 {
+            conn.execute_batch(
+                "ALTER TABLE manifests ADD COLUMN venue_tz TEXT NOT NULL DEFAULT 'UTC';
+                 PRAGMA user_version = 8;",
+            )?;
+        }
+        if version < 9 {
+            conn.execute_batch(
+                "CREATE TABLE symbol_map (
+                     provider  TEXT NOT NULL,
+                     canonical TEXT NOT NULL,
+                     remote    TEXT NOT NULL,
+                     PRIMARY KEY (provider, canonical),
+                     UNIQUE (provider, remote)
+                 );
+                 PRAGMA user_version = 9;",
+            )?;
+        }
+        if version < 10 {
+            // The coverage key grows a session discriminator, which means
+            // a new primary key — SQLite cannot alter one in place, so the
+            // table is rebuilt and existing rows carry over as 'all'.
+            conn.execute_batch(
+                "ALTER TABLE coverage RENAME TO coverage_old;
+                 CREATE TABLE coverage (
+                     manifest_id INTEGER NOT NULL REFERENCES manifests (manifest_id),
+                     session     TEXT NOT NULL DEFAULT 'all',
+                     version     INTEGER NOT NULL,
+                     roaring     BLOB NOT NULL,
+                     bucket_base INTEGER NOT NULL DEFAULT 0,
+                     PRIMARY KEY (manifest_id, session)
+                 );
+                 INSERT INTO coverage (manifest_id, session, version, roaring, bucket_base)
+                     SELECT manifest_id, 'all', version, roaring, bucket_base
+                     FROM coverage_old;
+                 DROP TABLE coverage_old;
+                 PRAGMA user_version = 10;",
+            )?;
+        }
+        Ok(())
+    }
+
+    // ---- assets ----
+
+    /// Insert the asset if new, returning its id either way.
+    pub fn upsert_asset(
+        conn: &Connection,
+        symbol: &str,
+        asset_class: &str,
+    ) -> Result<i64, RepoError> {
+        conn.execute(
+            "INSERT INTO assets (symbol, asset_class) VALUES (?1, ?2)
+             ON CONFLICT (symbol, asset_class) DO NOTHING",
+            params![symbol, asset_class],
+        )?;
+        let id = conn.query_row(
+            "SELECT asset_id FROM assets WHERE symbol = ?1 AND asset_class = ?2",
+            params![symbol, asset_class],
+            |r| r.get(0),
+        )?;
+        Ok(id)
+    }
+
+    // ---- symbol map ----
+
+    /// Declare that `canonical` is known to `provider` as `remote` (e.g.
+    /// a futures root mapping onto a dated contract). Re-mapping a
+    /// canonical symbol replaces its previous remote.
+    pub fn symbol_map_set(
+        conn: &Connection,
+        provider: &str,
+        canonical: &str,
+        remote: &str,
+    ) -> Result<(), RepoError> {
+        conn.execute(
+            "INSERT INTO symbol_map (provider, canonical, remote) VALUES (?1, ?2, ?3)
+             ON CONFLICT (provider, canonical) DO UPDATE SET remote = excluded.remote",
+            params![provider, canonical, remote],
+        )?;
+        Ok(())
+    }
+
+    /// The symbol `provider` expects on the wire for `canonical`. Symbols
+    /// without a mapping pass through unchanged, so equities and crypto
+    /// pairs need no rows here.
+    pub fn resolve_remote(
+        conn: &Connection,
+        provider: &str,
+        canonical: &str,
+    ) -> Result<String, RepoError> {
+        let remote = conn
+            .query_row(
+                "SELECT remote FROM symbol_map WHERE provider = ?1 AND canonical = ?2",
+                params![provider, canonical],
+                |r| r.get(0),
+            )
+            .optional()?;
+        Ok(remote.unwrap_or_else(|| canonical.to_string()))
+    }
+
+    /// Inverse of [`SqliteRepo::resolve_remote`]: map a provider's wire
+    /// symbol back to our canonical one, identity when unmapped.
+    pub fn resolve_canonical(
+        conn: &Connection,
+        provider: &str,
+        remote: &str,
+    ) -> Result<String, RepoError> {
+        let canonical = conn
+            .query_row(
+                "SELECT canonical FROM symbol_map WHERE provider = ?1 AND remote = ?2",
+                params![provider, remote],
+                |r| r.get(0),
+            )
+            .optional()?;
+        Ok(canonical.unwrap_or_else(|| remote.to_string()))
+    }
+
+    // ---- manifests ----
+
+    /// Insert or refresh the manifest keyed by (asset, provider, timeframe).
+    /// An upsert re-opens a previously closed manifest and updates its
+    /// desired window. Returns the manifest id.
+    pub fn upsert_manifest(conn: &Connection, new: &NewManifest) -> Result<i64, RepoError> {
+        Self::upsert_manifest_outcome(conn, new).map(|(id, _outcome)| id)
+    }
+
+    /// [`SqliteRepo::upsert_manifest`] that also says whether the row was
+    /// freshly inserted or an existing one updated, for callers keeping
+    /// created/updated counts.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            skip(conn, new),
+            fields(asset_id = new.asset_id, provider = %new.provider, timeframe = %new.timeframe)
+        )
+    )]
+    pub fn upsert_manifest_outcome(
+        conn: &Connection,
+        new: &NewManifest,
+    ) -> Result<(i64, UpsertOutcome), RepoError> {
+        // Probe first: `changes()` reports 1 for both arms of an upsert,
+        // so insert-vs-update is decided by whether the key already holds
+        // a row.
+        let existing: Option<i64> = conn
+            .query_row(
+                "SELECT manifest_id FROM manifests
+                 WHERE asset_id = ?1 AND provider = ?2 AND tf_amount = ?3 AND tf_unit = ?4",
+                params![
+                    new.asset_id,
+                    new.provider,
+                    new.timeframe.amount(),
+                    new.timeframe.unit().as_str(),
+                ],
+                |r| r.get(0),
+            )
+            .optional()?;
+        conn.execute(
+            "INSERT INTO manifests
+                 (asset_id, provider, tf_amount, tf_unit, desired_start, desired_end, status)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, 'open')
+             ON CONFLICT (asset_id, provider, tf_amount, tf_unit) DO UPDATE SET
+                 desired_start = excluded.desired_start,
+                 desired_end = excluded.desired_end,
+                 status = 'open'",
+            params![
+                new.asset_id,
+                new.provider,
+                new.timeframe.amount(),
+                new.timeframe.unit().as_str(),
+                new.desired_start.to_rfc3339(),
+                new.desired_end.map(|t| t.to_rfc3339()),
+            ],
+        )?;
+        let id = conn.query_row(
+            "SELECT manifest_id FROM manifests
+             WHERE asset_id = ?1 AND provider = ?2 AND tf_amount = ?3 AND tf_unit = ?4",
+            params![
+                new.asset_id,
+                new.provider,
+                new.timeframe.amount(),
+                new.timeframe.unit().as_str(),
+            ],
+            |r| r.get(0),
+        )?;
+        crate::audit::record(
+            conn,
+            &crate::audit::AuditEntry {
+                actor: &crate::audit::current_actor(),
+                action: "manifest.upsert",
+                entity: format!("manifest:{id}"),
+                detail: serde_json::json!({
+                    "provider": new.provider,
+                    "timeframe": new.timeframe.to_string(),
+                }),
+            },
+        )?;
+        let outcome = match existing {
+            Some(_) => UpsertOutcome::Updated,
+            None => UpsertOutcome::Inserted,
+        };
+        Ok((id, outcome))
+    }
+
+    /// Batch form of [`SqliteRepo::upsert_manifest`]: every upsert runs in
+    /// one transaction, so a failing entry rolls the whole batch back
+    /// instead of leaving some manifests created. Ids come back in input
+    /// order. SQLite does not enforce the asset foreign key here, so each
+    /// referenced asset is checked explicitly.
+    pub fn upsert_manifests(
+        conn: &Connection,
+        batch: &[NewManifest],
+    ) -> Result<Vec<i64>, RepoError> {
+        let tx = conn.unchecked_transaction()?;
+        let mut ids = Vec::with_capacity(batch.len());
+        for new in batch {
+            let known: Option<i64> = tx
+                .query_row(
+                    "SELECT asset_id FROM assets WHERE asset_id = ?1",
+                    params![new.asset_id],
+                    |r| r.get(0),
+                )
+                .optional()?;
+            if known.is_none() {
+                // Dropping the uncommitted transaction rolls everything back.
+                return Err(RepoError::AssetNotFound(new.asset_id));
+            }
+            ids.push(Self::upsert_manifest(&tx, new)?);
+        }
+        tx.commit()?;
+        Ok(ids)
+    }
+
+    pub fn close_manifest(conn: &Connection, manifest_id: i64) -> Result<(), RepoError> {
+        let n = conn.execute(
+            "UPDATE manifests SET status = 'closed' WHERE manifest_id = ?1",
+            params![manifest_id],
+        )?;
+        if n == 0 {
+            return Err(RepoError::ManifestNotFound(manifest_id));
+        }
+        Ok(())
+    }
+
+    /// Change a manifest's timeframe in place, resetting its coverage and
+    /// gaps.
+    ///
+    /// Bucket ids are timeframe-specific, so a bitmap or gap row written
+    /// under the old timeframe cannot be reinterpreted — carrying it over
+    /// would mark the wrong ranges as fetched. The only sound migration
+    /// is a reset: in one transaction the manifest keeps its identity
+    /// (id, priority, enabled, audit history) while every coverage
+    /// session and every gap row is deleted, and the next coverage
+    /// computation re-plans the window from scratch. A change that
+    /// collides with an existing (asset, provider, timeframe) manifest
+    /// fails on the unique constraint and rolls back.
+    pub fn change_timeframe(
+        conn: &Connection,
+        manifest_id: i64,
+        new_tf: Timeframe,
+    ) -> Result<(), RepoError> {
+        let tx = conn.unchecked_transaction()?;
+        let n = tx.execute(
+            "UPDATE manifests SET tf_amount = ?2, tf_unit = ?3 WHERE manifest_id = ?1",
+            params![manifest_id, new_tf.amount(), new_tf.unit().as_str()],
+        )?;
+        if n == 0 {
+            return Err(RepoError::ManifestNotFound(manifest_id));
+        }
+        tx.execute(
+            "DELETE FROM coverage WHERE manifest_id = ?1",
+            params![manifest_id],
+        )?;
+        tx.execute(
+            "DELETE FROM gaps WHERE manifest_id = ?1",
+            params![manifest_id],
+        )?;
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Set the lease-ordering weight of a manifest (default 0). Takes
+    /// effect on the next [`SqliteRepo::gaps_lease`] call; already-leased
+    /// gaps are unaffected.
+    pub fn manifest_set_priority(
+        conn: &Connection,
+        manifest_id: i64,
+        priority: i64,
+    ) -> Result<(), RepoError> {
+        let n = conn.execute(
+            "UPDATE manifests SET priority = ?2 WHERE manifest_id = ?1",
+            params![manifest_id, priority],
+        )?;
+        if n == 0 {
+            return Err(RepoError::ManifestNotFound(manifest_id));
+        }
+        Ok(())
+    }
+
+    /// Pause (`false`) or resume (`true`) fetching for a manifest without
+    /// touching its coverage or queued gaps. Disabled manifests stay open
+    /// — catalog sync will not close them — but produce no planned work
+    /// and their gaps cannot be leased.
+    pub fn manifest_set_enabled(
+        conn: &Connection,
+        manifest_id: i64,
+        enabled: bool,
+    ) -> Result<(), RepoError> {
+        let n = conn.execute(
+            "UPDATE manifests SET enabled = ?2 WHERE manifest_id = ?1",
+            params![manifest_id, enabled],
+        )?;
+        if n == 0 {
+            return Err(RepoError::ManifestNotFound(manifest_id));
+        }
+        Ok(())
+    }
+
+    /// Set the manifest's venue timezone for session math. The string is
+    /// validated against the IANA database before it touches the DB, so a
+    /// typo cannot silently push session windows onto the wrong clock.
+    pub fn manifest_set_venue_tz(
+        conn: &Connection,
+        manifest_id: i64,
+        venue_tz: &str,
+    ) -> Result<(), RepoError> {
+        if venue_tz.parse::<chrono_tz::Tz>().is_err() {
+            return Err(RepoError::InvalidTimezone(venue_tz.to_string()));
+        }
+        let n = conn.execute(
+            "UPDATE manifests SET venue_tz = ?2 WHERE manifest_id = ?1",
+            params![manifest_id, venue_tz],
+        )?;
+        if n == 0 {
+            return Err(RepoError::ManifestNotFound(manifest_id));
+        }
+        Ok(())
+    }
+
+    pub fn manifest_by_id(conn: &Connection, manifest_id: i64) -> Result<Manifest, RepoError> {
+        conn.query_row(
+            &format!("{MANIFEST_SELECT} WHERE m.manifest_id = ?1"),
+            params![manifest_id],
+            manifest_from_row,
+        )
+        .optional()?
+        .ok_or(RepoError::ManifestNotFound(manifest_id))
+    }
+
+    pub fn manifests_all(conn: &Connection) -> Result<Vec<Manifest>, RepoError> {
+        let mut stmt = conn.prepare(&format!("{MANIFEST_SELECT} ORDER BY m.manifest_id"))?;
+        let rows = stmt.query_map([], manifest_from_row)?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    pub fn manifests_open(conn: &Connection) -> Result<Vec<Manifest>, RepoError> {
+        let mut stmt = conn.prepare(&format!(
+            "{MANIFEST_SELECT} WHERE m.status = 'open' ORDER BY m.manifest_id"
+        ))?;
+        let rows = stmt.query_map([], manifest_from_row)?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    /// Providers with at least one manifest on an asset of `asset_class`,
+    /// sorted and deduplicated — "which providers can serve futures?" for
+    /// a UI. The answer reflects what the synced catalog declares, not a
+    /// static capability table.
+    pub fn providers_for_class(
+        conn: &Connection,
+        asset_class: &str,
+    ) -> Result<Vec<String>, RepoError> {
+        let mut stmt = conn.prepare(
+            "SELECT DISTINCT m.provider
+             FROM manifests m JOIN assets a ON a.asset_id = m.asset_id
+             WHERE a.asset_class = ?1
+             ORDER BY m.provider",
+        )?;
+        let rows = stmt.query_map(params![asset_class], |r| r.get(0))?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    /// Inverse of [`SqliteRepo::providers_for_class`]: the asset classes
+    /// `provider` has manifests for.
+    pub fn classes_for_provider(
+        conn: &Connection,
+        provider: &str,
+    ) -> Result<Vec<String>, RepoError> {
+        let mut stmt = conn.prepare(
+            "SELECT DISTINCT a.asset_class
+             FROM manifests m JOIN assets a ON a.asset_id = m.asset_id
+             WHERE m.provider = ?1
+             ORDER BY a.asset_class",
+        )?;
+        let rows = stmt.query_map(params![provider], |r| r.get(0))?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    // ---- coverage ----
+
+    /// Current coverage of a manifest, in the default [`SESSION_ALL`]
+    /// bucket set. The bitmap holds bucket ids relative to `bucket_base`
+    /// so windows anywhere on the timeline fit in u32 ids. A manifest
+    /// with no coverage row yet reads as version 0 with an empty bitmap
+    /// and base 0.
+    pub fn coverage_get(
+        conn: &Connection,
+        manifest_id: i64,
+    ) -> Result<CoverageSnapshot, RepoError> {
+        Self::coverage_get_session(conn, manifest_id, SESSION_ALL)
+    }
+
+    /// [`SqliteRepo::coverage_get`] for one session's bitmap. Sessions
+    /// version independently: judging regular-hours completeness does not
+    /// race extended-hours writers.
+    pub fn coverage_get_session(
+        conn: &Connection,
+        manifest_id: i64,
+        session: &str,
+    ) -> Result<CoverageSnapshot, RepoError> {
+        let row: Option<(i64, i64, Vec<u8>)> = conn
+            .query_row(
+                "SELECT version, bucket_base, roaring FROM coverage
+                 WHERE manifest_id = ?1 AND session = ?2",
+                params![manifest_id, session],
+                |r| Ok((r.get(0)?, r.get(1)?, r.get(2)?)),
+            )
+            .optional()?;
+        match row {
+            None => Ok(CoverageSnapshot {
+                version: 0,
+                bucket_base: 0,
+                bitmap: RoaringBitmap::new(),
+            }),
+            Some((version, bucket_base, bytes)) => {
+                let bitmap = crate::roaring_bytes::from_bytes(&bytes)
+                    .map_err(|_| RepoError::CorruptBitmap(manifest_id))?;
+                Ok(CoverageSnapshot {
+                    version,
+                    bucket_base: bucket_base as u64,
+                    bitmap,
+                })
+            }
+        }
+    }
+
+    /// Batch form of [`SqliteRepo::coverage_get`]: one `IN (...)` query per
+    /// chunk of ids instead of a round-trip per manifest. Ids without a
+    /// coverage row map to the same empty version-0 snapshot the single
+    /// call returns.
+    pub fn coverage_get_many(
+        conn: &Connection,
+        manifest_ids: &[i64],
+    ) -> Result<std::collections::HashMap<i64, CoverageSnapshot>, RepoError> {
+        // Comfortably under SQLite's default 999 bind-parameter limit.
+        const CHUNK: usize = 500;
+
+        let mut out: std::collections::HashMap<i64, CoverageSnapshot> = manifest_ids
+            .iter()
+            .map(|&id| {
+                (
+                    id,
+                    CoverageSnapshot {
+                        version: 0,
+                        bucket_base: 0,
+                        bitmap: RoaringBitmap::new(),
+                    },
+                )
+            })
+            .collect();
+
+        for chunk in manifest_ids.chunks(CHUNK) {
+            let placeholders = vec!["?"; chunk.len()].join(", ");
+            let sql = format!(
+                "SELECT manifest_id, version, bucket_base, roaring
+                 FROM coverage WHERE session = '{SESSION_ALL}'
+                 AND manifest_id IN ({placeholders})"
+            );
+            let mut stmt = conn.prepare(&sql)?;
+            let rows = stmt
+                .query_map(rusqlite::params_from_iter(chunk.iter()), |r| {
+                    Ok((
+                        r.get::<_, i64>(0)?,
+                        r.get::<_, i64>(1)?,
+                        r.get::<_, i64>(2)?,
+                        r.get::<_, Vec<u8>>(3)?,
+                    ))
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+            for (manifest_id, version, bucket_base, bytes) in rows {
+                let bitmap = crate::roaring_bytes::from_bytes(&bytes)
+                    .map_err(|_| RepoError::CorruptBitmap(manifest_id))?;
+                out.insert(
+                    manifest_id,
+                    CoverageSnapshot {
+                        version,
+                        bucket_base: bucket_base as u64,
+                        bitmap,
+                    },
+                );
+            }
+        }
+        Ok(out)
+    }
+
+    /// Store `bitmap` (ids relative to `bucket_base`) as the
+    /// [`SESSION_ALL`] coverage of `manifest_id`, guarded by the version
+    /// read alongside it. Fails with
+    /// [`RepoError::CoverageVersionConflict`] if someone wrote in between.
+    pub fn coverage_put(
+        conn: &Connection,
+        manifest_id: i64,
+        expected_version: i64,
+        bucket_base: u64,
+        bitmap: &RoaringBitmap,
+    ) -> Result<(), RepoError> {
+        Self::coverage_put_session(
+            conn,
+            manifest_id,
+            SESSION_ALL,
+            expected_version,
+            bucket_base,
+            bitmap,
+        )
+    }
+
+    /// [`SqliteRepo::coverage_put`] for one session's bitmap.
+    pub fn coverage_put_session(
+        conn: &Connection,
+        manifest_id: i64,
+        session: &str,
+        expected_version: i64,
+        bucket_base: u64,
+        bitmap: &RoaringBitmap,
+    ) -> Result<(), RepoError> {
+        let bytes = crate::roaring_bytes::to_bytes(bitmap);
+        let n = if expected_version == 0 {
+            conn.execute(
+                "INSERT INTO coverage (manifest_id, session, version, bucket_base, roaring)
+                 VALUES (?1, ?2, 1, ?3, ?4)
+                 ON CONFLICT (manifest_id, session) DO NOTHING",
+                params![manifest_id, session, bucket_base as i64, bytes],
+            )?
+        } else {
+            conn.execute(
+                "UPDATE coverage SET version = version + 1, bucket_base = ?4, roaring = ?5
+                 WHERE manifest_id = ?1 AND session = ?2 AND version = ?3",
+                params![
+                    manifest_id,
+                    session,
+                    expected_version,
+                    bucket_base as i64,
+                    bytes
+                ],
+            )?
+        };
+        if n == 0 {
+            return Err(RepoError::CoverageVersionConflict {
+                manifest_id,
+                expected: expected_version,
+            });
+        }
+        Ok(())
+    }
+
+    /// Total bytes of stored coverage blobs across all manifests, measured
+    /// inside SQLite so no blob is read out just to be sized. Pair with
+    /// [`crate::roaring_bytes::stats`] when a single manifest needs a
+    /// closer look.
+    pub fn coverage_blob_bytes(conn: &Connection) -> Result<u64, RepoError> {
+        let total: i64 = conn.query_row(
+            "SELECT coalesce(sum(length(roaring)), 0) FROM coverage",
+            [],
+            |r| r.get(0),
+        )?;
+        Ok(total as u64)
+    }
+
+    /// Read-merge-retry loop over [`SqliteRepo::coverage_get`] and
+    /// [`SqliteRepo::coverage_put`]: `f` maps the current bitmap to the new
+    /// one, and a version conflict re-reads and re-applies `f`, up to
+    /// `max_retries` extra rounds before the conflict surfaces to the
+    /// caller. `f` must be idempotent on the latest snapshot — it may run
+    /// more than once. Retries back off with [`ConflictBackoff::default`];
+    /// use [`SqliteRepo::coverage_update_with_backoff`] to tune that or to
+    /// observe the retry count.
+    pub fn coverage_update_with<F>(
+        conn: &Connection,
+        manifest_id: i64,
+        max_retries: u32,
+        f: F,
+    ) -> Result<(), RepoError>
+    where
+        F: FnMut(&RoaringBitmap) -> RoaringBitmap,
+    {
+        Self::coverage_update_with_backoff(
+            conn,
+            manifest_id,
+            max_retries,
+            &ConflictBackoff::default(),
+            f,
+        )
+        .map(|_retries| ())
+    }
+
+    /// [`SqliteRepo::coverage_update_with`] with an explicit backoff.
+    /// Returns how many conflict retries were needed, so contention on a
+    /// hot manifest is observable instead of silent.
+    pub fn coverage_update_with_backoff<F>(
+        conn: &Connection,
+        manifest_id: i64,
+        max_retries: u32,
+        backoff: &ConflictBackoff,
+        mut f: F,
+    ) -> Result<u32, RepoError>
+    where
+        F: FnMut(&RoaringBitmap) -> RoaringBitmap,
+    {
+        let mut retries = 0u32;
+        loop {
+            let snapshot = Self::coverage_get(conn, manifest_id)?;
+            let bitmap = f(&snapshot.bitmap);
+            match Self::coverage_put(
+                conn,
+                manifest_id,
+                snapshot.version,
+                snapshot.bucket_base,
+                &bitmap,
+            ) {
+                Ok(()) => return Ok(retries),
+                Err(RepoError::CoverageVersionConflict { .. }) if retries < max_retries => {
+                    std::thread::sleep(backoff.delay_for(retries));
+                    retries += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    // ---- gaps ----
+
+    /// Queue a missing run for backfill.
+    pub fn gaps_insert(
+        conn: &Connection,
+        manifest_id: i64,
+        start_bucket: u32,
+        end_bucket: u32,
+    ) -> Result<i64, RepoError> {
+        conn.execute(
+            "INSERT INTO gaps (manifest_id, start_bucket, end_bucket) VALUES (?1, ?2, ?3)",
+            params![manifest_id, start_bucket, end_bucket],
+        )?;
+        Ok(conn.last_insert_rowid())
+    }
+
+    /// Lease up to `limit` workable gaps to `worker` until `now + lease`.
+    /// A gap is workable if it is `open`, or `leased` with an expired
+    /// lease. Gaps of higher-priority manifests are handed out first;
+    /// within a priority, oldest gaps win.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(skip(conn), fields(worker, limit, leased = tracing::field::Empty))
+    )]
+    pub fn gaps_lease(
+        conn: &Connection,
+        now: DateTime<Utc>,
+        lease: chrono::Duration,
+        limit: usize,
+        worker: &str,
+    ) -> Result<Vec<Gap>, RepoError> {
+        let expires = (now + lease).to_rfc3339();
+        let now_s = now.to_rfc3339();
+        let mut stmt = conn.prepare(
+            "UPDATE gaps SET state = 'leased', attempts = attempts + 1, lease_expires_at = ?1,
+                             leased_by = ?4
+             WHERE gap_id IN (
+                 SELECT g.gap_id FROM gaps g
+                 JOIN manifests m ON m.manifest_id = g.manifest_id
+                 WHERE m.enabled = 1
+                   AND (g.state = 'open'
+                        OR (g.state = 'leased' AND g.lease_expires_at <= ?2))
+                 ORDER BY m.priority DESC, g.gap_id
+                 LIMIT ?3
+             )
+             RETURNING gap_id, manifest_id, start_bucket, end_bucket, state, attempts,
+                       lease_expires_at, leased_by",
+        )?;
+        let rows = stmt.query_map(params![expires, now_s, limit, worker], gap_from_row)?;
+        let gaps: Vec<Gap> = rows.collect::<Result<_, _>>()?;
+        #[cfg(feature = "tracing")]
+        tracing::Span::current().record("leased", gaps.len());
+        Ok(gaps)
+    }
+
+    /// Heartbeat for a long fill: push `lease_expires_at` out to
+    /// `now + ttl`, but only while `worker` still holds the lease. Returns
+    /// `false` when ownership was lost (the gap expired and was re-leased,
+    /// completed, or failed), in which case the worker must abandon the
+    /// fill rather than merge its result.
+    pub fn gaps_renew(
+        conn: &Connection,
+        now: DateTime<Utc>,
+        gap_id: i64,
+        worker: &str,
+        ttl: chrono::Duration,
+    ) -> Result<bool, RepoError> {
+        let n = conn.execute(
+            "UPDATE gaps SET lease_expires_at = ?2
+             WHERE gap_id = ?1 AND state = 'leased' AND leased_by = ?3",
+            params![gap_id, (now + ttl).to_rfc3339(), worker],
+        )?;
+        Ok(n > 0)
+    }
+
+    /// Mark a leased gap as filled.
+    pub fn gaps_complete(conn: &Connection, gap_id: i64) -> Result<(), RepoError> {
+        conn.execute(
+            "UPDATE gaps SET state = 'done', lease_expires_at = NULL WHERE gap_id = ?1",
+            params![gap_id],
+        )?;
+        Ok(())
+    }
+
+    /// Batch form of [`SqliteRepo::gaps_complete`]: all gaps flip to done
+    /// in one transaction, so a worker finishing a batch fetch pays one
+    /// round trip instead of N. All-or-nothing — an unknown id rolls the
+    /// whole batch back and the error names every missing id.
+    pub fn gaps_complete_many(conn: &Connection, gap_ids: &[i64]) -> Result<(), RepoError> {
+        let tx = conn.unchecked_transaction()?;
+        let mut missing = Vec::new();
+        for &gap_id in gap_ids {
+            let n = tx.execute(
+                "UPDATE gaps SET state = 'done', lease_expires_at = NULL WHERE gap_id = ?1",
+                params![gap_id],
+            )?;
+            if n == 0 {
+                missing.push(gap_id);
+            }
+        }
+        if !missing.is_empty() {
+            // Dropping the uncommitted transaction rolls everything back.
+            return Err(RepoError::GapsNotFound(missing));
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Return a leased gap to the queue, or dead-letter it after
+    /// [`MAX_GAP_ATTEMPTS`] attempts.
+    pub fn gaps_fail(conn: &Connection, gap_id: i64) -> Result<GapState, RepoError> {
+        let state: String = conn.query_row(
+            "UPDATE gaps
+             SET state = CASE WHEN attempts >= ?2 THEN 'dead' ELSE 'open' END,
+                 lease_expires_at = NULL
+             WHERE gap_id = ?1
+             RETURNING state",
+            params![gap_id, MAX_GAP_ATTEMPTS],
+            |r| r.get(0),
+        )?;
+        Ok(GapState::from_db(&state))
+    }
+
+    /// Dead-lettered gaps for operator inspection, optionally scoped to
+    /// one manifest.
+    pub fn gaps_list_dead(
+        conn: &Connection,
+        manifest_id: Option<i64>,
+    ) -> Result<Vec<Gap>, RepoError> {
+        let mut stmt = conn.prepare(
+            "SELECT gap_id, manifest_id, start_bucket, end_bucket, state, attempts,
+                    lease_expires_at, leased_by
+             FROM gaps
+             WHERE state = 'dead' AND (?1 IS NULL OR manifest_id = ?1)
+             ORDER BY gap_id",
+        )?;
+        let rows = stmt.query_map(params![manifest_id], gap_from_row)?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    /// Put a dead gap back in the queue with a clean attempt count, e.g.
+    /// after a provider outage blamed for the failures has passed.
+    pub fn gaps_requeue_dead(conn: &Connection, gap_id: i64) -> Result<(), RepoError> {
+        let n = conn.execute(
+            "UPDATE gaps
+             SET state = 'open', attempts = 0, lease_expires_at = NULL, leased_by = NULL
+             WHERE gap_id = ?1 AND state = 'dead'",
+            params![gap_id],
+        )?;
+        if n == 0 {
+            return Err(RepoError::GapNotDead(gap_id));
+        }
+        Ok(())
+    }
+
+    pub fn gaps_for_manifest(conn: &Connection, manifest_id: i64) -> Result<Vec<Gap>, RepoError> {
+        let mut stmt = conn.prepare(
+            "SELECT gap_id, manifest_id, start_bucket, end_bucket, state, attempts,
+                    lease_expires_at, leased_by
+             FROM gaps WHERE manifest_id = ?1 ORDER BY gap_id",
+        )?;
+        let rows = stmt.query_map(params![manifest_id], gap_from_row)?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    /// Every gap in `state`, across all manifests, oldest first.
+    pub fn gaps_by_state(conn: &Connection, state: GapState) -> Result<Vec<Gap>, RepoError> {
+        let mut stmt = conn.prepare(
+            "SELECT gap_id, manifest_id, start_bucket, end_bucket, state, attempts,
+                    lease_expires_at, leased_by
+             FROM gaps WHERE state = ?1 ORDER BY gap_id",
+        )?;
+        let rows = stmt.query_map(params![state.as_str()], gap_from_row)?;
+        Ok(rows.collect::<Result<_, _>>()?)
+    }
+
+    /// Reset every `leased` gap whose lease expired before `now` back to
+    /// `open`, returning how many were swept. [`SqliteRepo::gaps_lease`]
+    /// reclaims expired leases lazily, but only when someone is leasing;
+    /// a periodic maintenance call here unsticks manifests nobody is
+    /// working on. Attempt counts stay — the expired attempt happened.
+    pub fn sweep_expired_leases(conn: &Connection, now: DateTime<Utc>) -> Result<usize, RepoError> {
+        let n = conn.execute(
+            "UPDATE gaps
+             SET state = 'open', lease_expires_at = NULL, leased_by = NULL
+             WHERE state = 'leased' AND lease_expires_at < ?1",
+            params![now.to_rfc3339()],
+        )?;
+        Ok(n)
+    }
+
+    /// Gap counts per state, optionally scoped to one manifest. One
+    /// `GROUP BY` query; states without rows read as zero.
+    pub fn gaps_summary(
+        conn: &Connection,
+        manifest_id: Option<i64>,
+    ) -> Result<GapSummary, RepoError> {
+        let mut stmt = conn.prepare(
+            "SELECT state, count(*) FROM gaps
+             WHERE (?1 IS NULL OR manifest_id = ?1)
+             GROUP BY state",
+        )?;
+        let rows = stmt.query_map(params![manifest_id], |r| {
+            Ok((r.get::<_, String>(0)?, r.get::<_, i64>(1)?))
+        })?;
+        let mut summary = GapSummary::default();
+        for row in rows {
+            let (state, count) = row?;
+            match GapState::from_db(&state) {
+                GapState::Open => summary.open = count as u64,
+                GapState::Leased => summary.leased = count as u64,
+                GapState::Done => summary.done = count as u64,
+                GapState::Dead => summary.dead = count as u64,
+            }
+        }
+        Ok(summary)
+    }
+}
+
+const MANIFEST_SELECT: &str = "SELECT m.manifest_id, m.asset_id, a.symbol, a.asset_class,
+        m.provider, m.tf_amount, m.tf_unit, m.desired_start, m.desired_end, m.status,
+        m.priority, m.enabled, m.venue_tz
+ FROM manifests m JOIN assets a ON a.asset_id = m.asset_id";
+
+fn parse_utc(s: &str) -> DateTime<Utc> {
+    DateTime::parse_from_rfc3339(s)
+        .expect("RFC 3339 timestamp in DB")
+        .with_timezone(&Utc)
+}
+
+fn manifest_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<Manifest> {
+    let desired_end: Option<String> = row.get(8)?;
+    let status: String = row.get(9)?;
+    Ok(Manifest {
+        manifest_id: row.get(0)?,
+        asset_id: row.get(1)?,
+        symbol: row.get(2)?,
+        asset_class: row.get(3)?,
+        provider: row.get(4)?,
+        timeframe: Timeframe::from_db_row(row.get(5)?, &row.get::<_, String>(6)?).map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(6, rusqlite::types::Type::Text, e.into())
+        })?,
+        desired_start: parse_utc(&row.get::<_, String>(7)?),
+        desired_end: desired_end.as_deref().map(parse_utc),
+        status: ManifestStatus::from_db(&status),
+        priority: row.get(10)?,
+        enabled: row.get(11)?,
+        venue_tz: row.get(12)?,
+    })
+}
+
+fn gap_from_row(row: &rusqlite::Row<'_>) -> rusqlite::Result<Gap> {
+    let state: String = row.get(4)?;
+    let lease: Option<String> = row.get(6)?;
+    Ok(Gap {
+        gap_id: row.get(0)?,
+        manifest_id: row.get(1)?,
+        start_bucket: row.get(2)?,
+        end_bucket: row.get(3)?,
+        state: GapState::from_db(&state),
+        attempts: row.get(5)?,
+        lease_expires_at: lease.as_deref().map(parse_utc),
+        leased_by: row.get(7)?,
+    })
+}
+
+#[cfg(test)]
+pub(crate) mod test_support {
+    use super::*;
+    use crate::timeframe::TimeframeUnit;
+    use chrono::TimeZone;
+
+    pub fn mem_conn() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        SqliteRepo::init(&conn).unwrap();
+        conn
+    }
+
+    pub fn utc(y: i32, mo: u32, d: u32, h: u32, mi: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, mo, d, h, mi, 0).unwrap()
+    }
+
+    /// Convenience: asset + open manifest in one call. Uses the `crypto`
+    /// asset class so no session filtering applies; session-aware tests
+    /// use [`insert_manifest_class`].
+    pub fn insert_manifest(
+        conn: &Connection,
+        symbol: &str,
+        provider: &str,
+        tf: Timeframe,
+        start: DateTime<Utc>,
+        end: Option<DateTime<Utc>>,
+    ) -> i64 {
+        insert_manifest_class(conn, symbol, "crypto", provider, tf, start, end)
+    }
+
+    pub fn insert_manifest_class(
+        conn: &Connection,
+        symbol: &str,
+        asset_class: &str,
+        provider: &str,
+        tf: Timeframe,
+        start: DateTime<Utc>,
+        end: Option<DateTime<Utc>>,
+    ) -> i64 {
+        let asset_id = SqliteRepo::upsert_asset(conn, symbol, asset_class).unwrap();
+        SqliteRepo::upsert_manifest(
+            conn,
+            &NewManifest {
+                asset_id,
+                provider: provider.to_string(),
+                timeframe: tf,
+                desired_start: start,
+                desired_end: end,
+            },
+        )
+        .unwrap()
+    }
+
+    pub fn minute_tf() -> Timeframe {
+        Timeframe::new(1, TimeframeUnit::Minute).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::test_support::*;
+    use super::*;
+
+    #[test]
+    fn timeframe_change_resets_coverage_instead_of_reinterpreting_it() {
+        let conn = mem_conn();
+        let id = insert_manifest(
+            &conn,
+            "BTC/USD",
+            "alpaca",
+            minute_tf(),
+            utc(2024, 1, 1, 0, 0),
+            None,
+        );
+        let mut bm = RoaringBitmap::new();
+        bm.insert_range(0..60);
+        SqliteRepo::coverage_put(&conn, id, 0, 0, &bm).unwrap();
+        SqliteRepo::gaps_insert(&conn, id, 60, 120).unwrap();
+
+        let five_min = Timeframe::new(5, crate::timeframe::TimeframeUnit::Minute).unwrap();
+        SqliteRepo::change_timeframe(&conn, id, five_min).unwrap();
+
+        let m = SqliteRepo::manifest_by_id(&conn, id).unwrap();
+        assert_eq!(m.timeframe, five_min);
+        // Minute-bucket coverage would silently claim five-hour spans
+        // under the new timeframe; it must come back empty at version 0.
+        let snapshot = SqliteRepo::coverage_get(&conn, id).unwrap();
+        assert_eq!(snapshot.version, 0);
+        assert!(snapshot.bitmap.is_empty());
+        assert!(SqliteRepo::gaps_for_manifest(&conn, id).unwrap().is_empty());
+
+        let missing = SqliteRepo::change_timeframe(&conn, 999, five_min).unwrap_err();
+        assert!(matches!(missing, RepoError::ManifestNotFound(999)));
+    }
+
+    #[test]
+    fn upsert_manifest_is_idempotent_and_reopens() {
+        let conn = mem_conn();
+        let tf = minute_tf();
+        let id = insert_manifest(&conn, "AAPL", "alpaca", tf, utc(2024, 1, 1, 0, 0), None);
+        SqliteRepo::close_manifest(&conn, id).unwrap();
+        let id2 = insert_manifest(&conn, "AAPL", "alpaca", tf, utc(2024, 1, 1, 0, 0), None);
+        assert_eq!(id, id2);
+        let m = SqliteRepo::manifest_by_id(&conn, id).unwrap();
+        assert_eq!(m.status, ManifestStatus::Open);
+        assert_eq!(m.symbol, "AAPL");
+    }
+
+    #[test]
+    fn upsert_outcome_distinguishes_insert_from_update() {
+        let conn = mem_conn();
+        let asset_id = SqliteRepo::upsert_asset(&conn, "AAPL", "us_equity").unwrap();
+        let new = NewManifest {
+            asset_id,
+            provider: "alpaca".to_string(),
+            timeframe: minute_tf(),
+            desired_start: utc(2024, 1, 1, 0, 0),
+            desired_end: None,
+        };
+
+        let (id, outcome) = SqliteRepo::upsert_manifest_outcome(&conn, &new).unwrap();
+        assert_eq!(outcome, UpsertOutcome::Inserted);
+        // The identical call hits the existing row, even though the write
+        // itself looks the same to `changes()`.
+        let (id2, outcome2) = SqliteRepo::upsert_manifest_outcome(&conn, &new).unwrap();
+        assert_eq!(id2, id);
+        assert_eq!(outcome2, UpsertOutcome::Updated);
+    }
+
+    #[test]
+    fn symbol_map_resolves_both_ways_with_identity_fallback() {
+        let conn = mem_conn();
+        // Unmapped symbols pass through untouched, in both directions.
+        assert_eq!(
+            SqliteRepo::resolve_remote(&conn, "alpaca", "AAPL").unwrap(),
+            "AAPL"
+        );
+        assert_eq!(
+            SqliteRepo::resolve_canonical(&conn, "alpaca", "AAPL").unwrap(),
+            "AAPL"
+        );
+
+        SqliteRepo::symbol_map_set(&conn, "cme", "ES", "ESZ5").unwrap();
+        assert_eq!(
+            SqliteRepo::resolve_remote(&conn, "cme", "ES").unwrap(),
+            "ESZ5"
+        );
+        assert_eq!(
+            SqliteRepo::resolve_canonical(&conn, "cme", "ESZ5").unwrap(),
+            "ES"
+        );
+        // Re-mapping replaces the old contract; the mapping is per provider.
+        SqliteRepo::symbol_map_set(&conn, "cme", "ES", "ESH6").unwrap();
+        assert_eq!(
+            SqliteRepo::resolve_remote(&conn, "cme", "ES").unwrap(),
+            "ESH6"
+        );
+        assert_eq!(
+            SqliteRepo::resolve_remote(&conn, "alpaca", "ES").unwrap(),
+            "ES"
+        );
+    }
+
+    #[test]
+    fn venue_tz_rejects_strings_outside_the_iana_db() {
+        let conn = mem_conn();
+        let tf = minute_tf();
+        let id = insert_manifest(&conn, "AAPL", "alpaca", tf, utc(2024, 1, 1, 0, 0), None);
+        assert_eq!(
+            SqliteRepo::manifest_by_id(&conn, id).unwrap().venue_tz,
+            "UTC"
+        );
+
+        let err = SqliteRepo::manifest_set_venue_tz(&conn, id, "Mars/Olympus").unwrap_err();
+        assert!(matches!(err, RepoError::InvalidTimezone(tz) if tz == "Mars/Olympus"));
+
+        SqliteRepo::manifest_set_venue_tz(&conn, id, "America/New_York").unwrap();
+        let m = SqliteRepo::manifest_by_id(&conn, id).unwrap();
+        assert_eq!(m.venue_tz, "America/New_York");
+    }
+
+    #[test]
+    fn batch_upsert_rolls_back_entirely_on_failure() {
+        let conn = mem_conn();
+        let asset = SqliteRepo::upsert_asset(&conn, "AAPL", "us_equity").unwrap();
+        let new = |asset_id, unit| NewManifest {
+            asset_id,
+            provider: "alpaca".to_string(),
+            timeframe: Timeframe::new(1, unit).unwrap(),
+            desired_start: utc(2024, 1, 1, 0, 0),
+            desired_end: None,
+        };
+
+        // Middle entry references an asset that does not exist: nothing
+        // from the batch may land.
+        let batch = [
+            new(asset, crate::timeframe::TimeframeUnit::Minute),
+            new(asset + 999, crate::timeframe::TimeframeUnit::Hour),
+            new(asset, crate::timeframe::TimeframeUnit::Day),
+        ];
+        let err = SqliteRepo::upsert_manifests(&conn, &batch).unwrap_err();
+        assert!(matches!(err, RepoError::AssetNotFound(_)));
+        assert!(SqliteRepo::manifests_all(&conn).unwrap().is_empty());
+
+        // A clean batch commits and returns ids in input order.
+        let good = [
+            new(asset, crate::timeframe::TimeframeUnit::Minute),
+            new(asset, crate::timeframe::TimeframeUnit::Day),
+        ];
+        let ids = SqliteRepo::upsert_manifests(&conn, &good).unwrap();
+        assert_eq!(ids.len(), 2);
+        let all = SqliteRepo::manifests_all(&conn).unwrap();
+        assert_eq!(all.len(), 2);
+        assert_eq!(all[0].manifest_id, ids[0]);
+    }
+
+    #[test]
+    fn provider_and_class_enumerations_cross_reference() {
+        let conn = mem_conn();
+        let tf = minute_tf();
+        let start = utc(2024, 1, 1, 0, 0);
+        insert_manifest_class(&conn, "AAPL", "us_equity", "alpaca", tf, start, None);
+        insert_manifest_class(&conn, "MSFT", "us_equity", "alpaca", tf, start, None);
+        insert_manifest_class(&conn, "BTC/USD", "crypto", "alpaca", tf, start, None);
+        insert_manifest_class(&conn, "ES", "futures", "polygon", tf, start, None);
+
+        assert_eq!(
+            SqliteRepo::providers_for_class(&conn, "us_equity").unwrap(),
+            vec!["alpaca"]
+        );
+        assert_eq!(
+            SqliteRepo::providers_for_class(&conn, "futures").unwrap(),
+            vec!["polygon"]
+        );
+        assert!(
+            SqliteRepo::providers_for_class(&conn, "options")
+                .unwrap()
+                .is_empty()
+        );
+
+        assert_eq!(
+            SqliteRepo::classes_for_provider(&conn, "alpaca").unwrap(),
+            vec!["crypto", "us_equity"]
+        );
+        assert_eq!(
+            SqliteRepo::classes_for_provider(&conn, "polygon").unwrap(),
+            vec!["futures"]
+        );
+    }
+
+    #[test]
+    fn coverage_get_many_matches_single_calls() {
+        let conn = mem_conn();
+        let tf = minute_tf();
+        let start = utc(2024, 1, 1, 0, 0);
+        let a = insert_manifest(&conn, "AAPL", "alpaca", tf, start, None);
+        let b = insert_manifest(&conn, "MSFT", "alpaca", tf, start, None);
+        let c = insert_manifest(&conn, "NVDA", "alpaca", tf, start, None);
+
+        let mut bm = RoaringBitmap::new();
+        bm.insert_range(10..20);
+        SqliteRepo::coverage_put(&conn, a, 0, 100, &bm).unwrap();
+        let mut bm2 = RoaringBitmap::new();
+        bm2.insert(7);
+        SqliteRepo::coverage_put(&conn, c, 0, 0, &bm2).unwrap();
+
+        let ids = [a, b, c];
+        let many = SqliteRepo::coverage_get_many(&conn, &ids).unwrap();
+        assert_eq!(many.len(), 3);
+        for id in ids {
+            let single = SqliteRepo::coverage_get(&conn, id).unwrap();
+            let batched = &many[&id];
+            assert_eq!(batched.version, single.version);
+            assert_eq!(batched.bucket_base, single.bucket_base);
+            assert_eq!(batched.bitmap, single.bitmap);
+        }
+        // b never had coverage: empty version-0 snapshot, like the single call.
+        assert_eq!(many[&b].version, 0);
+        assert!(many[&b].bitmap.is_empty());
+    }
+
+    #[test]
+    fn coverage_blob_bytes_sums_stored_blob_sizes() {
+        let conn = mem_conn();
+        let tf = minute_tf();
+        let start = utc(2024, 1, 1, 0, 0);
+        assert_eq!(SqliteRepo::coverage_blob_bytes(&conn).unwrap(), 0);
+
+        let a = insert_manifest(&conn, "AAPL", "alpaca", tf, start, None);
+        let b = insert_manifest(&conn, "MSFT", "alpaca", tf, start, None);
+        let mut bm = RoaringBitmap::new();
+        bm.insert_range(0..100);
+        SqliteRepo::coverage_put(&conn, a, 0, 0, &bm).unwrap();
+        let mut bm2 = RoaringBitmap::new();
+        bm2.insert(7);
+        SqliteRepo::coverage_put(&conn, b, 0, 0, &bm2).unwrap();
+
+        let expected = crate::roaring_bytes::stats(&bm).serialized_len
+            + crate::roaring_bytes::stats(&bm2).serialized_len;
+        assert_eq!(
+            SqliteRepo::coverage_blob_bytes(&conn).unwrap(),
+            expected as u64
+        );
+    }
+
+    #[test]
+    fn coverage_versioning_detects_conflicts() {
+        let conn = mem_conn();
+        let id = insert_manifest(
+            &conn,
+            "AAPL",
+            "alpaca",
+            minute_tf(),
+            utc(2024, 1, 1, 0, 0),
+            None,
+        );
+
+        let snap = SqliteRepo::coverage_get(&conn, id).unwrap();
+        assert_eq!(snap.version, 0);
+        let mut bm = snap.bitmap;
+        bm.insert(42);
+        // Base offsets beyond u32 round-trip through the DB.
+        let base = u64::from(u32::MAX) + 7;
+        SqliteRepo::coverage_put(&conn, id, snap.version, base, &bm).unwrap();
+
+        // Stale writer loses.
+        let err = SqliteRepo::coverage_put(&conn, id, 0, base, &bm).unwrap_err();
+        assert!(matches!(err, RepoError::CoverageVersionConflict { .. }));
+
+        let snap2 = SqliteRepo::coverage_get(&conn, id).unwrap();
+        assert_eq!(snap2.version, 1);
+        assert_eq!(snap2.bucket_base, base);
+        assert!(snap2.bitmap.contains(42));
+    }
+
+    #[test]
+    fn coverage_update_with_retries_past_a_concurrent_bump() {
+        let conn = mem_conn();
+        let id = insert_manifest(
+            &conn,
+            "AAPL",
+            "alpaca",
+            minute_tf(),
+            utc(2024, 1, 1, 0, 0),
+            None,
+        );
+        let mut bm = RoaringBitmap::new();
+        bm.insert(1);
+        SqliteRepo::coverage_put(&conn, id, 0, 0, &bm).unwrap();
+
+        // First application of `f` races with another writer bumping the
+        // version; the loop must re-read and succeed on the second round.
+        let mut calls = 0;
+        SqliteRepo::coverage_update_with(&conn, id, 3, |current| {
+            calls += 1;
+            if calls == 1 {
+                let snap = SqliteRepo::coverage_get(&conn, id).unwrap();
+                let mut stomped = snap.bitmap.clone();
+                stomped.insert(2);
+                SqliteRepo::coverage_put(&conn, id, snap.version, 0, &stomped).unwrap();
+            }
+            let mut next = current.clone();
+            next.insert(3);
+            next
+        })
+        .unwrap();
+        assert_eq!(calls, 2);
+
+        let snap = SqliteRepo::coverage_get(&conn, id).unwrap();
+        assert_eq!(snap.version, 3);
+        for bucket in [1, 2, 3] {
+            assert!(snap.bitmap.contains(bucket));
+        }
+
+        // With retries exhausted the conflict surfaces.
+        let mut always_race = 0;
+        let err = SqliteRepo::coverage_update_with(&conn, id, 0, |current| {
+            always_race += 1;
+            let snap = SqliteRepo::coverage_get(&conn, id).unwrap();
+            SqliteRepo::coverage_put(&conn, id, snap.version, 0, &snap.bitmap).unwrap();
+            current.clone()
+        })
+        .unwrap_err();
+        assert!(matches!(err, RepoError::CoverageVersionConflict { .. }));
+        assert_eq!(always_race, 1);
+    }
+
+    #[test]
+    fn sessions_hold_independent_coverage_bitmaps() {
+        let conn = mem_conn();
+        let id = insert_manifest(
+            &conn,
+            "AAPL",
+            "alpaca",
+            minute_tf(),
+            utc(2024, 1, 1, 0, 0),
+            None,
+        );
+
+        let mut regular = RoaringBitmap::new();
+        regular.insert_range(0..390);
+        let mut extended = RoaringBitmap::new();
+        extended.insert_range(390..500);
+        SqliteRepo::coverage_put_session(&conn, id, "regular", 0, 0, &regular).unwrap();
+        SqliteRepo::coverage_put_session(&conn, id, "extended", 0, 0, &extended).unwrap();
+
+        let r = SqliteRepo::coverage_get_session(&conn, id, "regular").unwrap();
+        let x = SqliteRepo::coverage_get_session(&conn, id, "extended").unwrap();
+        assert_eq!(r.bitmap.len(), 390);
+        assert_eq!(x.bitmap.len(), 110);
+        // Sessions version independently: writing one does not bump the
+        // other, and the default 'all' bitmap is untouched.
+        assert_eq!(r.version, 1);
+        assert_eq!(x.version, 1);
+        let all = SqliteRepo::coverage_get(&conn, id).unwrap();
+        assert_eq!(all.version, 0);
+        assert!(all.bitmap.is_empty());
+
+        // The un-keyed API is the 'all' session.
+        SqliteRepo::coverage_put(&conn, id, 0, 0, &regular).unwrap();
+        assert_eq!(
+            SqliteRepo::coverage_get_session(&conn, id, SESSION_ALL)
+                .unwrap()
+                .bitmap,
+            regular
+        );
+    }
+
+    #[test]
+    fn contending_updates_converge_with_a_recorded_retry() {
+        let conn = mem_conn();
+        let id = insert_manifest(
+            &conn,
+            "AAPL",
+            "alpaca",
+            minute_tf(),
+            utc(2024, 1, 1, 0, 0),
+            None,
+        );
+
+        // Two writers race on the same manifest: the second lands its
+        // update mid-flight, forcing the first through a conflict round.
+        let mut first_call = true;
+        let retries = SqliteRepo::coverage_update_with_backoff(
+            &conn,
+            id,
+            3,
+            &ConflictBackoff::none(),
+            |current| {
+                if first_call {
+                    first_call = false;
+                    SqliteRepo::coverage_update_with_backoff(
+                        &conn,
+                        id,
+                        3,
+                        &ConflictBackoff::none(),
+                        |other| {
+                            let mut next = other.clone();
+                            next.insert(7);
+                            next
+                        },
+                    )
+                    .unwrap();
+                }
+                let mut next = current.clone();
+                next.insert(11);
+                next
+            },
+        )
+        .unwrap();
+        assert_eq!(retries, 1);
+
+        // Both writers' buckets survive — neither update was lost.
+        let snap = SqliteRepo::coverage_get(&conn, id).unwrap();
+        assert!(snap.bitmap.contains(7));
+        assert!(snap.bitmap.contains(11));
+    }
+
+    #[test]
+    fn gap_lease_lifecycle() {
+        let conn = mem_conn();
+        let id = insert_manifest(
+            &conn,
+            "AAPL",
+            "alpaca",
+            minute_tf(),
+            utc(2024, 1, 1, 0, 0),
+            None,
+        );
+        let gap_id = SqliteRepo::gaps_insert(&conn, id, 100, 200).unwrap();
+
+        let now = utc(2024, 6, 1, 12, 0);
+        let leased =
+            SqliteRepo::gaps_lease(&conn, now, chrono::Duration::minutes(10), 5, "w1").unwrap();
+        assert_eq!(leased.len(), 1);
+        assert_eq!(leased[0].gap_id, gap_id);
+        assert_eq!(leased[0].state, GapState::Leased);
+
+        // Still leased: nothing to hand out.
+        let again =
+            SqliteRepo::gaps_lease(&conn, now, chrono::Duration::minutes(10), 5, "w2").unwrap();
+        assert!(again.is_empty());
+
+        // Expired lease is reclaimable.
+        let later = now + chrono::Duration::minutes(11);
+        let reclaimed =
+            SqliteRepo::gaps_lease(&conn, later, chrono::Duration::minutes(10), 5, "w2").unwrap();
+        assert_eq!(reclaimed.len(), 1);
+
+        SqliteRepo::gaps_complete(&conn, gap_id).unwrap();
+        let gaps = SqliteRepo::gaps_for_manifest(&conn, id).unwrap();
+        assert_eq!(gaps[0].state, GapState::Done);
+    }
+
+    #[test]
+    fn higher_priority_manifests_lease_first() {
+        let conn = mem_conn();
+        let start = utc(2024, 1, 1, 0, 0);
+        let backlog = insert_manifest(&conn, "AAPL", "alpaca", minute_tf(), start, None);
+        let urgent = insert_manifest(&conn, "NVDA", "alpaca", minute_tf(), start, None);
+        // The backlog manifest queued its gap first.
+        let backlog_gap = SqliteRepo::gaps_insert(&conn, backlog, 0, 10).unwrap();
+        let urgent_gap = SqliteRepo::gaps_insert(&conn, urgent, 0, 10).unwrap();
+        SqliteRepo::manifest_set_priority(&conn, urgent, 10).unwrap();
+        assert_eq!(
+            SqliteRepo::manifest_by_id(&conn, urgent).unwrap().priority,
+            10
+        );
+
+        let now = utc(2024, 6, 1, 12, 0);
+        let ttl = chrono::Duration::minutes(10);
+        let first = SqliteRepo::gaps_lease(&conn, now, ttl, 1, "w1").unwrap();
+        assert_eq!(first[0].gap_id, urgent_gap);
+        let second = SqliteRepo::gaps_lease(&conn, now, ttl, 1, "w1").unwrap();
+        assert_eq!(second[0].gap_id, backlog_gap);
+    }
+
+    #[test]
+    fn renewal_works_for_the_owner_until_the_lease_is_stolen() {
+        let conn = mem_conn();
+        let id = insert_manifest(
+            &conn,
+            "AAPL",
+            "alpaca",
+            minute_tf(),
+            utc(2024, 1, 1, 0, 0),
+            None,
+        );
+        let gap_id = SqliteRepo::gaps_insert(&conn, id, 100, 200).unwrap();
+
+        let now = utc(2024, 6, 1, 12, 0);
+        let ttl = chrono::Duration::minutes(10);
+        let leased = SqliteRepo::gaps_lease(&conn, now, ttl, 5, "w1").unwrap();
+        assert_eq!(leased[0].leased_by.as_deref(), Some("w1"));
+
+        // Heartbeat mid-fill pushes the expiry forward.
+        let mid = now + chrono::Duration::minutes(5);
+        assert!(SqliteRepo::gaps_renew(&conn, mid, gap_id, "w1", ttl).unwrap());
+        let gaps = SqliteRepo::gaps_for_manifest(&conn, id).unwrap();
+        assert_eq!(gaps[0].lease_expires_at, Some(mid + ttl));
+
+        // Once the lease expires and another worker claims it, the old
+        // owner's heartbeat must fail.
+        let later = mid + ttl + chrono::Duration::minutes(1);
+        let stolen = SqliteRepo::gaps_lease(&conn, later, ttl, 5, "w2").unwrap();
+        assert_eq!(stolen[0].leased_by.as_deref(), Some("w2"));
+        assert!(!SqliteRepo::gaps_renew(&conn, later, gap_id, "w1", ttl).unwrap());
+        assert!(SqliteRepo::gaps_renew(&conn, later, gap_id, "w2", ttl).unwrap());
+    }
+
+    #[test]
+    fn batch_completion_is_all_or_nothing() {
+        let conn = mem_conn();
+        let id = insert_manifest(
+            &conn,
+            "AAPL",
+            "alpaca",
+            minute_tf(),
+            utc(2024, 1, 1, 0, 0),
+            None,
+        );
+        let gap_ids: Vec<i64> = (0..3)
+            .map(|i| SqliteRepo::gaps_insert(&conn, id, i * 10, i * 10 + 9).unwrap())
+            .collect();
+
+        // A bad id rolls the whole batch back, naming the stranger.
+        let err =
+            SqliteRepo::gaps_complete_many(&conn, &[gap_ids[0], 9999, gap_ids[1]]).unwrap_err();
+        assert!(matches!(err, RepoError::GapsNotFound(ref ids) if ids == &[9999]));
+        let gaps = SqliteRepo::gaps_for_manifest(&conn, id).unwrap();
+        assert!(gaps.iter().all(|g| g.state == GapState::Open));
+
+        SqliteRepo::gaps_complete_many(&conn, &gap_ids).unwrap();
+        let gaps = SqliteRepo::gaps_for_manifest(&conn, id).unwrap();
+        assert_eq!(gaps.len(), 3);
+        assert!(gaps.iter().all(|g| g.state == GapState::Done));
+    }
+
+    #[test]
+    fn failed_gap_dead_letters_after_max_attempts() {
+        let conn = mem_conn();
+        let id = insert_manifest(
+            &conn,
+            "AAPL",
+            "alpaca",
+            minute_tf(),
+            utc(2024, 1, 1, 0, 0),
+            None,
+        );
+        let gap_id = SqliteRepo::gaps_insert(&conn, id, 0, 10).unwrap();
+        let now = utc(2024, 6, 1, 12, 0);
+        for attempt in 1..=MAX_GAP_ATTEMPTS {
+            let leased =
+                SqliteRepo::gaps_lease(&conn, now, chrono::Duration::minutes(1), 1, "w1").unwrap();
+            assert_eq!(leased.len(), 1, "attempt {attempt} should lease");
+            let state = SqliteRepo::gaps_fail(&conn, gap_id).unwrap();
+            if attempt == MAX_GAP_ATTEMPTS {
+                assert_eq!(state, GapState::Dead);
+            } else {
+                assert_eq!(state, GapState::Open);
+            }
+        }
+    }
+
+    #[test]
+    fn disabled_manifest_gaps_are_not_leased() {
+        let conn = mem_conn();
+        let id = insert_manifest(
+            &conn,
+            "AAPL",
+            "alpaca",
+            minute_tf(),
+            utc(2024, 1, 1, 0, 0),
+            None,
+        );
+        SqliteRepo::gaps_insert(&conn, id, 0, 10).unwrap();
+        SqliteRepo::manifest_set_enabled(&conn, id, false).unwrap();
+        assert!(!SqliteRepo::manifest_by_id(&conn, id).unwrap().enabled);
+
+        let now = utc(2024, 6, 1, 12, 0);
+        let leased =
+            SqliteRepo::gaps_lease(&conn, now, chrono::Duration::minutes(5), 10, "w1").unwrap();
+        assert!(leased.is_empty());
+
+        // Resuming makes the same gap workable again.
+        SqliteRepo::manifest_set_enabled(&conn, id, true).unwrap();
+        let leased =
+            SqliteRepo::gaps_lease(&conn, now, chrono::Duration::minutes(5), 10, "w1").unwrap();
+        assert_eq!(leased.len(), 1);
+    }
+
+    #[test]
+    fn dead_gaps_can_be_listed_and_requeued() {
+        let conn = mem_conn();
+        let id = insert_manifest(
+            &conn,
+            "AAPL",
+            "alpaca",
+            minute_tf(),
+            utc(2024, 1, 1, 0, 0),
+            None,
+        );
+        let gap_id = SqliteRepo::gaps_insert(&conn, id, 0, 10).unwrap();
+        let now = utc(2024, 6, 1, 12, 0);
+        for _ in 1..=MAX_GAP_ATTEMPTS {
+            SqliteRepo::gaps_lease(&conn, now, chrono::Duration::minutes(1), 1, "w1").unwrap();
+            SqliteRepo::gaps_fail(&conn, gap_id).unwrap();
+        }
+
+        let dead = SqliteRepo::gaps_list_dead(&conn, None).unwrap();
+        assert_eq!(dead.len(), 1);
+        assert_eq!(dead[0].gap_id, gap_id);
+        assert!(
+            SqliteRepo::gaps_list_dead(&conn, Some(id + 1))
+                .unwrap()
+                .is_empty()
+        );
+
+        SqliteRepo::gaps_requeue_dead(&conn, gap_id).unwrap();
+        let gaps = SqliteRepo::gaps_for_manifest(&conn, id).unwrap();
+        assert_eq!(gaps[0].state, GapState::Open);
+        assert_eq!(gaps[0].attempts, 0);
+        assert_eq!(gaps[0].leased_by, None);
+
+        // Requeueing a gap that isn't dead is refused.
+        let err = SqliteRepo::gaps_requeue_dead(&conn, gap_id).unwrap_err();
+        assert!(matches!(err, RepoError::GapNotDead(_)));
+    }
+
+    #[test]
+    fn sweeping_reclaims_only_expired_leases() {
+        let conn = mem_conn();
+        let id = insert_manifest(
+            &conn,
+            "AAPL",
+            "alpaca",
+            minute_tf(),
+            utc(2024, 1, 1, 0, 0),
+            None,
+        );
+        SqliteRepo::gaps_insert(&conn, id, 0, 10).unwrap();
+        SqliteRepo::gaps_insert(&conn, id, 10, 20).unwrap();
+
+        // Two live leases with different expiries; by sweep time only the
+        // first has lapsed. (Both are taken while still valid — a later
+        // `gaps_lease` would lazily reclaim an expired one itself.)
+        let early = utc(2024, 6, 1, 12, 0);
+        let leased =
+            SqliteRepo::gaps_lease(&conn, early, chrono::Duration::minutes(5), 1, "w1").unwrap();
+        let expired_id = leased[0].gap_id;
+        SqliteRepo::gaps_lease(
+            &conn,
+            utc(2024, 6, 1, 12, 3),
+            chrono::Duration::minutes(30),
+            1,
+            "w2",
+        )
+        .unwrap();
+
+        assert_eq!(
+            SqliteRepo::sweep_expired_leases(&conn, utc(2024, 6, 1, 12, 10)).unwrap(),
+            1
+        );
+        let gaps = SqliteRepo::gaps_for_manifest(&conn, id).unwrap();
+        let swept = gaps.iter().find(|g| g.gap_id == expired_id).unwrap();
+        assert_eq!(swept.state, GapState::Open);
+        assert_eq!(swept.leased_by, None);
+        // Its attempt count survives the sweep.
+        assert_eq!(swept.attempts, 1);
+        let live = gaps.iter().find(|g| g.gap_id != expired_id).unwrap();
+        assert_eq!(live.state, GapState::Leased);
+
+        // Nothing left to sweep on a second pass.
+        assert_eq!(
+            SqliteRepo::sweep_expired_leases(&conn, utc(2024, 6, 1, 12, 10)).unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn gap_summary_counts_per_state_and_scopes_to_a_manifest() {
+        let conn = mem_conn();
+        let a = insert_manifest(
+            &conn,
+            "AAPL",
+            "alpaca",
+            minute_tf(),
+            utc(2024, 1, 1, 0, 0),
+            None,
+        );
+        let b = insert_manifest(
+            &conn,
+            "MSFT",
+            "alpaca",
+            minute_tf(),
+            utc(2024, 1, 1, 0, 0),
+            None,
+        );
+        // Manifest A: one open, one leased, one done. Manifest B: one open.
+        SqliteRepo::gaps_insert(&conn, a, 0, 10).unwrap();
+        let leased_id = SqliteRepo::gaps_insert(&conn, a, 10, 20).unwrap();
+        let done_id = SqliteRepo::gaps_insert(&conn, a, 20, 30).unwrap();
+        SqliteRepo::gaps_insert(&conn, b, 0, 10).unwrap();
+        let now = utc(2024, 6, 1, 12, 0);
+        conn.execute(
+            "UPDATE gaps SET state = 'leased', leased_by = 'w1', lease_expires_at = ?2
+             WHERE gap_id = ?1",
+            params![leased_id, (now + chrono::Duration::minutes(5)).to_rfc3339()],
+        )
+        .unwrap();
+        SqliteRepo::gaps_complete(&conn, done_id).unwrap();
+
+        let all = SqliteRepo::gaps_summary(&conn, None).unwrap();
+        assert_eq!(
+            all,
+            GapSummary {
+                open: 2,
+                leased: 1,
+                done: 1,
+                dead: 0
+            }
+        );
+        let scoped = SqliteRepo::gaps_summary(&conn, Some(b)).unwrap();
+        assert_eq!(
+            scoped,
+            GapSummary {
+                open: 1,
+                ..GapSummary::default()
+            }
+        );
+        // A manifest with no gaps reads as all zeroes, not an error.
+        assert_eq!(
+            SqliteRepo::gaps_summary(&conn, Some(999)).unwrap(),
+            GapSummary::default()
+        );
+    }
+}
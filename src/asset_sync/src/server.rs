@@ -0,0 +1,288 @@
+//! Read-only HTTP status endpoints (feature `server`).
+//!
+//! Dashboards poll coverage and the gap queue without opening the SQLite
+//! file themselves: `GET /manifests`, `GET /manifests/{id}/coverage` and
+//! `GET /gaps?state=open`. The connection sits behind a mutex — every
+//! handler is one short read, so a status server needs no pool. axum is
+//! async, so [`serve`] drives a private current-thread runtime and the
+//! rest of the crate stays sync, the same arrangement the delta storage
+//! module uses.
+
+use std::sync::{Arc, Mutex};
+
+use axum::Router;
+use axum::extract::{Path, Query, State};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Json, Response};
+use axum::routing::get;
+use chrono::{DateTime, Utc};
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+
+use crate::coverage::CoverageError;
+use crate::repo::{Gap, GapState, Manifest, RepoError, SqliteRepo};
+
+type Db = Arc<Mutex<Connection>>;
+
+/// Build the router over an already-opened (and migrated) connection.
+pub fn router(conn: Connection) -> Router {
+    Router::new()
+        .route("/manifests", get(list_manifests))
+        .route("/manifests/{id}/coverage", get(manifest_coverage))
+        .route("/gaps", get(list_gaps))
+        .with_state(Arc::new(Mutex::new(conn)))
+}
+
+/// Bind `addr` and serve [`router`] until the process is killed.
+pub fn serve(conn: Connection, addr: std::net::SocketAddr) -> std::io::Result<()> {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    runtime.block_on(async {
+        let listener = tokio::net::TcpListener::bind(addr).await?;
+        axum::serve(listener, router(conn)).await
+    })
+}
+
+/// An error response: the status it maps to plus a plain-text body.
+struct ApiError(StatusCode, String);
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.0, self.1).into_response()
+    }
+}
+
+impl From<RepoError> for ApiError {
+    fn from(e: RepoError) -> Self {
+        let status = match e {
+            RepoError::ManifestNotFound(_) => StatusCode::NOT_FOUND,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        ApiError(status, e.to_string())
+    }
+}
+
+impl From<CoverageError> for ApiError {
+    fn from(e: CoverageError) -> Self {
+        match e {
+            CoverageError::Repo(e) => e.into(),
+            // Bucket errors on a stored manifest mean the window the
+            // caller asked for is unusable, not that the server broke.
+            CoverageError::Bucket(e) => ApiError(StatusCode::BAD_REQUEST, e.to_string()),
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ManifestRow {
+    manifest_id: i64,
+    symbol: String,
+    asset_class: String,
+    provider: String,
+    timeframe: String,
+    desired_start: DateTime<Utc>,
+    desired_end: Option<DateTime<Utc>>,
+    status: &'static str,
+    priority: i64,
+    enabled: bool,
+}
+
+impl From<Manifest> for ManifestRow {
+    fn from(m: Manifest) -> Self {
+        ManifestRow {
+            manifest_id: m.manifest_id,
+            symbol: m.symbol,
+            asset_class: m.asset_class,
+            provider: m.provider,
+            timeframe: m.timeframe.to_string(),
+            desired_start: m.desired_start,
+            desired_end: m.desired_end,
+            status: m.status.as_str(),
+            priority: m.priority,
+            enabled: m.enabled,
+        }
+    }
+}
+
+async fn list_manifests(State(db): State<Db>) -> Result<Json<Vec<ManifestRow>>, ApiError> {
+    let conn = db.lock().expect("no handler panics while holding the DB");
+    let manifests = SqliteRepo::manifests_all(&conn)?;
+    Ok(Json(manifests.into_iter().map(ManifestRow::from).collect()))
+}
+
+#[derive(Deserialize)]
+struct WindowParams {
+    /// Defaults to the manifest's desired start.
+    start: Option<DateTime<Utc>>,
+    /// Defaults to the manifest's desired end, or "now" if open-ended.
+    end: Option<DateTime<Utc>>,
+}
+
+#[derive(Serialize)]
+struct RangeRow {
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+    covered: bool,
+}
+
+#[derive(Serialize)]
+struct CoverageReport {
+    manifest_id: i64,
+    window_start: DateTime<Utc>,
+    window_end: DateTime<Utc>,
+    fraction: f64,
+    ranges: Vec<RangeRow>,
+}
+
+async fn manifest_coverage(
+    State(db): State<Db>,
+    Path(id): Path<i64>,
+    Query(params): Query<WindowParams>,
+) -> Result<Json<CoverageReport>, ApiError> {
+    let conn = db.lock().expect("no handler panics while holding the DB");
+    let manifest = SqliteRepo::manifest_by_id(&conn, id)?;
+    let start = params.start.unwrap_or(manifest.desired_start);
+    let end = params.end.or(manifest.desired_end).unwrap_or_else(Utc::now);
+    let fraction = SqliteRepo::coverage_fraction(&conn, id, (start, end))?;
+    let ranges = SqliteRepo::coverage_ranges(&conn, id, (start, end))?
+        .into_iter()
+        .map(|(start, end, covered)| RangeRow {
+            start,
+            end,
+            covered,
+        })
+        .collect();
+    Ok(Json(CoverageReport {
+        manifest_id: id,
+        window_start: start,
+        window_end: end,
+        fraction,
+        ranges,
+    }))
+}
+
+#[derive(Deserialize)]
+struct GapsParams {
+    /// `open`, `leased`, `done` or `dead`; defaults to `open`.
+    state: Option<String>,
+}
+
+#[derive(Serialize)]
+struct GapRow {
+    gap_id: i64,
+    manifest_id: i64,
+    start_bucket: u32,
+    end_bucket: u32,
+    state: &'static str,
+    attempts: i64,
+    lease_expires_at: Option<DateTime<Utc>>,
+    leased_by: Option<String>,
+}
+
+impl From<Gap> for GapRow {
+    fn from(g: Gap) -> Self {
+        GapRow {
+            gap_id: g.gap_id,
+            manifest_id: g.manifest_id,
+            start_bucket: g.start_bucket,
+            end_bucket: g.end_bucket,
+            state: g.state.as_str(),
+            attempts: g.attempts,
+            lease_expires_at: g.lease_expires_at,
+            leased_by: g.leased_by,
+        }
+    }
+}
+
+async fn list_gaps(
+    State(db): State<Db>,
+    Query(params): Query<GapsParams>,
+) -> Result<Json<Vec<GapRow>>, ApiError> {
+    let state = match params.state.as_deref() {
+        None => GapState::Open,
+        Some(s) => GapState::parse(s)
+            .ok_or_else(|| ApiError(StatusCode::BAD_REQUEST, format!("unknown gap state {s:?}")))?,
+    };
+    let conn = db.lock().expect("no handler panics while holding the DB");
+    let gaps = SqliteRepo::gaps_by_state(&conn, state)?;
+    Ok(Json(gaps.into_iter().map(GapRow::from).collect()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repo::test_support;
+    use axum::body::Body;
+    use axum::http::Request;
+    use roaring::RoaringBitmap;
+    use tower::ServiceExt;
+
+    fn get_json(router: Router, uri: &str) -> (StatusCode, serde_json::Value) {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let response = router
+                .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+                .await
+                .unwrap();
+            let status = response.status();
+            let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            let json = if bytes.is_empty() {
+                serde_json::Value::Null
+            } else {
+                serde_json::from_slice(&bytes).unwrap_or(serde_json::Value::Null)
+            };
+            (status, json)
+        })
+    }
+
+    #[test]
+    fn coverage_endpoint_reports_fraction_and_ranges() {
+        let conn = test_support::mem_conn();
+        let id = test_support::insert_manifest(
+            &conn,
+            "BTC/USD",
+            "mock",
+            test_support::minute_tf(),
+            test_support::utc(2024, 1, 1, 0, 0),
+            Some(test_support::utc(2024, 1, 1, 1, 0)),
+        );
+        // Cover the first half-hour of the one-hour desired window.
+        let (first, _) = crate::bucket::bucket_range(
+            test_support::utc(2024, 1, 1, 0, 0),
+            test_support::utc(2024, 1, 1, 1, 0),
+            &test_support::minute_tf(),
+        )
+        .unwrap();
+        let bitmap: RoaringBitmap = (0..30).collect();
+        SqliteRepo::coverage_put(&conn, id, 0, first, &bitmap).unwrap();
+
+        let (status, body) = get_json(router(conn), &format!("/manifests/{id}/coverage"));
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["manifest_id"], id);
+        assert!((body["fraction"].as_f64().unwrap() - 0.5).abs() < 1e-9);
+        let ranges = body["ranges"].as_array().unwrap();
+        assert_eq!(ranges.len(), 2);
+        assert_eq!(ranges[0]["covered"], true);
+        assert_eq!(ranges[0]["end"], "2024-01-01T00:30:00Z");
+        assert_eq!(ranges[1]["covered"], false);
+    }
+
+    #[test]
+    fn unknown_manifest_is_404_and_bad_gap_state_is_400() {
+        let conn = test_support::mem_conn();
+        let app = router(conn);
+        let (status, _) = get_json(app.clone(), "/manifests/999/coverage");
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (status, _) = get_json(app.clone(), "/gaps?state=queued");
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        // With no gaps queued the default filter returns an empty list.
+        let (status, body) = get_json(app, "/gaps");
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, serde_json::json!([]));
+    }
+}
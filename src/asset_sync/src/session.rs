@@ -0,0 +1,118 @@
+//! Trading-session definitions used to decide which buckets are "desired".
+
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+
+use chrono::{NaiveDate, NaiveTime};
+use thiserror::Error;
+
+/// Per-provider knobs that affect session math.
+#[derive(Debug, Clone)]
+pub struct ProviderCfg {
+    pub name: String,
+    /// Whether the provider serves pre/post-market bars.
+    pub supports_extended: bool,
+}
+
+/// Daily session window (local exchange time), Monday through Friday.
+/// US equities: regular 09:30–16:00, extended 04:00–20:00.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SessionCalendar {
+    pub open: NaiveTime,
+    pub close: NaiveTime,
+}
+
+impl SessionCalendar {
+    pub fn regular() -> Self {
+        SessionCalendar {
+            open: NaiveTime::from_hms_opt(9, 30, 0).unwrap(),
+            close: NaiveTime::from_hms_opt(16, 0, 0).unwrap(),
+        }
+    }
+
+    pub fn extended() -> Self {
+        SessionCalendar {
+            open: NaiveTime::from_hms_opt(4, 0, 0).unwrap(),
+            close: NaiveTime::from_hms_opt(20, 0, 0).unwrap(),
+        }
+    }
+
+    /// Extended hours when the provider serves them, regular otherwise.
+    pub fn for_provider(cfg: &ProviderCfg) -> Self {
+        if cfg.supports_extended {
+            SessionCalendar::extended()
+        } else {
+            SessionCalendar::regular()
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum HolidayError {
+    #[error("reading holiday file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid holiday TOML: {0}")]
+    Toml(#[from] toml::de::Error),
+    #[error("venue {0:?} not present in holiday file")]
+    UnknownVenue(String),
+}
+
+/// Full-day market closures for one venue. Nothing is persisted; the
+/// calendar is applied when desired buckets are computed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HolidayCalendar {
+    holidays: BTreeSet<NaiveDate>,
+}
+
+impl HolidayCalendar {
+    /// A calendar with no closures (the default for venue-less assets).
+    pub fn empty() -> Self {
+        HolidayCalendar::default()
+    }
+
+    pub fn from_dates(dates: impl IntoIterator<Item = NaiveDate>) -> Self {
+        HolidayCalendar {
+            holidays: dates.into_iter().collect(),
+        }
+    }
+
+    /// Load the closures of `venue` from a TOML file of the form:
+    ///
+    /// ```toml
+    /// XNYS = ["2024-01-01", "2024-12-25"]
+    /// XNAS = ["2024-01-01"]
+    /// ```
+    pub fn from_toml_path(path: &Path, venue: &str) -> Result<Self, HolidayError> {
+        let text = std::fs::read_to_string(path)?;
+        let mut venues: HashMap<String, Vec<NaiveDate>> = toml::from_str(&text)?;
+        let dates = venues
+            .remove(venue)
+            .ok_or_else(|| HolidayError::UnknownVenue(venue.to_string()))?;
+        Ok(HolidayCalendar::from_dates(dates))
+    }
+
+    pub fn is_holiday(&self, date: NaiveDate) -> bool {
+        self.holidays.contains(&date)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+
+    use super::*;
+
+    #[test]
+    fn from_toml_path_reads_one_venue() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        writeln!(file, r#"XNYS = ["2024-01-01", "2024-12-25"]"#).unwrap();
+        let calendar = HolidayCalendar::from_toml_path(file.path(), "XNYS").unwrap();
+        assert!(calendar.is_holiday(NaiveDate::from_ymd_opt(2024, 1, 1).unwrap()));
+        assert!(!calendar.is_holiday(NaiveDate::from_ymd_opt(2024, 1, 2).unwrap()));
+
+        assert!(matches!(
+            HolidayCalendar::from_toml_path(file.path(), "XLON"),
+            Err(HolidayError::UnknownVenue(_))
+        ));
+    }
+}
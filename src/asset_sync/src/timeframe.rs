@@ -0,0 +1,151 @@
+//! Bar timeframes as stored in the `manifests` table.
+//!
+//! Coverage bookkeeping works in whole minutes, so only minute-multiple
+//! units are representable here. Provider-facing timeframe types (which may
+//! carry looser semantics like "1 Month") live with the provider code.
+
+use std::fmt;
+
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum TimeframeError {
+    #[error("timeframe amount must be non-zero")]
+    ZeroAmount,
+    #[error("unknown timeframe unit {unit:?} (valid: minute, hour, day)")]
+    UnknownUnit { unit: String },
+}
+
+/// Unit of a [`Timeframe`]. Each unit has an exact minute length.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum TimeframeUnit {
+    Minute,
+    Hour,
+    Day,
+}
+
+impl TimeframeUnit {
+    pub fn minutes(self) -> u32 {
+        match self {
+            TimeframeUnit::Minute => 1,
+            TimeframeUnit::Hour => 60,
+            TimeframeUnit::Day => 24 * 60,
+        }
+    }
+
+    /// Canonical name used in the DB and in TOML catalogs.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            TimeframeUnit::Minute => "minute",
+            TimeframeUnit::Hour => "hour",
+            TimeframeUnit::Day => "day",
+        }
+    }
+}
+
+/// A bar interval: `amount` × `unit`, e.g. 5 × Minute.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Timeframe {
+    amount: u32,
+    unit: TimeframeUnit,
+}
+
+impl Timeframe {
+    pub fn new(amount: u32, unit: TimeframeUnit) -> Result<Self, TimeframeError> {
+        if amount == 0 {
+            return Err(TimeframeError::ZeroAmount);
+        }
+        Ok(Timeframe { amount, unit })
+    }
+
+    pub fn amount(&self) -> u32 {
+        self.amount
+    }
+
+    pub fn unit(&self) -> TimeframeUnit {
+        self.unit
+    }
+
+    /// Total bucket width in minutes.
+    pub fn minutes(&self) -> u32 {
+        self.amount * self.unit.minutes()
+    }
+
+    /// Rehydrate from the `(tf_amount, tf_unit)` columns of a manifest
+    /// row. The unit string is the contract with
+    /// [`TimeframeUnit::as_str`]; anything else means a corrupt or
+    /// newer-schema DB and surfaces as [`TimeframeError::UnknownUnit`]
+    /// rather than a panic.
+    pub fn from_db_row(amount: i64, unit: &str) -> Result<Self, TimeframeError> {
+        let unit = match unit {
+            "minute" => TimeframeUnit::Minute,
+            "hour" => TimeframeUnit::Hour,
+            "day" => TimeframeUnit::Day,
+            other => {
+                return Err(TimeframeError::UnknownUnit {
+                    unit: other.to_string(),
+                });
+            }
+        };
+        Timeframe::new(amount as u32, unit)
+    }
+}
+
+impl fmt::Display for Timeframe {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.amount, self.unit.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn minutes_are_exact() {
+        assert_eq!(
+            Timeframe::new(5, TimeframeUnit::Minute).unwrap().minutes(),
+            5
+        );
+        assert_eq!(
+            Timeframe::new(2, TimeframeUnit::Hour).unwrap().minutes(),
+            120
+        );
+        assert_eq!(
+            Timeframe::new(1, TimeframeUnit::Day).unwrap().minutes(),
+            1440
+        );
+    }
+
+    #[test]
+    fn from_db_row_rejects_unknown_units_with_a_clear_message() {
+        let err = Timeframe::from_db_row(1, "Fortnight").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "unknown timeframe unit \"Fortnight\" (valid: minute, hour, day)"
+        );
+    }
+
+    #[test]
+    fn db_unit_strings_round_trip_every_unit() {
+        for unit in [
+            TimeframeUnit::Minute,
+            TimeframeUnit::Hour,
+            TimeframeUnit::Day,
+        ] {
+            let tf = Timeframe::new(3, unit).unwrap();
+            assert_eq!(
+                Timeframe::from_db_row(i64::from(tf.amount()), tf.unit().as_str()),
+                Ok(tf)
+            );
+        }
+    }
+
+    #[test]
+    fn zero_amount_rejected() {
+        assert_eq!(
+            Timeframe::new(0, TimeframeUnit::Minute),
+            Err(TimeframeError::ZeroAmount)
+        );
+    }
+}
@@ -0,0 +1,110 @@
+//! Local-time → UTC conversion helpers that make DST edge cases explicit.
+
+use chrono::{DateTime, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+
+/// How to resolve a local wall-clock time that maps to zero or two UTC
+/// instants around a DST transition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DstPolicy {
+    /// Ambiguous times resolve to the earlier instant (pre-transition
+    /// offset). This is what session math wants: the session opens as
+    /// soon as the wall clock says so.
+    #[default]
+    Earliest,
+    /// Ambiguous times resolve to the later instant.
+    Latest,
+}
+
+/// Module-wide conversion settings, for applications that pick one
+/// [`DstPolicy`] once instead of threading it through every call. Holds
+/// only the policy today; a struct so later knobs don't ripple through
+/// signatures again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct TzConfig {
+    /// Policy [`local_to_utc_cfg`] applies to ambiguous times.
+    pub default_policy: DstPolicy,
+}
+
+/// [`local_to_utc`] with the policy taken from `cfg` — the primitive
+/// stays the explicit-policy function.
+pub fn local_to_utc_cfg(local: NaiveDateTime, tz: Tz, cfg: &TzConfig) -> Option<DateTime<Utc>> {
+    local_to_utc(local, tz, cfg.default_policy)
+}
+
+/// Convert a local wall-clock time in `tz` to UTC under `policy`.
+/// Returns `None` for nonexistent times (skipped by a spring-forward).
+pub fn local_to_utc(local: NaiveDateTime, tz: Tz, policy: DstPolicy) -> Option<DateTime<Utc>> {
+    match tz.from_local_datetime(&local) {
+        chrono::LocalResult::Single(dt) => Some(dt.with_timezone(&Utc)),
+        chrono::LocalResult::Ambiguous(earliest, latest) => Some(match policy {
+            DstPolicy::Earliest => earliest.with_timezone(&Utc),
+            DstPolicy::Latest => latest.with_timezone(&Utc),
+        }),
+        chrono::LocalResult::None => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+
+    use super::*;
+
+    fn naive(y: i32, mo: u32, d: u32, h: u32, mi: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(y, mo, d)
+            .unwrap()
+            .and_hms_opt(h, mi, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn unambiguous_time_converts() {
+        let utc = local_to_utc(
+            naive(2024, 1, 15, 9, 30),
+            chrono_tz::America::New_York,
+            DstPolicy::Earliest,
+        )
+        .unwrap();
+        assert_eq!(utc.to_rfc3339(), "2024-01-15T14:30:00+00:00");
+    }
+
+    #[test]
+    fn ambiguous_fall_back_honors_policy() {
+        // 2024-11-03 01:30 happens twice in New York.
+        let tz = chrono_tz::America::New_York;
+        let early = local_to_utc(naive(2024, 11, 3, 1, 30), tz, DstPolicy::Earliest).unwrap();
+        let late = local_to_utc(naive(2024, 11, 3, 1, 30), tz, DstPolicy::Latest).unwrap();
+        assert_eq!(late - early, chrono::Duration::hours(1));
+    }
+
+    #[test]
+    fn config_driven_conversion_matches_the_explicit_policy() {
+        let tz = chrono_tz::America::New_York;
+        let ambiguous = naive(2024, 11, 3, 1, 30);
+        let cfg = TzConfig {
+            default_policy: DstPolicy::Latest,
+        };
+        assert_eq!(
+            local_to_utc_cfg(ambiguous, tz, &cfg),
+            local_to_utc(ambiguous, tz, DstPolicy::Latest)
+        );
+        assert_eq!(
+            local_to_utc_cfg(ambiguous, tz, &TzConfig::default()),
+            local_to_utc(ambiguous, tz, DstPolicy::Earliest)
+        );
+    }
+
+    #[test]
+    fn nonexistent_spring_forward_is_none() {
+        // 2024-03-10 02:30 never happens in New York.
+        assert!(
+            local_to_utc(
+                naive(2024, 3, 10, 2, 30),
+                chrono_tz::America::New_York,
+                DstPolicy::Earliest
+            )
+            .is_none()
+        );
+    }
+}
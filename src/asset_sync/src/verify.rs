@@ -0,0 +1,380 @@
+//! Cross-check between coverage bitmaps and bars actually stored in a
+//! Delta table (feature `delta`).
+//!
+//! Coverage and storage are written separately, so a crash between the
+//! storage write and the coverage merge — or the reverse order — can leave
+//! buckets marked covered with no bars behind them. That state is worse
+//! than a plain gap: the scheduler will never refetch it. [`verify_manifest`]
+//! reads the covered window back from the table and reports every covered
+//! bucket with no bar in it.
+
+use roaring::RoaringBitmap;
+use thiserror::Error;
+
+use market_data_ingestor::models::bar::BarSeries;
+use market_data_ingestor::models::timeframe as ingestor_tf;
+use market_data_ingestor::storage::delta::{DeltaStorageError, read_bars};
+
+use crate::bucket::{self, BucketError};
+use crate::coverage::{UtcRange, coalesce_runs_to_utc_ranges};
+use crate::repo::{RepoError, SqliteRepo};
+use crate::timeframe::{Timeframe, TimeframeUnit};
+
+#[derive(Debug, Error)]
+pub enum VerifyError {
+    #[error(transparent)]
+    Repo(#[from] RepoError),
+    #[error(transparent)]
+    Bucket(#[from] BucketError),
+    #[error(transparent)]
+    Storage(#[from] DeltaStorageError),
+    #[error("timeframe not representable for storage reads: {0}")]
+    Timeframe(String),
+}
+
+/// Outcome of one manifest check. `phantom` buckets are covered in the
+/// bitmap but have no bar in the table.
+#[derive(Debug)]
+pub struct VerifyReport {
+    pub manifest_id: i64,
+    pub covered_buckets: u64,
+    pub phantom_buckets: u64,
+    pub phantom_ranges: Vec<UtcRange>,
+}
+
+/// Bucket ids (relative to `base`) of every bar in `series`. Bars from
+/// before `base` cannot be expressed and surface as a bucket error rather
+/// than being dropped.
+pub fn series_to_bitmap(
+    series: &BarSeries,
+    tf: &Timeframe,
+    base: u64,
+) -> Result<RoaringBitmap, BucketError> {
+    series
+        .bars
+        .iter()
+        .map(|bar| bucket::bucket_of(bar.timestamp, tf).and_then(|id| bucket::rel(id, base)))
+        .collect()
+}
+
+/// [`series_to_bitmap`] with tolerance for tiny interior holes: runs of up
+/// to `fill_small_gaps` consecutive missing buckets with present buckets
+/// on both sides are marked covered, reading them as "no trades printed"
+/// rather than "data missing". Providers skip the odd illiquid bar, and
+/// treating each skip as a permanent gap means refetching it forever.
+///
+/// The risk is the flip side: a genuinely dropped bar inside such a run is
+/// recorded as covered and never refetched. Keep the tolerance at the
+/// scale of the provider's known skips — a minute or two — never the
+/// session. Leading and trailing holes are always left open; only what
+/// the series brackets can plausibly be "no trades".
+pub fn series_to_bitmap_filled(
+    series: &BarSeries,
+    tf: &Timeframe,
+    base: u64,
+    fill_small_gaps: usize,
+) -> Result<RoaringBitmap, BucketError> {
+    let mut bitmap = series_to_bitmap(series, tf, base)?;
+    if fill_small_gaps == 0 {
+        return Ok(bitmap);
+    }
+    let mut fills = Vec::new();
+    let mut prev: Option<u32> = None;
+    for id in bitmap.iter() {
+        if let Some(p) = prev
+            && id - p > 1
+            && (id - p - 1) as usize <= fill_small_gaps
+        {
+            fills.push(p + 1..id);
+        }
+        prev = Some(id);
+    }
+    for range in fills {
+        bitmap.insert_range(range);
+    }
+    Ok(bitmap)
+}
+
+/// The provider-facing timeframe matching a manifest timeframe; fails for
+/// combinations the ingestor rejects (e.g. multi-day bars).
+fn storage_timeframe(tf: &Timeframe) -> Result<ingestor_tf::TimeFrame, VerifyError> {
+    let unit = match tf.unit() {
+        TimeframeUnit::Minute => ingestor_tf::TimeFrameUnit::Minute,
+        TimeframeUnit::Hour => ingestor_tf::TimeFrameUnit::Hour,
+        TimeframeUnit::Day => ingestor_tf::TimeFrameUnit::Day,
+    };
+    ingestor_tf::TimeFrame::new(tf.amount(), unit)
+        .map_err(|e| VerifyError::Timeframe(e.to_string()))
+}
+
+/// Check every covered bucket of `manifest_id` against the bars stored at
+/// `table_uri`.
+#[cfg_attr(
+    feature = "tracing",
+    tracing::instrument(skip(conn), fields(phantom = tracing::field::Empty))
+)]
+pub fn verify_manifest(
+    conn: &rusqlite::Connection,
+    table_uri: &str,
+    manifest_id: i64,
+) -> Result<VerifyReport, VerifyError> {
+    let manifest = SqliteRepo::manifest_by_id(conn, manifest_id)?;
+    let snap = SqliteRepo::coverage_get(conn, manifest_id)?;
+    let tf = manifest.timeframe;
+
+    let (Some(first), Some(last)) = (snap.bitmap.min(), snap.bitmap.max()) else {
+        return Ok(VerifyReport {
+            manifest_id,
+            covered_buckets: 0,
+            phantom_buckets: 0,
+            phantom_ranges: Vec::new(),
+        });
+    };
+
+    let start = bucket::bucket_start(bucket::abs(first, snap.bucket_base), &tf);
+    let end = bucket::bucket_start(bucket::abs(last, snap.bucket_base) + 1, &tf);
+    let series = read_bars(
+        table_uri,
+        std::slice::from_ref(&manifest.symbol),
+        storage_timeframe(&tf)?,
+        start,
+        end,
+    )?;
+    let actual = series_to_bitmap(&series[0], &tf, snap.bucket_base)?;
+
+    let phantom = &snap.bitmap - actual;
+    #[cfg(feature = "tracing")]
+    tracing::Span::current().record("phantom", phantom.len());
+    let phantom_ranges = coalesce_runs_to_utc_ranges(
+        phantom.iter().map(|id| bucket::abs(id, snap.bucket_base)),
+        &tf,
+    );
+    Ok(VerifyReport {
+        manifest_id,
+        covered_buckets: snap.bitmap.len(),
+        phantom_buckets: phantom.len(),
+        phantom_ranges,
+    })
+}
+
+/// Outcome of rebuilding one manifest's coverage from stored bars.
+#[derive(Debug)]
+pub struct ReconcileReport {
+    pub manifest_id: i64,
+    pub symbol: String,
+    /// Buckets with stored bars that the bitmap did not claim before.
+    pub buckets_added: u64,
+}
+
+/// Re-derive coverage from the bars actually stored at `table_uri`: the
+/// inverse of [`verify_manifest`]. Verification reports coverage with no
+/// data behind it; reconciliation claims data with no coverage over it —
+/// the state a backup restore or an external import leaves behind, where
+/// every unclaimed bucket would otherwise be refetched from the provider
+/// for nothing.
+///
+/// For each manifest, the desired window (open-ended ones clamped to
+/// `now`) is read back from the table, bucketized, and unioned into the
+/// stored bitmap. Nothing is ever removed — pruning phantom coverage is
+/// [`verify_manifest`]'s job to report and the operator's to act on.
+pub fn reconcile_coverage(
+    conn: &rusqlite::Connection,
+    table_uri: &str,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Result<Vec<ReconcileReport>, VerifyError> {
+    let mut reports = Vec::new();
+    for manifest in SqliteRepo::manifests_all(conn)? {
+        let end = manifest.desired_end.unwrap_or(now).min(now);
+        if manifest.desired_start >= end {
+            continue;
+        }
+        let tf = manifest.timeframe;
+        let (first, _) = bucket::bucket_range(manifest.desired_start, end, &tf)?;
+        let series = read_bars(
+            table_uri,
+            std::slice::from_ref(&manifest.symbol),
+            storage_timeframe(&tf)?,
+            manifest.desired_start,
+            end,
+        )?;
+
+        let snap = SqliteRepo::coverage_get(conn, manifest.manifest_id)?;
+        // A manifest with no bitmap yet anchors its base at the window
+        // start, the same choice the missing-range computation makes.
+        let base = if snap.version == 0 {
+            first
+        } else {
+            snap.bucket_base
+        };
+        let actual = series_to_bitmap(&series[0], &tf, base)?;
+        let buckets_added = (&actual - &snap.bitmap).len();
+        if buckets_added > 0 {
+            SqliteRepo::coverage_put(
+                conn,
+                manifest.manifest_id,
+                snap.version,
+                base,
+                &(snap.bitmap | actual),
+            )?;
+        }
+        reports.push(ReconcileReport {
+            manifest_id: manifest.manifest_id,
+            symbol: manifest.symbol,
+            buckets_added,
+        });
+    }
+    Ok(reports)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::repo::test_support::*;
+    use market_data_ingestor::models::bar::Bar;
+    use market_data_ingestor::storage::delta::write_bars;
+
+    #[test]
+    fn reports_covered_buckets_without_stored_bars() {
+        let conn = mem_conn();
+        let tf = Timeframe::new(1, TimeframeUnit::Minute).unwrap();
+        let start = utc(2024, 1, 2, 14, 30);
+        let end = utc(2024, 1, 2, 14, 35);
+        let id = insert_manifest(&conn, "AAPL", "alpaca", tf, start, Some(end));
+
+        // Store bars for 14:30 and 14:31 only.
+        let dir = tempfile::tempdir().unwrap();
+        let table_uri = dir.path().to_str().unwrap().to_string();
+        let bar = |minute: u32| Bar {
+            timestamp: utc(2024, 1, 2, 14, minute),
+            open: 10.0,
+            high: 10.5,
+            low: 9.5,
+            close: 10.2,
+            volume: 100.0,
+            trade_count: Some(10),
+            vwap: Some(10.1),
+        };
+        write_bars(
+            &table_uri,
+            &[BarSeries {
+                symbol: "AAPL".to_string(),
+                timeframe: storage_timeframe(&tf).unwrap(),
+                bars: vec![bar(30), bar(31)],
+                source_feed: None,
+            }],
+        )
+        .unwrap();
+
+        // Claim coverage of 14:30-14:33: one phantom run at 14:32-14:33.
+        let (first, _) = bucket::bucket_range(start, end, &tf).unwrap();
+        let mut bm = RoaringBitmap::new();
+        bm.insert_range(0..3);
+        SqliteRepo::coverage_put(&conn, id, 0, first, &bm).unwrap();
+
+        let report = verify_manifest(&conn, &table_uri, id).unwrap();
+        assert_eq!(report.covered_buckets, 3);
+        assert_eq!(report.phantom_buckets, 1);
+        assert_eq!(
+            report.phantom_ranges,
+            vec![(utc(2024, 1, 2, 14, 32), utc(2024, 1, 2, 14, 33))]
+        );
+    }
+
+    #[test]
+    fn reconcile_claims_stored_bars_the_bitmap_missed() {
+        let conn = mem_conn();
+        let tf = Timeframe::new(1, TimeframeUnit::Minute).unwrap();
+        let start = utc(2024, 1, 2, 14, 30);
+        let end = utc(2024, 1, 2, 14, 35);
+        let id = insert_manifest(&conn, "AAPL", "alpaca", tf, start, Some(end));
+
+        // The table holds four bars; the bitmap (restored from an old
+        // backup, say) only claims the first two.
+        let dir = tempfile::tempdir().unwrap();
+        let table_uri = dir.path().to_str().unwrap().to_string();
+        let bar = |minute: u32| Bar {
+            timestamp: utc(2024, 1, 2, 14, minute),
+            open: 10.0,
+            high: 10.5,
+            low: 9.5,
+            close: 10.2,
+            volume: 100.0,
+            trade_count: Some(10),
+            vwap: Some(10.1),
+        };
+        write_bars(
+            &table_uri,
+            &[BarSeries {
+                symbol: "AAPL".to_string(),
+                timeframe: storage_timeframe(&tf).unwrap(),
+                bars: vec![bar(30), bar(31), bar(32), bar(33)],
+                source_feed: None,
+            }],
+        )
+        .unwrap();
+        let (first, _) = bucket::bucket_range(start, end, &tf).unwrap();
+        let mut stale = RoaringBitmap::new();
+        stale.insert_range(0..2);
+        SqliteRepo::coverage_put(&conn, id, 0, first, &stale).unwrap();
+
+        let now = utc(2024, 6, 1, 0, 0);
+        let reports = reconcile_coverage(&conn, &table_uri, now).unwrap();
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].manifest_id, id);
+        assert_eq!(reports[0].buckets_added, 2);
+
+        // Coverage caught up to the table: only 14:34 stays missing.
+        let missing = crate::coverage::compute_missing(&conn, id, now).unwrap();
+        assert_eq!(missing, vec![(utc(2024, 1, 2, 14, 34), end)]);
+
+        // A second pass finds nothing left to claim.
+        let reports = reconcile_coverage(&conn, &table_uri, now).unwrap();
+        assert_eq!(reports[0].buckets_added, 0);
+    }
+
+    #[test]
+    fn small_interior_gaps_fill_under_the_tolerance_and_large_ones_stay() {
+        let tf = Timeframe::new(1, TimeframeUnit::Minute).unwrap();
+        let bar = |minute: u32| Bar {
+            timestamp: utc(2024, 1, 2, 14, minute),
+            open: 10.0,
+            high: 10.5,
+            low: 9.5,
+            close: 10.2,
+            volume: 100.0,
+            trade_count: Some(10),
+            vwap: Some(10.1),
+        };
+        // Buckets 0,1,3,4 (one-bucket hole), then 10,11 after a five-bucket
+        // hole.
+        let series = BarSeries {
+            symbol: "AAPL".to_string(),
+            timeframe: storage_timeframe(&tf).unwrap(),
+            bars: vec![bar(30), bar(31), bar(33), bar(34), bar(40), bar(41)],
+            source_feed: None,
+        };
+        let (base, _) =
+            crate::bucket::bucket_range(utc(2024, 1, 2, 14, 30), utc(2024, 1, 2, 14, 42), &tf)
+                .unwrap();
+
+        let filled = series_to_bitmap_filled(&series, &tf, base, 2).unwrap();
+        let ids: Vec<u32> = filled.iter().collect();
+        assert_eq!(ids, vec![0, 1, 2, 3, 4, 10, 11]);
+
+        // Tolerance 0 is the plain bucketization.
+        assert_eq!(
+            series_to_bitmap_filled(&series, &tf, base, 0).unwrap(),
+            series_to_bitmap(&series, &tf, base).unwrap()
+        );
+    }
+
+    #[test]
+    fn empty_coverage_verifies_without_touching_the_table() {
+        let conn = mem_conn();
+        let tf = Timeframe::new(1, TimeframeUnit::Minute).unwrap();
+        let id = insert_manifest(&conn, "AAPL", "alpaca", tf, utc(2024, 1, 2, 14, 30), None);
+        let report = verify_manifest(&conn, "/nonexistent/table", id).unwrap();
+        assert_eq!(report.covered_buckets, 0);
+        assert_eq!(report.phantom_buckets, 0);
+    }
+}
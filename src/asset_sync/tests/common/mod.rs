@@ -0,0 +1,15 @@
+//! Shared setup for integration tests.
+
+use rusqlite::Connection;
+
+use asset_sync::repo::SqliteRepo;
+
+/// A migrated SQLite database in a temp directory, as a worker process
+/// would open it. The `TempDir` must be kept alive alongside the
+/// connection.
+pub fn setup_db() -> (tempfile::TempDir, Connection) {
+    let dir = tempfile::tempdir().expect("create temp dir");
+    let conn = Connection::open(dir.path().join("asset_sync.db")).expect("open db");
+    SqliteRepo::init(&conn).expect("migrate schema");
+    (dir, conn)
+}
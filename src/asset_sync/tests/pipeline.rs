@@ -0,0 +1,246 @@
+//! End-to-end pipeline test: catalog spec → manifest → missing ranges →
+//! gap queue → (mock) fetch → coverage merge → gap completion.
+//!
+//! Every piece here is exercised on its own in unit tests; this file
+//! proves they compose, and is the place regressions in the handoffs
+//! between them show up.
+
+mod common;
+
+use chrono::{DateTime, Duration, TimeZone, Utc};
+use roaring::RoaringBitmap;
+
+use asset_sync::bucket;
+use asset_sync::catalog::{load_catalog_str, sync_catalog};
+use asset_sync::coverage::compute_missing;
+use asset_sync::repo::{GapState, SqliteRepo};
+
+use market_data_ingestor::models::bar::{Bar, BarSeries};
+use market_data_ingestor::models::request_params::BarsRequestParams;
+use market_data_ingestor::models::timeframe::TimeFrameUnit;
+use market_data_ingestor::providers::{DataProvider, ProviderCapabilities, ProviderError};
+
+/// Deterministic in-memory provider: one bar per timeframe bucket whose
+/// start lies in the requested window, prices derived from the timestamp.
+struct MockProvider;
+
+impl DataProvider for MockProvider {
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            max_symbols_per_request: 10,
+            earliest_data: None,
+            max_bars_per_window: None,
+        }
+    }
+
+    fn fetch_bars(&self, params: &BarsRequestParams) -> Result<Vec<BarSeries>, ProviderError> {
+        let amount = i64::from(params.timeframe.amount());
+        let step = match params.timeframe.unit() {
+            TimeFrameUnit::Minute => Duration::minutes(amount),
+            TimeFrameUnit::Hour => Duration::hours(amount),
+            TimeFrameUnit::Day => Duration::days(amount),
+            other => {
+                return Err(ProviderError::InvalidRequest(format!(
+                    "mock provider does not serve {other:?} bars"
+                )));
+            }
+        };
+        Ok(params
+            .symbols
+            .iter()
+            .map(|symbol| {
+                let mut bars = Vec::new();
+                let mut ts = params.start;
+                while ts < params.end {
+                    let price = 100.0 + (ts.timestamp() % 1000) as f64 / 10.0;
+                    bars.push(Bar {
+                        timestamp: ts,
+                        open: price,
+                        high: price + 1.0,
+                        low: price - 1.0,
+                        close: price + 0.5,
+                        volume: 1_000.0,
+                        trade_count: Some(10),
+                        vwap: Some(price),
+                    });
+                    ts += step;
+                }
+                BarSeries {
+                    symbol: symbol.clone(),
+                    timeframe: params.timeframe,
+                    bars,
+                    source_feed: None,
+                }
+            })
+            .collect())
+    }
+}
+
+fn utc(y: i32, mo: u32, d: u32, h: u32, mi: u32) -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(y, mo, d, h, mi, 0).unwrap()
+}
+
+/// A provider that only recognizes its own remote symbols, like a real
+/// futures venue that has never heard of the canonical root.
+struct RemoteKeyedProvider {
+    known_remotes: Vec<&'static str>,
+}
+
+impl DataProvider for RemoteKeyedProvider {
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            max_symbols_per_request: 10,
+            earliest_data: None,
+            max_bars_per_window: None,
+        }
+    }
+
+    fn fetch_bars(&self, params: &BarsRequestParams) -> Result<Vec<BarSeries>, ProviderError> {
+        params
+            .symbols
+            .iter()
+            .map(|symbol| {
+                if !self.known_remotes.contains(&symbol.as_str()) {
+                    return Err(ProviderError::InvalidRequest(format!(
+                        "unknown symbol {symbol:?}"
+                    )));
+                }
+                Ok(BarSeries {
+                    symbol: symbol.clone(),
+                    timeframe: params.timeframe,
+                    bars: vec![Bar {
+                        timestamp: params.start,
+                        open: 100.0,
+                        high: 101.0,
+                        low: 99.0,
+                        close: 100.5,
+                        volume: 1_000.0,
+                        trade_count: Some(10),
+                        vwap: Some(100.0),
+                    }],
+                    source_feed: None,
+                })
+            })
+            .collect()
+    }
+}
+
+#[test]
+fn fetch_path_translates_canonical_symbols_through_the_map() {
+    use asset_sync::fetch::fetch_bars_translated;
+    use asset_sync::repo::SqliteRepo;
+    use market_data_ingestor::models::timeframe::TimeFrame;
+
+    let (_dir, conn) = common::setup_db();
+    let provider = RemoteKeyedProvider {
+        known_remotes: vec!["ESZ5"],
+    };
+    let params = BarsRequestParams {
+        symbols: vec!["ES".to_string()],
+        timeframe: TimeFrame::new(1, TimeFrameUnit::Day).unwrap(),
+        start: utc(2024, 1, 2, 0, 0),
+        end: utc(2024, 1, 3, 0, 0),
+    };
+
+    // Without a mapping the canonical root goes out on the wire verbatim
+    // and the venue rejects it.
+    assert!(fetch_bars_translated(&conn, &provider, "cme", &params).is_err());
+
+    SqliteRepo::symbol_map_set(&conn, "cme", "ES", "ESZ5").unwrap();
+    let series = fetch_bars_translated(&conn, &provider, "cme", &params).unwrap();
+    assert_eq!(series.len(), 1);
+    // The response comes back under the canonical symbol, not the remote.
+    assert_eq!(series[0].symbol, "ES");
+    assert_eq!(series[0].bars.len(), 1);
+}
+
+#[test]
+fn full_pipeline_reaches_complete_coverage() {
+    let (_dir, conn) = common::setup_db();
+
+    // Spec → manifest. Crypto trades around the clock, so the desired
+    // window needs no session filtering and the arithmetic stays legible.
+    let catalog = load_catalog_str(
+        r#"
+        [[assets]]
+        symbol = "BTC/USD"
+        asset_class = "crypto"
+        provider = "mock"
+        start = "2024-01-01T00:00:00Z"
+        end = "2024-01-01T06:00:00Z"
+        timeframes = [{ amount = 1, unit = "hour" }]
+    "#,
+    )
+    .unwrap();
+    let diff = sync_catalog(&conn, &catalog).unwrap();
+    assert_eq!(diff.manifests_upserted, 1);
+    let manifest = SqliteRepo::manifests_open(&conn).unwrap().remove(0);
+    let tf = manifest.timeframe;
+
+    // Missing ranges → gap queue. Gap buckets are stored relative to the
+    // window's first bucket, the same base the coverage rows will use.
+    let now = utc(2024, 6, 1, 0, 0);
+    let missing = compute_missing(&conn, manifest.manifest_id, now).unwrap();
+    assert_eq!(
+        missing,
+        vec![(utc(2024, 1, 1, 0, 0), utc(2024, 1, 1, 6, 0))]
+    );
+    let (window_first, _) =
+        bucket::bucket_range(manifest.desired_start, manifest.desired_end.unwrap(), &tf).unwrap();
+    for &(start, end) in &missing {
+        let (first, end_ex) = bucket::bucket_range(start, end, &tf).unwrap();
+        SqliteRepo::gaps_insert(
+            &conn,
+            manifest.manifest_id,
+            bucket::rel(first, window_first).unwrap(),
+            bucket::rel(end_ex - 1, window_first).unwrap(),
+        )
+        .unwrap();
+    }
+
+    // Lease → fetch → merge → complete, as a worker would.
+    let provider = MockProvider;
+    let leased = SqliteRepo::gaps_lease(&conn, now, Duration::minutes(5), 10, "w1").unwrap();
+    assert_eq!(leased.len(), 1);
+    for gap in leased {
+        let start = bucket::bucket_start(bucket::abs(gap.start_bucket, window_first), &tf);
+        let end = bucket::bucket_start(bucket::abs(gap.end_bucket, window_first) + 1, &tf);
+        let series = provider
+            .fetch_bars(&BarsRequestParams {
+                symbols: vec![manifest.symbol.clone()],
+                timeframe: (&catalog.assets[0].timeframes[0]).try_into().unwrap(),
+                start,
+                end,
+            })
+            .unwrap();
+        assert_eq!(series.len(), 1);
+        assert_eq!(series[0].bars.len(), 6);
+
+        let mut fetched = RoaringBitmap::new();
+        for bar in &series[0].bars {
+            let id = bucket::bucket_of(bar.timestamp, &tf).unwrap();
+            fetched.insert(bucket::rel(id, window_first).unwrap());
+        }
+        let snap = SqliteRepo::coverage_get(&conn, manifest.manifest_id).unwrap();
+        let base = if snap.version == 0 {
+            window_first
+        } else {
+            snap.bucket_base
+        };
+        let merged = &snap.bitmap | &fetched;
+        SqliteRepo::coverage_put(&conn, manifest.manifest_id, snap.version, base, &merged).unwrap();
+        SqliteRepo::gaps_complete(&conn, gap.gap_id).unwrap();
+    }
+
+    // Coverage is complete, nothing is missing, and the queue drained.
+    let snap = SqliteRepo::coverage_get(&conn, manifest.manifest_id).unwrap();
+    assert_eq!(snap.bitmap.len(), 6);
+    assert!(
+        compute_missing(&conn, manifest.manifest_id, now)
+            .unwrap()
+            .is_empty()
+    );
+    let gaps = SqliteRepo::gaps_for_manifest(&conn, manifest.manifest_id).unwrap();
+    assert!(!gaps.is_empty());
+    assert!(gaps.iter().all(|g| g.state == GapState::Done));
+}
@@ -0,0 +1,327 @@
+//! Strict parsing of batch request parameter files.
+//!
+//! A batch file is a JSON document:
+//!
+//! ```json
+//! {
+//!   "entries": [
+//!     {
+//!       "symbols": ["AAPL", "MSFT"],
+//!       "timeframe_amount": 5,
+//!       "timeframe_unit": "minute",
+//!       "start": "2024-01-01T00:00:00Z",
+//!       "end": "2024-02-01T00:00:00Z"
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! `timeframe_unit` is a closed enum — shorthands like `"m"`, `"min"` or
+//! `"d"` are rejected rather than guessed at, and unknown fields anywhere
+//! in an entry are an error. Parse failures name the offending entry by
+//! index. The accepted shape is published as [`BATCH_PARAMS_SCHEMA`].
+
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::models::request_params::BarsRequestParams;
+use crate::models::symbol::{self, SymbolError};
+use crate::models::timeframe::{TimeFrame, TimeFrameError, TimeFrameUnit};
+
+#[derive(Debug, Error)]
+pub enum BatchError {
+    #[error("cannot read batch params file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("batch params file is not valid JSON: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("entry {index}: {source}")]
+    Entry {
+        index: usize,
+        source: serde_json::Error,
+    },
+    #[error("entry {index}: {source}")]
+    Timeframe {
+        index: usize,
+        source: TimeFrameError,
+    },
+    #[error("entry {index}: symbols must be non-empty")]
+    EmptySymbols { index: usize },
+    #[error("entry {index}: {source}")]
+    Symbol { index: usize, source: SymbolError },
+    #[error("entry {index}: start must precede end")]
+    InvertedWindow { index: usize },
+}
+
+/// JSON Schema (draft-07) for batch params files, for editor tooling and
+/// `--print-schema`.
+pub const BATCH_PARAMS_SCHEMA: &str = r##"{
+  "$schema": "http://json-schema.org/draft-07/schema#",
+  "title": "Batch bars request parameters",
+  "type": "object",
+  "additionalProperties": false,
+  "required": ["entries"],
+  "properties": {
+    "entries": {
+      "type": "array",
+      "items": {
+        "type": "object",
+        "additionalProperties": false,
+        "required": ["symbols", "timeframe_amount", "timeframe_unit", "start", "end"],
+        "properties": {
+          "symbols": {
+            "type": "array",
+            "items": { "type": "string" },
+            "minItems": 1
+          },
+          "timeframe_amount": { "type": "integer", "minimum": 1 },
+          "timeframe_unit": {
+            "type": "string",
+            "enum": ["minute", "hour", "day", "week", "month"]
+          },
+          "start": { "type": "string", "format": "date-time" },
+          "end": { "type": "string", "format": "date-time" }
+        }
+      }
+    }
+  }
+}
+"##;
+
+/// `timeframe_unit` values accepted in batch files. Deliberately a closed
+/// set with no aliases, so an ambiguous `"m"` can never silently mean
+/// minute on one machine and month on another.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BatchUnit {
+    Minute,
+    Hour,
+    Day,
+    Week,
+    Month,
+}
+
+impl From<BatchUnit> for TimeFrameUnit {
+    fn from(unit: BatchUnit) -> Self {
+        match unit {
+            BatchUnit::Minute => TimeFrameUnit::Minute,
+            BatchUnit::Hour => TimeFrameUnit::Hour,
+            BatchUnit::Day => TimeFrameUnit::Day,
+            BatchUnit::Week => TimeFrameUnit::Week,
+            BatchUnit::Month => TimeFrameUnit::Month,
+        }
+    }
+}
+
+/// One batch entry as written in the file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BatchEntry {
+    pub symbols: Vec<String>,
+    pub timeframe_amount: u32,
+    pub timeframe_unit: BatchUnit,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+// Entries stay opaque here so per-entry deserialization errors can carry
+// the entry index.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BatchFile {
+    entries: Vec<serde_json::Value>,
+}
+
+/// Parse batch params from JSON text into validated fetch requests.
+pub fn parse_batch_params_str(s: &str) -> Result<Vec<BarsRequestParams>, BatchError> {
+    let file: BatchFile = serde_json::from_str(s)?;
+    file.entries
+        .into_iter()
+        .enumerate()
+        .map(|(index, value)| {
+            let entry: BatchEntry = serde_json::from_value(value)
+                .map_err(|source| BatchError::Entry { index, source })?;
+            request_from_entry(index, entry)
+        })
+        .collect()
+}
+
+/// Validate one entry into a fetch request; `index` names it in errors.
+fn request_from_entry(index: usize, entry: BatchEntry) -> Result<BarsRequestParams, BatchError> {
+    if entry.symbols.is_empty() {
+        return Err(BatchError::EmptySymbols { index });
+    }
+    let symbols = entry
+        .symbols
+        .iter()
+        .map(|s| symbol::canonicalize(s))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|source| BatchError::Symbol { index, source })?;
+    if entry.start >= entry.end {
+        return Err(BatchError::InvertedWindow { index });
+    }
+    let timeframe = TimeFrame::new(entry.timeframe_amount, entry.timeframe_unit.into())
+        .map_err(|source| BatchError::Timeframe { index, source })?;
+    Ok(BarsRequestParams {
+        symbols,
+        timeframe,
+        start: entry.start,
+        end: entry.end,
+    })
+}
+
+/// Stream batch entries from NDJSON: each non-blank line is one entry
+/// object (the same shape as an element of `entries`), yielded as soon as
+/// its line is read rather than after EOF. That lets a long-running
+/// producer pipe requests in and see results flow back before it
+/// finishes. Entry indices in errors are zero-based line numbers.
+pub fn ndjson_requests<R: std::io::BufRead>(
+    reader: R,
+) -> impl Iterator<Item = Result<BarsRequestParams, BatchError>> {
+    reader
+        .lines()
+        .enumerate()
+        .filter_map(|(index, line)| match line {
+            Ok(line) if line.trim().is_empty() => None,
+            Ok(line) => {
+                let parsed = serde_json::from_str::<BatchEntry>(&line)
+                    .map_err(|source| BatchError::Entry { index, source })
+                    .and_then(|entry| request_from_entry(index, entry));
+                Some(parsed)
+            }
+            Err(e) => Some(Err(BatchError::Io(e))),
+        })
+}
+
+/// Read and parse a batch params file from disk.
+pub fn parse_batch_params_from_file(path: &Path) -> Result<Vec<BarsRequestParams>, BatchError> {
+    parse_batch_params_str(&std::fs::read_to_string(path)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const VALID: &str = r#"{
+        "entries": [{
+            "symbols": ["AAPL"],
+            "timeframe_amount": 5,
+            "timeframe_unit": "minute",
+            "start": "2024-01-01T00:00:00Z",
+            "end": "2024-02-01T00:00:00Z"
+        }]
+    }"#;
+
+    #[test]
+    fn valid_file_parses() {
+        let requests = parse_batch_params_str(VALID).unwrap();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].symbols, vec!["AAPL"]);
+        assert_eq!(requests[0].timeframe.to_string(), "5Min");
+    }
+
+    #[test]
+    fn ambiguous_unit_shorthand_rejected_with_index() {
+        let shorthand = VALID.replace("\"minute\"", "\"m\"");
+        let err = parse_batch_params_str(&shorthand).unwrap_err();
+        assert!(matches!(err, BatchError::Entry { index: 0, .. }), "{err}");
+    }
+
+    #[test]
+    fn unknown_field_rejected_with_index() {
+        let extra = VALID.replace(
+            "\"timeframe_amount\": 5,",
+            "\"timeframe_amount\": 5, \"limit\": 10,",
+        );
+        let err = parse_batch_params_str(&extra).unwrap_err();
+        match err {
+            BatchError::Entry { index, source } => {
+                assert_eq!(index, 0);
+                assert!(source.to_string().contains("limit"), "{source}");
+            }
+            other => panic!("expected Entry error, got {other}"),
+        }
+    }
+
+    #[test]
+    fn symbols_are_canonicalized() {
+        let lower = VALID.replace("\"AAPL\"", "\" aapl \"");
+        let requests = parse_batch_params_str(&lower).unwrap();
+        assert_eq!(requests[0].symbols, vec!["AAPL"]);
+
+        let bad = VALID.replace("\"AAPL\"", "\"AA PL\"");
+        let err = parse_batch_params_str(&bad).unwrap_err();
+        assert!(matches!(err, BatchError::Symbol { index: 0, .. }), "{err}");
+    }
+
+    #[test]
+    fn provider_invalid_timeframe_rejected_with_index() {
+        let wide = VALID.replace("\"timeframe_amount\": 5", "\"timeframe_amount\": 120");
+        let err = parse_batch_params_str(&wide).unwrap_err();
+        assert!(
+            matches!(err, BatchError::Timeframe { index: 0, .. }),
+            "{err}"
+        );
+    }
+
+    const NDJSON_LINE: &str = r#"{"symbols":["AAPL"],"timeframe_amount":5,"timeframe_unit":"minute","start":"2024-01-01T00:00:00Z","end":"2024-02-01T00:00:00Z"}"#;
+
+    #[test]
+    fn ndjson_lines_stream_in_order() {
+        let input = format!(
+            "{}\n\n{}\n{}\n",
+            NDJSON_LINE,
+            NDJSON_LINE.replace("AAPL", "MSFT"),
+            NDJSON_LINE.replace("AAPL", "NVDA"),
+        );
+        let requests: Vec<_> = ndjson_requests(std::io::Cursor::new(input)).collect();
+        assert_eq!(requests.len(), 3);
+        let symbols: Vec<_> = requests
+            .iter()
+            .map(|r| r.as_ref().unwrap().symbols[0].as_str())
+            .collect();
+        assert_eq!(symbols, vec!["AAPL", "MSFT", "NVDA"]);
+    }
+
+    #[test]
+    fn ndjson_yields_entries_before_the_stream_ends() {
+        // A reader that errors after the first line: only streaming
+        // consumption can produce the first request.
+        struct FailAfter<R>(R, bool);
+        impl<R: std::io::Read> std::io::Read for FailAfter<R> {
+            fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+                match self.0.read(buf) {
+                    Ok(0) if !self.1 => {
+                        self.1 = true;
+                        Err(std::io::Error::other("producer hung up"))
+                    }
+                    other => other,
+                }
+            }
+        }
+        let reader = std::io::BufReader::new(FailAfter(
+            std::io::Cursor::new(format!("{NDJSON_LINE}\n")),
+            false,
+        ));
+        let mut requests = ndjson_requests(reader);
+        let first = requests.next().unwrap().unwrap();
+        assert_eq!(first.symbols, vec!["AAPL"]);
+        assert!(matches!(requests.next(), Some(Err(BatchError::Io(_)))));
+    }
+
+    #[test]
+    fn ndjson_errors_carry_the_line_number() {
+        let input = format!("{NDJSON_LINE}\n{{\"symbols\": []}}\n");
+        let mut requests = ndjson_requests(std::io::Cursor::new(input));
+        assert!(requests.next().unwrap().is_ok());
+        let err = requests.next().unwrap().unwrap_err();
+        assert!(matches!(err, BatchError::Entry { index: 1, .. }), "{err}");
+    }
+
+    #[test]
+    fn schema_is_valid_json() {
+        serde_json::from_str::<serde_json::Value>(BATCH_PARAMS_SCHEMA).unwrap();
+    }
+}
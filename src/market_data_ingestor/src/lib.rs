@@ -0,0 +1,14 @@
+//! Bar models and provider clients for market data ingestion.
+//!
+//! [`models`] holds provider-agnostic types ([`models::bar::Bar`],
+//! [`models::timeframe::TimeFrame`], request parameters), and
+//! [`providers`] the [`providers::DataProvider`] trait plus concrete
+//! clients — currently Alpaca's historical bars API.
+//!
+//! With the `tracing` feature enabled, fetch paths emit spans and
+//! structured fields; no subscriber is ever installed by this crate.
+
+pub mod batch;
+pub mod models;
+pub mod providers;
+pub mod storage;
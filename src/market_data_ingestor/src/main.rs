@@ -0,0 +1,347 @@
+//! Thin CLI over the ingestor library: fetch bars through either backend,
+//! validate batch param files and publish their schema.
+
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand, ValueEnum};
+
+use market_data_ingestor::batch::{BATCH_PARAMS_SCHEMA, parse_batch_params_from_file};
+use market_data_ingestor::models::timeframe::{TimeFrame, TimeFrameUnit};
+use market_data_ingestor::providers::alpaca::{AlpacaConfig, StockBarsParams};
+use market_data_ingestor::providers::python::StockBarData;
+
+#[derive(Debug, Parser)]
+#[command(name = "market-data-ingestor", version, about)]
+struct Cli {
+    /// Implementation that services `single` and `batch`: the in-process
+    /// Rust provider, or the legacy Python fetch script.
+    #[arg(long, value_enum, default_value_t = BackendChoice::Rust)]
+    backend: BackendChoice,
+
+    /// Python interpreter to run the fetch script with (normally the
+    /// venv's `python`). Required with `--backend python`.
+    #[arg(long, value_name = "PATH")]
+    python_interpreter: Option<PathBuf>,
+
+    /// The legacy fetch script. Required with `--backend python`.
+    #[arg(long, value_name = "PATH")]
+    python_script: Option<PathBuf>,
+
+    /// Where the rust backend writes its artifacts.
+    #[arg(long, value_name = "DIR", default_value = "artifacts")]
+    output_dir: PathBuf,
+
+    /// Alpaca key id; read from $APCA_API_KEY_ID when omitted.
+    #[arg(long, value_name = "KEY")]
+    api_key_id: Option<String>,
+
+    /// Alpaca secret key; read from $APCA_API_SECRET_KEY when omitted.
+    #[arg(long, value_name = "KEY")]
+    api_secret_key: Option<String>,
+
+    #[command(subcommand)]
+    command: Command,
+}
+
+/// Shape of a batch params input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum BatchFormat {
+    /// One JSON document with an `entries` array (see print-schema).
+    Json,
+    /// One entry object per line, streamed.
+    Ndjson,
+}
+
+/// Which implementation services the fetch commands.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum BackendChoice {
+    /// In-process REST fetch through `AlpacaProvider`; no Python needed.
+    Rust,
+    /// The legacy alpaca-py subprocess bridge.
+    Python,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Fetch one request and print its artifact path.
+    Single {
+        /// Symbols to fetch, comma separated.
+        #[arg(long, value_delimiter = ',', required = true)]
+        symbols: Vec<String>,
+        /// Bar width, e.g. `5minute` or `1day`.
+        #[arg(long, value_parser = parse_timeframe)]
+        timeframe: TimeFrame,
+        /// Window start (RFC 3339).
+        #[arg(long)]
+        start: chrono::DateTime<chrono::Utc>,
+        /// Window end (RFC 3339, exclusive).
+        #[arg(long)]
+        end: chrono::DateTime<chrono::Utc>,
+    },
+    /// Run every request in a params file, printing one line per entry.
+    Batch {
+        /// Batch request params file, or `-` for stdin.
+        #[arg(value_name = "FILE")]
+        params: PathBuf,
+        /// Input format; `ndjson` fetches each entry as its line
+        /// arrives, so piped producers see results before EOF.
+        #[arg(long, value_enum, default_value_t = BatchFormat::Json)]
+        format: BatchFormat,
+    },
+    /// Parse a params file and report what it asks for, without fetching.
+    Validate {
+        /// Batch request params file (JSON; see print-schema).
+        #[arg(value_name = "FILE")]
+        params: PathBuf,
+    },
+    /// Print the JSON schema for batch params files and exit.
+    PrintSchema,
+}
+
+fn parse_timeframe(s: &str) -> Result<TimeFrame, String> {
+    let split = s
+        .find(|c: char| !c.is_ascii_digit())
+        .filter(|&i| i > 0)
+        .ok_or_else(|| format!("{s:?}: expected <amount><unit>, e.g. 5minute"))?;
+    let (amount, unit) = s.split_at(split);
+    let amount: u32 = amount.parse().map_err(|e| format!("{s:?}: {e}"))?;
+    let unit = match unit.to_ascii_lowercase().as_str() {
+        "minute" => TimeFrameUnit::Minute,
+        "hour" => TimeFrameUnit::Hour,
+        "day" => TimeFrameUnit::Day,
+        "week" => TimeFrameUnit::Week,
+        "month" => TimeFrameUnit::Month,
+        other => {
+            return Err(format!(
+                "unknown unit {other:?}; expected minute|hour|day|week|month"
+            ));
+        }
+    };
+    TimeFrame::new(amount, unit).map_err(|e| e.to_string())
+}
+
+/// Alpaca credentials from flags, falling back to the conventional
+/// `APCA_*` environment variables.
+fn alpaca_config(cli: &Cli) -> anyhow::Result<AlpacaConfig> {
+    let key = match &cli.api_key_id {
+        Some(key) => key.clone(),
+        None => std::env::var("APCA_API_KEY_ID")
+            .map_err(|_| anyhow::anyhow!("pass --api-key-id or set APCA_API_KEY_ID"))?,
+    };
+    let secret = match &cli.api_secret_key {
+        Some(secret) => secret.clone(),
+        None => std::env::var("APCA_API_SECRET_KEY")
+            .map_err(|_| anyhow::anyhow!("pass --api-secret-key or set APCA_API_SECRET_KEY"))?,
+    };
+    Ok(AlpacaConfig::new(key, secret))
+}
+
+/// Build the selected backend, validating its configuration up front so a
+/// misconfigured run fails before any fetching starts.
+fn build_backend(cli: &Cli) -> anyhow::Result<StockBarData> {
+    match cli.backend {
+        BackendChoice::Rust => Ok(StockBarData::new_native(
+            alpaca_config(cli)?,
+            &cli.output_dir,
+        )),
+        #[cfg(feature = "python")]
+        BackendChoice::Python => {
+            let (Some(interpreter), Some(script)) = (&cli.python_interpreter, &cli.python_script)
+            else {
+                anyhow::bail!(
+                    "--backend python needs the venv config: \
+                     pass --python-interpreter and --python-script"
+                );
+            };
+            Ok(StockBarData::new(interpreter, script))
+        }
+        #[cfg(not(feature = "python"))]
+        BackendChoice::Python => anyhow::bail!(
+            "this binary was built without the `python` feature; \
+             rebuild with --features python or use --backend rust"
+        ),
+    }
+}
+
+/// One stdout line per completed request: what was asked, where it went.
+fn report_fetched(request: &StockBarsParams, path: &std::path::Path) {
+    println!(
+        "{} {}: {}",
+        request.symbol_or_symbols.join(","),
+        request.timeframe,
+        path.display()
+    );
+}
+
+fn report_failed(request: &StockBarsParams, error: &dyn std::fmt::Display) {
+    eprintln!(
+        "{} {}: {error}",
+        request.symbol_or_symbols.join(","),
+        request.timeframe
+    );
+}
+
+fn main() -> anyhow::Result<()> {
+    let cli = Cli::parse();
+    match &cli.command {
+        Command::PrintSchema => {
+            print!("{BATCH_PARAMS_SCHEMA}");
+            Ok(())
+        }
+        Command::Validate { params } => {
+            let requests = parse_batch_params_from_file(params)?;
+            for request in &requests {
+                println!(
+                    "{} {} [{} .. {})",
+                    request.symbols.join(","),
+                    request.timeframe,
+                    request.start.to_rfc3339(),
+                    request.end.to_rfc3339()
+                );
+            }
+            println!("{} request(s) OK", requests.len());
+            Ok(())
+        }
+        Command::Single {
+            symbols,
+            timeframe,
+            start,
+            end,
+        } => {
+            let backend = build_backend(&cli)?;
+            let params = StockBarsParams {
+                symbol_or_symbols: symbols.clone(),
+                timeframe: *timeframe,
+                start: *start,
+                end: *end,
+                feed: None,
+                exchange: None,
+                limit: None,
+            };
+            let path = backend.fetch_historical_bars(&params)?;
+            println!("{}", path.display());
+            Ok(())
+        }
+        Command::Batch { params, format } => {
+            let backend = build_backend(&cli)?;
+            match format {
+                BatchFormat::Json => {
+                    let requests = if params == std::path::Path::new("-") {
+                        let mut input = String::new();
+                        std::io::Read::read_to_string(&mut std::io::stdin().lock(), &mut input)?;
+                        market_data_ingestor::batch::parse_batch_params_str(&input)?
+                    } else {
+                        parse_batch_params_from_file(params)?
+                    };
+                    let batch: Vec<StockBarsParams> =
+                        requests.into_iter().map(StockBarsParams::from).collect();
+                    let mut failures = 0usize;
+                    for (request, result) in
+                        batch.iter().zip(backend.fetch_bars_batch_partial(&batch))
+                    {
+                        match result {
+                            Ok(path) => report_fetched(request, &path),
+                            Err(e) => {
+                                failures += 1;
+                                report_failed(request, &e);
+                            }
+                        }
+                    }
+                    if failures > 0 {
+                        anyhow::bail!("{failures} of {} request(s) failed", batch.len());
+                    }
+                }
+                BatchFormat::Ndjson => {
+                    let stdin = std::io::stdin();
+                    let reader: Box<dyn std::io::BufRead> = if params == std::path::Path::new("-") {
+                        Box::new(stdin.lock())
+                    } else {
+                        Box::new(std::io::BufReader::new(std::fs::File::open(params)?))
+                    };
+                    let (mut total, mut failures) = (0usize, 0usize);
+                    for request in market_data_ingestor::batch::ndjson_requests(reader) {
+                        total += 1;
+                        match request {
+                            Err(e) => {
+                                failures += 1;
+                                eprintln!("{e}");
+                            }
+                            Ok(request) => {
+                                let request = StockBarsParams::from(request);
+                                match backend.fetch_historical_bars(&request) {
+                                    Ok(path) => report_fetched(&request, &path),
+                                    Err(e) => {
+                                        failures += 1;
+                                        report_failed(&request, &e);
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    if failures > 0 {
+                        anyhow::bail!("{failures} of {total} request(s) failed");
+                    }
+                }
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Cli {
+        Cli::try_parse_from(args).unwrap()
+    }
+
+    #[test]
+    fn rust_backend_builds_without_a_python_venv() {
+        let cli = parse(&[
+            "market-data-ingestor",
+            "--api-key-id",
+            "k",
+            "--api-secret-key",
+            "s",
+            "single",
+            "--symbols",
+            "AAPL,MSFT",
+            "--timeframe",
+            "5minute",
+            "--start",
+            "2024-01-01T00:00:00Z",
+            "--end",
+            "2024-02-01T00:00:00Z",
+        ]);
+        assert_eq!(cli.backend, BackendChoice::Rust);
+        build_backend(&cli).expect("rust backend needs no venv config");
+    }
+
+    #[test]
+    fn python_backend_without_venv_config_errors_clearly() {
+        let cli = parse(&[
+            "market-data-ingestor",
+            "--backend",
+            "python",
+            "print-schema",
+        ]);
+        let err = match build_backend(&cli) {
+            Err(e) => e.to_string(),
+            Ok(_) => panic!("python backend built without venv config"),
+        };
+        #[cfg(feature = "python")]
+        assert!(err.contains("--python-interpreter"), "{err}");
+        #[cfg(not(feature = "python"))]
+        assert!(err.contains("python` feature"), "{err}");
+    }
+
+    #[test]
+    fn timeframes_parse_compact_and_reject_shorthand() {
+        assert_eq!(parse_timeframe("5minute").unwrap().to_string(), "5Min");
+        assert_eq!(parse_timeframe("1Day").unwrap().to_string(), "1Day");
+        let err = parse_timeframe("5m").unwrap_err();
+        assert!(err.contains("unknown unit"), "{err}");
+        assert!(parse_timeframe("minute").is_err());
+    }
+}
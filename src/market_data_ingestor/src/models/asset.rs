@@ -0,0 +1,18 @@
+//! Provider-agnostic asset metadata.
+
+use serde::{Deserialize, Serialize};
+
+/// What a provider knows about one listed asset, independent of bars:
+/// enough to confirm a symbol exists — and is worth a manifest — before
+/// any fetching starts.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AssetInfo {
+    pub symbol: String,
+    pub name: String,
+    pub exchange: String,
+    /// Whether the provider will accept orders for it; data-only listings
+    /// report `false`.
+    pub tradable: bool,
+    /// Provider-reported listing status, e.g. `"active"` or `"inactive"`.
+    pub status: String,
+}
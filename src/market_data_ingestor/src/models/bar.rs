@@ -0,0 +1,221 @@
+//! OHLCV bar types shared by every provider.
+
+use chrono::{DateTime, NaiveDateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::models::timeframe::TimeFrame;
+
+/// A single OHLCV bar. Field names follow Alpaca's wire format so bars
+/// deserialize straight out of the API response.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Bar {
+    #[serde(rename = "t", deserialize_with = "deserialize_utc")]
+    pub timestamp: DateTime<Utc>,
+    #[serde(rename = "o")]
+    pub open: f64,
+    #[serde(rename = "h")]
+    pub high: f64,
+    #[serde(rename = "l")]
+    pub low: f64,
+    #[serde(rename = "c")]
+    pub close: f64,
+    #[serde(rename = "v")]
+    pub volume: f64,
+    #[serde(rename = "n", default)]
+    pub trade_count: Option<u64>,
+    #[serde(rename = "vw", default)]
+    pub vwap: Option<f64>,
+}
+
+/// Default tolerance for [`Bar::approx_eq`]: generous against last-ULP
+/// drift between float parsers (pandas vs Rust), far below one cent.
+pub const PRICE_TOLERANCE: f64 = 1e-9;
+
+impl Bar {
+    /// True when `other` describes the same bar up to float noise:
+    /// timestamps and trade counts match exactly, prices/volume/vwap
+    /// within `tolerance`. Exact `==` on f64 fields is fragile across
+    /// serialization round trips that differ in the last ULP.
+    pub fn approx_eq(&self, other: &Bar, tolerance: f64) -> bool {
+        fn near(a: f64, b: f64, tolerance: f64) -> bool {
+            (a - b).abs() <= tolerance
+        }
+        self.timestamp == other.timestamp
+            && self.trade_count == other.trade_count
+            && near(self.open, other.open, tolerance)
+            && near(self.high, other.high, tolerance)
+            && near(self.low, other.low, tolerance)
+            && near(self.close, other.close, tolerance)
+            && near(self.volume, other.volume, tolerance)
+            && match (self.vwap, other.vwap) {
+                (None, None) => true,
+                (Some(a), Some(b)) => near(a, b, tolerance),
+                _ => false,
+            }
+    }
+}
+
+/// All bars fetched for one symbol at one timeframe, in ascending
+/// timestamp order.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BarSeries {
+    pub symbol: String,
+    pub timeframe: TimeFrame,
+    pub bars: Vec<Bar>,
+    /// Which upstream feed actually served the data (e.g. `"sip"` or
+    /// `"iex"` on Alpaca equities) when it may differ from what was
+    /// requested; `None` for providers without a feed concept.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_feed: Option<String>,
+}
+
+impl BarSeries {
+    /// Fold another fetch of the same symbol/timeframe into this series:
+    /// bars are concatenated, re-sorted, and near-identical duplicates at
+    /// window seams dropped ([`PRICE_TOLERANCE`]). A merge across feeds
+    /// clears `source_feed`, since the result no longer came from one.
+    pub fn merge(&mut self, other: BarSeries) {
+        if self.source_feed != other.source_feed {
+            self.source_feed = None;
+        }
+        self.bars.extend(other.bars);
+        self.bars.sort_by_key(|bar| bar.timestamp);
+        self.bars.dedup_by(|a, b| a.approx_eq(b, PRICE_TOLERANCE));
+    }
+
+    /// [`Bar::approx_eq`] lifted to whole series: symbol, timeframe and
+    /// bar count must match, each bar within `tolerance`. `source_feed`
+    /// is provenance, not data, and is ignored.
+    pub fn approx_eq(&self, other: &BarSeries, tolerance: f64) -> bool {
+        self.symbol == other.symbol
+            && self.timeframe == other.timeframe
+            && self.bars.len() == other.bars.len()
+            && self
+                .bars
+                .iter()
+                .zip(&other.bars)
+                .all(|(a, b)| a.approx_eq(b, tolerance))
+    }
+}
+
+/// Normalize a wire timestamp to UTC. Artifacts written from pandas may
+/// carry naive timestamps (no offset); those are taken as UTC, matching
+/// the assumption the comparison tooling already makes. Offset-bearing
+/// RFC 3339 converts; anything else is an error rather than a silently
+/// shifted clock.
+pub fn parse_utc_timestamp(s: &str) -> Result<DateTime<Utc>, String> {
+    if let Ok(dt) = DateTime::parse_from_rfc3339(s) {
+        return Ok(dt.with_timezone(&Utc));
+    }
+    if let Ok(naive) = NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S%.f") {
+        return Ok(naive.and_utc());
+    }
+    Err(format!(
+        "unrecognized timestamp {s:?}: expected RFC 3339 or a naive UTC datetime"
+    ))
+}
+
+fn deserialize_utc<'de, D>(deserializer: D) -> Result<DateTime<Utc>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    parse_utc_timestamp(&s).map_err(serde::de::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn naive_timestamps_are_taken_as_utc() {
+        let bar: Bar = serde_json::from_str(
+            r#"{"t":"2024-01-02T14:30:00","o":1.0,"h":2.0,"l":0.5,"c":1.5,"v":10.0}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            bar.timestamp,
+            "2024-01-02T14:30:00Z".parse::<DateTime<Utc>>().unwrap()
+        );
+    }
+
+    #[test]
+    fn offset_timestamps_convert_to_utc() {
+        assert_eq!(
+            parse_utc_timestamp("2024-01-02T09:30:00-05:00").unwrap(),
+            "2024-01-02T14:30:00Z".parse::<DateTime<Utc>>().unwrap()
+        );
+        // Fractional seconds survive on both paths.
+        assert_eq!(
+            parse_utc_timestamp("2024-01-02T14:30:00.250").unwrap(),
+            parse_utc_timestamp("2024-01-02T14:30:00.250Z").unwrap()
+        );
+    }
+
+    fn sample_bar() -> Bar {
+        Bar {
+            timestamp: "2024-01-02T14:30:00Z".parse().unwrap(),
+            open: 1.0,
+            high: 2.0,
+            low: 0.5,
+            close: 1.5,
+            volume: 10.0,
+            trade_count: Some(3),
+            vwap: Some(1.2),
+        }
+    }
+
+    #[test]
+    fn approx_eq_is_inclusive_at_the_tolerance_boundary() {
+        // Anchor at 0.0 so the offsets are exactly representable; offsets
+        // added to a price like 1.5 round and land off the boundary.
+        let mut a = sample_bar();
+        a.close = 0.0;
+        let mut b = a.clone();
+        b.close = PRICE_TOLERANCE;
+        assert!(a.approx_eq(&b, PRICE_TOLERANCE));
+        b.close = 2.0 * PRICE_TOLERANCE;
+        assert!(!a.approx_eq(&b, PRICE_TOLERANCE));
+    }
+
+    #[test]
+    fn approx_eq_keeps_timestamps_and_counts_exact() {
+        let a = sample_bar();
+        let mut shifted = a.clone();
+        shifted.timestamp += chrono::Duration::nanoseconds(1);
+        assert!(!a.approx_eq(&shifted, f64::INFINITY));
+
+        let mut counted = a.clone();
+        counted.trade_count = Some(4);
+        assert!(!a.approx_eq(&counted, f64::INFINITY));
+
+        let mut unvwapped = a.clone();
+        unvwapped.vwap = None;
+        assert!(!a.approx_eq(&unvwapped, f64::INFINITY));
+    }
+
+    #[test]
+    fn series_approx_eq_ignores_source_feed_but_not_length() {
+        let series = BarSeries {
+            symbol: "AAPL".to_string(),
+            timeframe: crate::models::timeframe::TimeFrame::new(
+                1,
+                crate::models::timeframe::TimeFrameUnit::Day,
+            )
+            .unwrap(),
+            bars: vec![sample_bar()],
+            source_feed: Some("sip".to_string()),
+        };
+        let mut other = series.clone();
+        other.source_feed = Some("iex".to_string());
+        assert!(series.approx_eq(&other, PRICE_TOLERANCE));
+        other.bars.push(sample_bar());
+        assert!(!series.approx_eq(&other, PRICE_TOLERANCE));
+    }
+
+    #[test]
+    fn unrecognized_timestamps_error() {
+        let err = parse_utc_timestamp("02/01/2024 14:30").unwrap_err();
+        assert!(err.contains("unrecognized timestamp"), "{err}");
+    }
+}
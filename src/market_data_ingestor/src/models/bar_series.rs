@@ -0,0 +1,564 @@
+//! Whole-series operations on [`BarSeries`]: resampling to coarser
+//! timeframes without refetching.
+
+use thiserror::Error;
+
+use crate::models::bar::{Bar, BarSeries};
+use crate::models::timeframe::{TimeFrame, TimeFrameUnit};
+
+/// A half-open `[start, end)` UTC window.
+pub type UtcRange = (chrono::DateTime<chrono::Utc>, chrono::DateTime<chrono::Utc>);
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum ResampleError {
+    #[error("target timeframe {target} is finer than the source {from}")]
+    TargetFinerThanSource { from: String, target: String },
+    #[error("{0} timeframes have no fixed duration and cannot be resample targets")]
+    IrregularTarget(&'static str),
+    #[error("source bars must not cross a {0}-minute boundary")]
+    MisalignedSource(i64),
+}
+
+/// Fixed duration of a timeframe in minutes; `None` for week/month, whose
+/// buckets are not all the same length.
+fn fixed_minutes(tf: TimeFrame) -> Option<i64> {
+    let per_unit = match tf.unit() {
+        TimeFrameUnit::Minute => 1,
+        TimeFrameUnit::Hour => 60,
+        TimeFrameUnit::Day => 1440,
+        TimeFrameUnit::Week | TimeFrameUnit::Month => return None,
+    };
+    Some(per_unit * i64::from(tf.amount()))
+}
+
+fn unit_name(tf: TimeFrame) -> &'static str {
+    match tf.unit() {
+        TimeFrameUnit::Minute => "minute",
+        TimeFrameUnit::Hour => "hour",
+        TimeFrameUnit::Day => "day",
+        TimeFrameUnit::Week => "week",
+        TimeFrameUnit::Month => "month",
+    }
+}
+
+/// Bucket index of a timestamp at `minutes` per bucket, from the epoch.
+fn bucket_id(ts: chrono::DateTime<chrono::Utc>, minutes: i64) -> i64 {
+    ts.timestamp().div_euclid(60 * minutes)
+}
+
+/// Aggregate `series` into coarser `target` buckets: open = first,
+/// high = max, low = min, close = last, volume and trade_count = sum,
+/// vwap = volume-weighted mean (absent if no input bar carried one).
+///
+/// Bars are grouped by epoch-aligned `target` buckets, so the source must
+/// be at least as fine as the target and the target must have a fixed
+/// duration (no week/month).
+pub fn resample(series: &BarSeries, target: TimeFrame) -> Result<BarSeries, ResampleError> {
+    let Some(target_minutes) = fixed_minutes(target) else {
+        return Err(ResampleError::IrregularTarget(unit_name(target)));
+    };
+    let source_minutes = fixed_minutes(series.timeframe)
+        .ok_or(ResampleError::IrregularTarget(unit_name(series.timeframe)))?;
+    if source_minutes > target_minutes {
+        return Err(ResampleError::TargetFinerThanSource {
+            from: series.timeframe.to_string(),
+            target: target.to_string(),
+        });
+    }
+
+    let mut out: Vec<Bar> = Vec::new();
+    let mut current: Option<(i64, Bar, f64)> = None; // (bucket, acc, vwap_notional)
+    for bar in &series.bars {
+        let id = bucket_id(bar.timestamp, target_minutes);
+        // A source bar spilling past its bucket end would be split between
+        // two output bars; reject instead of silently misattributing.
+        if bucket_id(
+            bar.timestamp + chrono::Duration::minutes(source_minutes - 1),
+            target_minutes,
+        ) != id
+        {
+            return Err(ResampleError::MisalignedSource(target_minutes));
+        }
+        match &mut current {
+            Some((bucket, acc, notional)) if *bucket == id => {
+                acc.high = acc.high.max(bar.high);
+                acc.low = acc.low.min(bar.low);
+                acc.close = bar.close;
+                acc.volume += bar.volume;
+                acc.trade_count = match (acc.trade_count, bar.trade_count) {
+                    (Some(a), Some(b)) => Some(a + b),
+                    (a, b) => a.or(b),
+                };
+                if let Some(vw) = bar.vwap {
+                    *notional += vw * bar.volume;
+                }
+            }
+            _ => {
+                if let Some((_, acc, notional)) = current.take() {
+                    out.push(finish_bar(acc, notional));
+                }
+                let notional = bar.vwap.map_or(0.0, |vw| vw * bar.volume);
+                let mut acc = bar.clone();
+                acc.timestamp = chrono::DateTime::from_timestamp(id * 60 * target_minutes, 0)
+                    .expect("bucket start is a valid timestamp");
+                current = Some((id, acc, notional));
+            }
+        }
+    }
+    if let Some((_, acc, notional)) = current {
+        out.push(finish_bar(acc, notional));
+    }
+
+    Ok(BarSeries {
+        symbol: series.symbol.clone(),
+        timeframe: target,
+        bars: out,
+        source_feed: series.source_feed.clone(),
+    })
+}
+
+/// One defect found by [`validate`], locating the offending bar by index.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BarIssue {
+    /// Timestamp is not strictly after the previous bar's.
+    NonMonotonicTimestamp { index: usize },
+    /// An OHLC field is NaN or infinite.
+    NonFinitePrice { index: usize, field: &'static str },
+    /// `low <= open, close <= high` does not hold.
+    InconsistentOhlc { index: usize },
+    /// Volume is negative or non-finite.
+    BadVolume { index: usize },
+}
+
+/// Sanity-check a fetched series before it is merged into coverage:
+/// strictly ascending timestamps, finite OHLC with
+/// `low <= open, close <= high`, and non-negative volume. Returns every
+/// issue found so a worker can log them all and fail the gap once.
+pub fn validate(series: &BarSeries) -> Result<(), Vec<BarIssue>> {
+    let mut issues = Vec::new();
+    let mut prev_ts = None;
+    for (index, bar) in series.bars.iter().enumerate() {
+        if prev_ts.is_some_and(|prev| bar.timestamp <= prev) {
+            issues.push(BarIssue::NonMonotonicTimestamp { index });
+        }
+        prev_ts = Some(bar.timestamp);
+
+        let mut finite = true;
+        for (field, value) in [
+            ("open", bar.open),
+            ("high", bar.high),
+            ("low", bar.low),
+            ("close", bar.close),
+        ] {
+            if !value.is_finite() {
+                issues.push(BarIssue::NonFinitePrice { index, field });
+                finite = false;
+            }
+        }
+        if finite
+            && !(bar.low <= bar.open
+                && bar.low <= bar.close
+                && bar.open <= bar.high
+                && bar.close <= bar.high
+                && bar.low <= bar.high)
+        {
+            issues.push(BarIssue::InconsistentOhlc { index });
+        }
+        if !bar.volume.is_finite() || bar.volume < 0.0 {
+            issues.push(BarIssue::BadVolume { index });
+        }
+    }
+    if issues.is_empty() {
+        Ok(())
+    } else {
+        Err(issues)
+    }
+}
+
+/// Find holes in a fetched series without involving a manifest or the DB:
+/// the in-memory twin of the coverage layer's `compute_missing`.
+///
+/// Buckets whose start lies in `[window.0, window.1)` but that have no bar
+/// in `series` are coalesced into half-open UTC ranges. `tf` must have a
+/// fixed duration (no week/month).
+pub fn missing_buckets(
+    series: &BarSeries,
+    tf: TimeFrame,
+    window: UtcRange,
+) -> Result<Vec<UtcRange>, ResampleError> {
+    let Some(minutes) = fixed_minutes(tf) else {
+        return Err(ResampleError::IrregularTarget(unit_name(tf)));
+    };
+    let (start, end) = window;
+    if start >= end {
+        return Ok(Vec::new());
+    }
+    let width = 60 * minutes;
+    let ceil_div = |a: i64| a.div_euclid(width) + i64::from(a.rem_euclid(width) != 0);
+    let first = ceil_div(start.timestamp());
+    let end_ex = ceil_div(end.timestamp());
+
+    let covered: std::collections::BTreeSet<i64> = series
+        .bars
+        .iter()
+        .map(|bar| bucket_id(bar.timestamp, minutes))
+        .collect();
+
+    let mut ranges = Vec::new();
+    let mut run: Option<(i64, i64)> = None;
+    for id in first..end_ex {
+        if covered.contains(&id) {
+            if let Some(r) = run.take() {
+                ranges.push(r);
+            }
+        } else {
+            match &mut run {
+                Some((_, run_end)) => *run_end = id + 1,
+                None => run = Some((id, id + 1)),
+            }
+        }
+    }
+    if let Some(r) = run {
+        ranges.push(r);
+    }
+
+    Ok(ranges
+        .into_iter()
+        .map(|(lo, hi)| {
+            (
+                chrono::DateTime::from_timestamp(lo * width, 0).expect("bucket in chrono range"),
+                chrono::DateTime::from_timestamp(hi * width, 0).expect("bucket in chrono range"),
+            )
+        })
+        .collect())
+}
+
+fn finish_bar(mut bar: Bar, vwap_notional: f64) -> Bar {
+    bar.vwap = (vwap_notional > 0.0 && bar.volume > 0.0).then(|| vwap_notional / bar.volume);
+    bar
+}
+
+/// Arrow IPC ingestion (feature `delta`, which supplies the arrow crates):
+/// decode record batches in the crate's flat bar layout straight into
+/// [`BarSeries`], with no DataFrame or row-map intermediate.
+#[cfg(feature = "delta")]
+pub mod ipc {
+    use std::collections::{BTreeSet, HashMap};
+
+    use deltalake::arrow::array::{
+        Array, Float64Array, Int64Array, StringArray, TimestampMicrosecondArray,
+    };
+    use deltalake::arrow::ipc::reader::StreamReader;
+    use deltalake::arrow::record_batch::RecordBatch;
+    use thiserror::Error;
+
+    use crate::models::bar::{Bar, BarSeries};
+    use crate::models::timeframe::TimeFrame;
+
+    #[derive(Debug, Error)]
+    pub enum IpcError {
+        #[error("arrow error: {0}")]
+        Arrow(#[from] deltalake::arrow::error::ArrowError),
+        #[error("IPC batch is missing column {column}")]
+        MissingColumn { column: &'static str },
+    }
+
+    /// Decode an Arrow IPC stream into one [`BarSeries`] per symbol, in
+    /// first-seen order with row order preserved.
+    ///
+    /// Batches use the flat `(symbol, t, o, h, l, c, v, n, vw)` layout the
+    /// delta storage writes; `feed`, `n` and `vw` may be null or absent,
+    /// and extra columns (`timeframe`, `date`) are ignored — the caller
+    /// says what timeframe the stream holds. As with the delta reader, a
+    /// series is feed-tagged only when every one of its rows agrees.
+    pub fn from_ipc_bytes(bytes: &[u8], timeframe: TimeFrame) -> Result<Vec<BarSeries>, IpcError> {
+        let reader = StreamReader::try_new(std::io::Cursor::new(bytes), None)?;
+        let mut order: Vec<String> = Vec::new();
+        let mut bars_by_symbol: HashMap<String, Vec<Bar>> = HashMap::new();
+        let mut feeds: HashMap<String, BTreeSet<String>> = HashMap::new();
+        for batch in reader {
+            collect_ipc_batch(&batch?, &mut order, &mut bars_by_symbol, &mut feeds)?;
+        }
+        Ok(order
+            .into_iter()
+            .map(|symbol| {
+                let bars = bars_by_symbol.remove(&symbol).unwrap_or_default();
+                let source_feed = feeds
+                    .remove(&symbol)
+                    .filter(|set| set.len() == 1)
+                    .and_then(|set| set.into_iter().next());
+                BarSeries {
+                    symbol,
+                    timeframe,
+                    bars,
+                    source_feed,
+                }
+            })
+            .collect())
+    }
+
+    fn collect_ipc_batch(
+        batch: &RecordBatch,
+        order: &mut Vec<String>,
+        bars_by_symbol: &mut HashMap<String, Vec<Bar>>,
+        feeds: &mut HashMap<String, BTreeSet<String>>,
+    ) -> Result<(), IpcError> {
+        fn col<'a, T: 'static>(
+            batch: &'a RecordBatch,
+            name: &'static str,
+        ) -> Result<&'a T, IpcError> {
+            batch
+                .column_by_name(name)
+                .and_then(|a| a.as_any().downcast_ref::<T>())
+                .ok_or(IpcError::MissingColumn { column: name })
+        }
+
+        let symbols: &StringArray = col(batch, "symbol")?;
+        let feed_col: Option<&StringArray> = batch
+            .column_by_name("feed")
+            .and_then(|a| a.as_any().downcast_ref());
+        let ts: &TimestampMicrosecondArray = col(batch, "t")?;
+        let o: &Float64Array = col(batch, "o")?;
+        let h: &Float64Array = col(batch, "h")?;
+        let l: &Float64Array = col(batch, "l")?;
+        let c: &Float64Array = col(batch, "c")?;
+        let v: &Float64Array = col(batch, "v")?;
+        let n: Option<&Int64Array> = batch
+            .column_by_name("n")
+            .and_then(|a| a.as_any().downcast_ref());
+        let vw: Option<&Float64Array> = batch
+            .column_by_name("vw")
+            .and_then(|a| a.as_any().downcast_ref());
+
+        for row in 0..batch.num_rows() {
+            let symbol = symbols.value(row);
+            let bars = match bars_by_symbol.get_mut(symbol) {
+                Some(bars) => bars,
+                None => {
+                    order.push(symbol.to_string());
+                    bars_by_symbol.entry(symbol.to_string()).or_default()
+                }
+            };
+            if let Some(feed) = feed_col.filter(|f| !f.is_null(row)) {
+                feeds
+                    .entry(symbol.to_string())
+                    .or_default()
+                    .insert(feed.value(row).to_string());
+            }
+            bars.push(Bar {
+                timestamp: chrono::DateTime::from_timestamp_micros(ts.value(row))
+                    .expect("IPC timestamp within chrono range"),
+                open: o.value(row),
+                high: h.value(row),
+                low: l.value(row),
+                close: c.value(row),
+                volume: v.value(row),
+                trade_count: n.filter(|a| !a.is_null(row)).map(|a| a.value(row) as u64),
+                vwap: vw.filter(|a| !a.is_null(row)).map(|a| a.value(row)),
+            });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn minute_bar(minute: u32, open: f64, high: f64, low: f64, close: f64, volume: f64) -> Bar {
+        Bar {
+            timestamp: format!("2024-01-02T14:{minute:02}:00Z").parse().unwrap(),
+            open,
+            high,
+            low,
+            close,
+            volume,
+            trade_count: Some(10),
+            vwap: Some((high + low) / 2.0),
+        }
+    }
+
+    fn one_minute_series(bars: Vec<Bar>) -> BarSeries {
+        BarSeries {
+            symbol: "AAPL".to_string(),
+            timeframe: TimeFrame::new(1, TimeFrameUnit::Minute).unwrap(),
+            bars,
+            source_feed: None,
+        }
+    }
+
+    #[test]
+    fn one_minute_to_five_minute_aggregates_ohlcv() {
+        let series = one_minute_series(vec![
+            minute_bar(30, 10.0, 11.0, 9.5, 10.5, 100.0),
+            minute_bar(31, 10.5, 12.0, 10.4, 11.8, 200.0),
+            minute_bar(34, 11.8, 11.9, 10.0, 10.2, 50.0),
+            // Next 5-minute bucket.
+            minute_bar(35, 10.2, 10.4, 10.1, 10.3, 70.0),
+        ]);
+        let target = TimeFrame::new(5, TimeFrameUnit::Minute).unwrap();
+        let out = resample(&series, target).unwrap();
+
+        assert_eq!(out.timeframe, target);
+        assert_eq!(out.bars.len(), 2);
+        let first = &out.bars[0];
+        assert_eq!(
+            first.timestamp,
+            "2024-01-02T14:30:00Z"
+                .parse::<chrono::DateTime<chrono::Utc>>()
+                .unwrap()
+        );
+        assert_eq!(first.open, 10.0);
+        assert_eq!(first.high, 12.0);
+        assert_eq!(first.low, 9.5);
+        assert_eq!(first.close, 10.2);
+        assert_eq!(first.volume, 350.0);
+        assert_eq!(first.trade_count, Some(30));
+        // Volume-weighted: (10.25*100 + 11.2*200 + 10.95*50) / 350.
+        let expected_vwap = (10.25 * 100.0 + 11.2 * 200.0 + 10.95 * 50.0) / 350.0;
+        assert!((first.vwap.unwrap() - expected_vwap).abs() < 1e-9);
+        assert_eq!(out.bars[1].volume, 70.0);
+    }
+
+    #[test]
+    fn resample_carries_the_source_feed() {
+        let mut series = one_minute_series(vec![minute_bar(30, 10.0, 11.0, 9.5, 10.5, 100.0)]);
+        series.source_feed = Some("iex".to_string());
+        let out = resample(&series, TimeFrame::new(5, TimeFrameUnit::Minute).unwrap()).unwrap();
+        // The derived bars came from the same upstream feed.
+        assert_eq!(out.source_feed.as_deref(), Some("iex"));
+    }
+
+    #[test]
+    fn finer_target_rejected() {
+        let series = BarSeries {
+            symbol: "AAPL".to_string(),
+            timeframe: TimeFrame::new(1, TimeFrameUnit::Hour).unwrap(),
+            bars: Vec::new(),
+            source_feed: None,
+        };
+        let err = resample(&series, TimeFrame::new(5, TimeFrameUnit::Minute).unwrap());
+        assert!(
+            matches!(err, Err(ResampleError::TargetFinerThanSource { .. })),
+            "{err:?}"
+        );
+    }
+
+    #[test]
+    fn missing_buckets_coalesces_holes() {
+        // Bars at 14:30 and 14:33; window 14:30..14:35 at 1-minute buckets.
+        let series = one_minute_series(vec![
+            minute_bar(30, 10.0, 11.0, 9.5, 10.5, 100.0),
+            minute_bar(33, 10.5, 10.6, 10.4, 10.5, 80.0),
+        ]);
+        let tf = TimeFrame::new(1, TimeFrameUnit::Minute).unwrap();
+        let window = (
+            "2024-01-02T14:30:00Z".parse().unwrap(),
+            "2024-01-02T14:35:00Z".parse().unwrap(),
+        );
+        let missing = missing_buckets(&series, tf, window).unwrap();
+        let expect = |s: &str, e: &str| {
+            (
+                s.parse::<chrono::DateTime<chrono::Utc>>().unwrap(),
+                e.parse::<chrono::DateTime<chrono::Utc>>().unwrap(),
+            )
+        };
+        assert_eq!(
+            missing,
+            vec![
+                expect("2024-01-02T14:31:00Z", "2024-01-02T14:33:00Z"),
+                expect("2024-01-02T14:34:00Z", "2024-01-02T14:35:00Z"),
+            ]
+        );
+
+        // Empty window yields nothing.
+        assert!(
+            missing_buckets(&series, tf, (window.1, window.0))
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn irregular_target_rejected() {
+        let series = one_minute_series(Vec::new());
+        let err = resample(&series, TimeFrame::new(1, TimeFrameUnit::Month).unwrap());
+        assert_eq!(err, Err(ResampleError::IrregularTarget("month")));
+    }
+
+    #[test]
+    fn validate_accepts_clean_series() {
+        let series = one_minute_series(vec![
+            minute_bar(30, 10.0, 11.0, 9.5, 10.5, 100.0),
+            minute_bar(31, 10.5, 10.6, 10.4, 10.5, 80.0),
+        ]);
+        assert_eq!(validate(&series), Ok(()));
+    }
+
+    #[test]
+    fn validate_flags_inverted_high_low_and_nan_close() {
+        // Bar 0 has high < low; bar 1 has a NaN close and repeats bar 0's
+        // timestamp.
+        let series = one_minute_series(vec![
+            minute_bar(30, 10.0, 9.0, 11.0, 10.0, 100.0),
+            minute_bar(30, 10.0, 10.5, 9.5, f64::NAN, 80.0),
+        ]);
+        let issues = validate(&series).unwrap_err();
+        assert_eq!(
+            issues,
+            vec![
+                BarIssue::InconsistentOhlc { index: 0 },
+                BarIssue::NonMonotonicTimestamp { index: 1 },
+                BarIssue::NonFinitePrice {
+                    index: 1,
+                    field: "close",
+                },
+            ]
+        );
+    }
+
+    #[cfg(feature = "delta")]
+    #[test]
+    fn ipc_bytes_round_trip_into_series() {
+        use deltalake::arrow::ipc::writer::StreamWriter;
+
+        // Two symbols interleaved across two batches, exactly as the delta
+        // writer would emit them.
+        let series = vec![
+            BarSeries {
+                symbol: "AAPL".to_string(),
+                source_feed: Some("iex".to_string()),
+                ..one_minute_series(vec![
+                    minute_bar(30, 10.0, 11.0, 9.5, 10.5, 100.0),
+                    minute_bar(31, 10.5, 10.6, 10.4, 10.5, 80.0),
+                ])
+            },
+            BarSeries {
+                symbol: "MSFT".to_string(),
+                ..one_minute_series(vec![minute_bar(30, 20.0, 21.0, 19.5, 20.5, 50.0)])
+            },
+        ];
+
+        let mut bytes = Vec::new();
+        let first = crate::storage::delta::series_to_batch(&series[0]);
+        let mut writer = StreamWriter::try_new(&mut bytes, &first.schema()).unwrap();
+        writer.write(&first).unwrap();
+        writer
+            .write(&crate::storage::delta::series_to_batch(&series[1]))
+            .unwrap();
+        writer.finish().unwrap();
+
+        let tf = TimeFrame::new(1, TimeFrameUnit::Minute).unwrap();
+        let out = ipc::from_ipc_bytes(&bytes, tf).unwrap();
+        assert_eq!(out, series);
+    }
+
+    #[test]
+    fn validate_flags_negative_volume() {
+        let series = one_minute_series(vec![minute_bar(30, 10.0, 11.0, 9.5, 10.5, -1.0)]);
+        assert_eq!(
+            validate(&series),
+            Err(vec![BarIssue::BadVolume { index: 0 }])
+        );
+    }
+}
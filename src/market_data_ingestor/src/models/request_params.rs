@@ -0,0 +1,168 @@
+//! Provider-agnostic description of a bars fetch.
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::models::symbol::{self, SymbolError};
+use crate::models::timeframe::TimeFrame;
+
+/// What to fetch: which symbols, at which timeframe, over which half-open
+/// UTC window. Providers translate this into their own request shapes.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct BarsRequestParams {
+    pub symbols: Vec<String>,
+    pub timeframe: TimeFrame,
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+}
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum RequestParamsError {
+    #[error("request is missing {0}")]
+    Missing(&'static str),
+    #[error("no symbols requested")]
+    NoSymbols,
+    #[error(transparent)]
+    Symbol(#[from] SymbolError),
+    #[error("start must precede end")]
+    InvertedWindow,
+}
+
+impl BarsRequestParams {
+    /// Start building a request. [`BarsRequestParamsBuilder::build`]
+    /// validates, so a request assembled this way cannot be empty,
+    /// inverted or carry a malformed symbol.
+    pub fn builder() -> BarsRequestParamsBuilder {
+        BarsRequestParamsBuilder::default()
+    }
+}
+
+/// Assembles a [`BarsRequestParams`] and validates it on `build`:
+/// symbols are canonicalized ([`symbol::canonicalize`]), the list must be
+/// non-empty and the window must run forward. Providers still apply
+/// their own checks (history floor, data delay) at fetch time.
+#[derive(Debug, Default)]
+pub struct BarsRequestParamsBuilder {
+    symbols: Vec<String>,
+    timeframe: Option<TimeFrame>,
+    start: Option<DateTime<Utc>>,
+    end: Option<DateTime<Utc>>,
+}
+
+impl BarsRequestParamsBuilder {
+    /// Add one symbol; call repeatedly or combine with
+    /// [`BarsRequestParamsBuilder::symbols`].
+    pub fn symbol(mut self, symbol: impl Into<String>) -> Self {
+        self.symbols.push(symbol.into());
+        self
+    }
+
+    pub fn symbols<I, S>(mut self, symbols: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.symbols.extend(symbols.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn timeframe(mut self, timeframe: TimeFrame) -> Self {
+        self.timeframe = Some(timeframe);
+        self
+    }
+
+    pub fn start(mut self, start: DateTime<Utc>) -> Self {
+        self.start = Some(start);
+        self
+    }
+
+    pub fn end(mut self, end: DateTime<Utc>) -> Self {
+        self.end = Some(end);
+        self
+    }
+
+    pub fn build(self) -> Result<BarsRequestParams, RequestParamsError> {
+        let timeframe = self
+            .timeframe
+            .ok_or(RequestParamsError::Missing("timeframe"))?;
+        let start = self.start.ok_or(RequestParamsError::Missing("start"))?;
+        let end = self.end.ok_or(RequestParamsError::Missing("end"))?;
+        if self.symbols.is_empty() {
+            return Err(RequestParamsError::NoSymbols);
+        }
+        let symbols = self
+            .symbols
+            .iter()
+            .map(|s| symbol::canonicalize(s))
+            .collect::<Result<Vec<_>, _>>()?;
+        if start >= end {
+            return Err(RequestParamsError::InvertedWindow);
+        }
+        Ok(BarsRequestParams {
+            symbols,
+            timeframe,
+            start,
+            end,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::timeframe::TimeFrameUnit;
+
+    #[test]
+    fn builder_canonicalizes_symbols_and_validates_the_window() {
+        let params = BarsRequestParams::builder()
+            .symbol("aapl")
+            .symbols(["msft", "btc/usd"])
+            .timeframe(TimeFrame::new(1, TimeFrameUnit::Day).unwrap())
+            .start("2024-01-01T00:00:00Z".parse().unwrap())
+            .end("2024-02-01T00:00:00Z".parse().unwrap())
+            .build()
+            .unwrap();
+        assert_eq!(params.symbols, ["AAPL", "MSFT", "BTC/USD"]);
+        assert_eq!(params.timeframe.to_string(), "1Day");
+    }
+
+    #[test]
+    fn builder_rejects_incomplete_or_inverted_requests() {
+        let base = || {
+            BarsRequestParams::builder()
+                .symbol("AAPL")
+                .timeframe(TimeFrame::new(1, TimeFrameUnit::Day).unwrap())
+                .start("2024-02-01T00:00:00Z".parse().unwrap())
+        };
+
+        assert_eq!(
+            base().build().unwrap_err(),
+            RequestParamsError::Missing("end")
+        );
+        assert_eq!(
+            base()
+                .end("2024-01-01T00:00:00Z".parse().unwrap())
+                .build()
+                .unwrap_err(),
+            RequestParamsError::InvertedWindow
+        );
+        assert_eq!(
+            BarsRequestParams::builder()
+                .timeframe(TimeFrame::new(1, TimeFrameUnit::Day).unwrap())
+                .start("2024-01-01T00:00:00Z".parse().unwrap())
+                .end("2024-02-01T00:00:00Z".parse().unwrap())
+                .build()
+                .unwrap_err(),
+            RequestParamsError::NoSymbols
+        );
+        assert!(matches!(
+            base()
+                .symbol("AA PL")
+                .end("2024-03-01T00:00:00Z".parse().unwrap())
+                .build()
+                .unwrap_err(),
+            RequestParamsError::Symbol(_)
+        ));
+    }
+}
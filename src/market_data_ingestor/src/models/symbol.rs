@@ -0,0 +1,59 @@
+//! Symbol canonicalization at the ingestor boundary.
+//!
+//! Every symbol entering the system — CLI arguments, batch files,
+//! catalogs — passes through [`canonicalize`] so `aapl`, ` AAPL ` and
+//! `AAPL` can never fan out into distinct downstream identities.
+
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum SymbolError {
+    #[error("symbol is empty")]
+    Empty,
+    #[error("symbol {symbol:?} contains invalid character {ch:?}")]
+    InvalidChar { symbol: String, ch: char },
+}
+
+/// Trim, uppercase and charset-check a raw symbol.
+///
+/// Accepted characters after uppercasing are `A..=Z`, `0..=9`, `.`, `-`
+/// and `/` (class shares like `BRK.B`, crypto pairs like `BTC/USD`).
+pub fn canonicalize(raw: &str) -> Result<String, SymbolError> {
+    let symbol = raw.trim().to_ascii_uppercase();
+    if symbol.is_empty() {
+        return Err(SymbolError::Empty);
+    }
+    for ch in symbol.chars() {
+        if !(ch.is_ascii_uppercase() || ch.is_ascii_digit() || matches!(ch, '.' | '-' | '/')) {
+            return Err(SymbolError::InvalidChar { symbol, ch });
+        }
+    }
+    Ok(symbol)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lowercase_is_uppercased() {
+        assert_eq!(canonicalize("aapl").unwrap(), "AAPL");
+    }
+
+    #[test]
+    fn surrounding_whitespace_is_trimmed() {
+        assert_eq!(canonicalize("  brk.b\t").unwrap(), "BRK.B");
+    }
+
+    #[test]
+    fn invalid_character_rejected() {
+        assert_eq!(
+            canonicalize("AA PL"),
+            Err(SymbolError::InvalidChar {
+                symbol: "AA PL".to_string(),
+                ch: ' '
+            })
+        );
+        assert_eq!(canonicalize("   "), Err(SymbolError::Empty));
+    }
+}
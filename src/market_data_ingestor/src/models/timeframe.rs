@@ -0,0 +1,130 @@
+//! Provider-facing bar timeframe with Alpaca's validity rules.
+
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum TimeFrameError {
+    #[error("minute timeframes must be 1..=59, got {0}")]
+    InvalidMinute(u32),
+    #[error("hour timeframes must be 1..=23, got {0}")]
+    InvalidHour(u32),
+    #[error("{unit} timeframes only support amount 1, got {amount}")]
+    AmountMustBeOne { unit: &'static str, amount: u32 },
+    #[error("month timeframes must be 1, 2, 3, 4, 6 or 12, got {0}")]
+    InvalidMonth(u32),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum TimeFrameUnit {
+    Minute,
+    Hour,
+    Day,
+    Week,
+    Month,
+}
+
+impl TimeFrameUnit {
+    fn as_alpaca_str(self) -> &'static str {
+        match self {
+            TimeFrameUnit::Minute => "Min",
+            TimeFrameUnit::Hour => "Hour",
+            TimeFrameUnit::Day => "Day",
+            TimeFrameUnit::Week => "Week",
+            TimeFrameUnit::Month => "Month",
+        }
+    }
+}
+
+/// A validated bar interval. Construct via [`TimeFrame::new`], which
+/// enforces the amounts Alpaca's data API actually accepts.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct TimeFrame {
+    amount: u32,
+    unit: TimeFrameUnit,
+}
+
+impl TimeFrame {
+    pub fn new(amount: u32, unit: TimeFrameUnit) -> Result<Self, TimeFrameError> {
+        match unit {
+            TimeFrameUnit::Minute if !(1..=59).contains(&amount) => {
+                return Err(TimeFrameError::InvalidMinute(amount));
+            }
+            TimeFrameUnit::Hour if !(1..=23).contains(&amount) => {
+                return Err(TimeFrameError::InvalidHour(amount));
+            }
+            TimeFrameUnit::Day if amount != 1 => {
+                return Err(TimeFrameError::AmountMustBeOne {
+                    unit: "day",
+                    amount,
+                });
+            }
+            TimeFrameUnit::Week if amount != 1 => {
+                return Err(TimeFrameError::AmountMustBeOne {
+                    unit: "week",
+                    amount,
+                });
+            }
+            TimeFrameUnit::Month if ![1, 2, 3, 4, 6, 12].contains(&amount) => {
+                return Err(TimeFrameError::InvalidMonth(amount));
+            }
+            _ => {}
+        }
+        Ok(TimeFrame { amount, unit })
+    }
+
+    pub fn amount(&self) -> u32 {
+        self.amount
+    }
+
+    pub fn unit(&self) -> TimeFrameUnit {
+        self.unit
+    }
+}
+
+impl fmt::Display for TimeFrame {
+    /// Alpaca query-string form, e.g. `5Min`, `1Day`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.amount, self.unit.as_alpaca_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn valid_timeframes() {
+        assert_eq!(
+            TimeFrame::new(5, TimeFrameUnit::Minute)
+                .unwrap()
+                .to_string(),
+            "5Min"
+        );
+        assert_eq!(
+            TimeFrame::new(1, TimeFrameUnit::Day).unwrap().to_string(),
+            "1Day"
+        );
+    }
+
+    #[test]
+    fn invalid_amounts_rejected() {
+        assert_eq!(
+            TimeFrame::new(60, TimeFrameUnit::Minute),
+            Err(TimeFrameError::InvalidMinute(60))
+        );
+        assert_eq!(
+            TimeFrame::new(2, TimeFrameUnit::Day),
+            Err(TimeFrameError::AmountMustBeOne {
+                unit: "day",
+                amount: 2
+            })
+        );
+        assert_eq!(
+            TimeFrame::new(5, TimeFrameUnit::Month),
+            Err(TimeFrameError::InvalidMonth(5))
+        );
+    }
+}
@@ -0,0 +1,1030 @@
+//! Client for Alpaca's historical bars API (`/v2/stocks/bars`).
+
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+
+use crate::models::bar::{Bar, BarSeries};
+use crate::models::request_params::BarsRequestParams;
+use crate::providers::{DataProvider, ProviderCapabilities, ProviderError, validate_date_range};
+
+/// Alpaca documents up to ~1000 symbols per multi-bars request; we stay
+/// well under that, and [`chunk_symbols_for_query`] additionally bounds
+/// each chunk by URL length.
+const MAX_SYMBOLS_PER_REQUEST: usize = 200;
+/// Default for [`AlpacaConfig::max_query_len`]: conservative against the
+/// point where Alpaca's edge answers with 414 URI Too Long.
+const DEFAULT_MAX_QUERY_LEN: usize = 4096;
+/// Bars per page; Alpaca's maximum.
+pub const PAGE_LIMIT: u32 = 10_000;
+
+/// Alpaca's historical bars go back to the start of 2016; requests before
+/// that come back empty, so we reject them up front.
+pub fn earliest_data() -> chrono::DateTime<chrono::Utc> {
+    chrono::DateTime::parse_from_rfc3339("2016-01-01T00:00:00Z")
+        .expect("valid literal")
+        .with_timezone(&chrono::Utc)
+}
+
+/// Which data subscription the credentials carry; decides how close to
+/// real time a request may end.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AlpacaSubscriptionPlan {
+    /// Free tier: SIP data is embargoed for the most recent 15 minutes.
+    #[default]
+    Basic,
+    /// Paid tier with real-time data.
+    AlgoTraderPlus,
+}
+
+impl AlpacaSubscriptionPlan {
+    /// How far behind real time requests must end on this plan.
+    pub fn data_delay(self) -> chrono::Duration {
+        match self {
+            AlpacaSubscriptionPlan::Basic => chrono::Duration::minutes(15),
+            AlpacaSubscriptionPlan::AlgoTraderPlus => chrono::Duration::zero(),
+        }
+    }
+}
+
+/// Credentials and endpoint for the data API.
+#[derive(Debug, Clone, Deserialize)]
+pub struct AlpacaConfig {
+    pub api_key_id: String,
+    pub api_secret_key: String,
+    #[serde(default = "default_base_url")]
+    pub base_url: String,
+    /// Abort a paged fetch once this many bars have accumulated, instead
+    /// of letting a multi-year minute request page forever. `None` means
+    /// unbounded.
+    #[serde(default)]
+    pub max_total_bars: Option<u64>,
+    /// Subscription tier of the credentials; defaults to the free plan
+    /// and its 15-minute data delay.
+    #[serde(default)]
+    pub plan: AlpacaSubscriptionPlan,
+    /// Equity data feed to request (`"sip"` or `"iex"`); `None` lets
+    /// Alpaca pick the account default. Ignored on crypto requests.
+    #[serde(default)]
+    pub feed: Option<String>,
+    /// Opt-in: when a SIP request fails because the account lacks the
+    /// entitlement, retry once on IEX instead of failing the fetch. The
+    /// returned series carry `source_feed` so the substitution is visible.
+    #[serde(default)]
+    pub feed_fallback: bool,
+    /// Base URL of the trading API, which serves the assets endpoint —
+    /// a different host from the bars data in `base_url`.
+    #[serde(default = "default_trading_base_url")]
+    pub trading_base_url: String,
+    /// Longest query string one bars request may carry. A symbol list
+    /// that would push past it is split into several requests and the
+    /// results merged, instead of letting Alpaca answer 414.
+    #[serde(default = "default_max_query_len")]
+    pub max_query_len: usize,
+}
+
+fn default_base_url() -> String {
+    "https://data.alpaca.markets".to_string()
+}
+
+fn default_max_query_len() -> usize {
+    DEFAULT_MAX_QUERY_LEN
+}
+
+fn default_trading_base_url() -> String {
+    "https://api.alpaca.markets".to_string()
+}
+
+impl AlpacaConfig {
+    pub fn new(api_key_id: impl Into<String>, api_secret_key: impl Into<String>) -> Self {
+        AlpacaConfig {
+            api_key_id: api_key_id.into(),
+            api_secret_key: api_secret_key.into(),
+            base_url: default_base_url(),
+            max_total_bars: None,
+            plan: AlpacaSubscriptionPlan::default(),
+            feed: None,
+            feed_fallback: false,
+            max_query_len: DEFAULT_MAX_QUERY_LEN,
+            trading_base_url: default_trading_base_url(),
+        }
+    }
+
+    /// Load from a TOML file with `api_key_id`/`api_secret_key`
+    /// (and optionally `base_url`) keys.
+    pub fn from_toml_path(path: &std::path::Path) -> Result<Self, ProviderError> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| ProviderError::InvalidRequest(format!("reading {path:?}: {e}")))?;
+        toml::from_str(&text)
+            .map_err(|e| ProviderError::InvalidRequest(format!("parsing {path:?}: {e}")))
+    }
+}
+
+/// Which of Alpaca's bar endpoints a request belongs to. Crypto symbols
+/// are pair-formatted (`BTC/USD`), so the slash decides; mixing both
+/// classes in one request is rejected because the endpoints differ.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Endpoint {
+    Equity,
+    Crypto,
+}
+
+fn endpoint_for(symbols: &[String]) -> Result<Endpoint, ProviderError> {
+    let crypto = symbols.iter().filter(|s| s.contains('/')).count();
+    match crypto {
+        0 => Ok(Endpoint::Equity),
+        n if n == symbols.len() => Ok(Endpoint::Crypto),
+        _ => Err(ProviderError::InvalidRequest(
+            "cannot mix equity and crypto symbols in one request".to_string(),
+        )),
+    }
+}
+
+/// Legacy request shape mirroring alpaca-py's `StockBarsRequest`, kept so
+/// call sites migrated from the Python fetcher can convert cleanly instead
+/// of rebuilding structs field by field. New code should use
+/// [`BarsRequestParams`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct StockBarsParams {
+    pub symbol_or_symbols: Vec<String>,
+    pub timeframe: crate::models::timeframe::TimeFrame,
+    pub start: chrono::DateTime<chrono::Utc>,
+    pub end: chrono::DateTime<chrono::Utc>,
+    /// Alpaca-specific data feed selector (`iex`/`sip`); equity-only, no
+    /// equivalent in the unified params.
+    pub feed: Option<String>,
+    /// Crypto exchange filter (e.g. `CBSE`); crypto-only.
+    pub exchange: Option<String>,
+    /// Alpaca-specific *per-page* row cap — not a total; a window with
+    /// more bars still pages to the end. Values above Alpaca's documented
+    /// maximum are clamped by [`StockBarsParams::page_limit`].
+    pub limit: Option<u32>,
+}
+
+impl StockBarsParams {
+    /// The per-page limit to put on the wire: the requested value clamped
+    /// to Alpaca's documented maximum of [`PAGE_LIMIT`], since the API
+    /// silently caps larger values and the response pagination then looks
+    /// inconsistent with what was asked for.
+    pub fn page_limit(&self) -> u32 {
+        self.limit.map_or(PAGE_LIMIT, |l| l.min(PAGE_LIMIT))
+    }
+
+    /// Reject combinations the wire would refuse: the SIP/IEX `feed`
+    /// selector exists only on the equity endpoint, and `exchange` only on
+    /// the crypto one.
+    pub fn validate(&self) -> Result<(), ProviderError> {
+        let endpoint = endpoint_for(&self.symbol_or_symbols)?;
+        if endpoint == Endpoint::Crypto && self.feed.is_some() {
+            return Err(ProviderError::InvalidRequest(
+                "feed is an equity-only parameter; crypto bars have no SIP/IEX feed".to_string(),
+            ));
+        }
+        if endpoint == Endpoint::Equity && self.exchange.is_some() {
+            return Err(ProviderError::InvalidRequest(
+                "exchange is a crypto-only parameter".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl From<BarsRequestParams> for StockBarsParams {
+    fn from(params: BarsRequestParams) -> Self {
+        StockBarsParams {
+            symbol_or_symbols: params.symbols,
+            timeframe: params.timeframe,
+            start: params.start,
+            end: params.end,
+            feed: None,
+            exchange: None,
+            limit: None,
+        }
+    }
+}
+
+impl From<StockBarsParams> for BarsRequestParams {
+    /// Drops the provider-specific `feed`, `exchange` and `limit` fields.
+    fn from(params: StockBarsParams) -> Self {
+        BarsRequestParams {
+            symbols: params.symbol_or_symbols,
+            timeframe: params.timeframe,
+            start: params.start,
+            end: params.end,
+        }
+    }
+}
+
+pub struct AlpacaProvider {
+    config: AlpacaConfig,
+    agent: ureq::Agent,
+}
+
+/// One page of a multi-symbol bars response.
+#[derive(Debug, Deserialize)]
+struct BarsPage {
+    #[serde(default)]
+    bars: MergedBars,
+    next_page_token: Option<String>,
+}
+
+/// Bars accumulated per symbol across response pages.
+type MergedBars = BTreeMap<String, Vec<Bar>>;
+
+impl AlpacaProvider {
+    pub fn new(config: AlpacaConfig) -> Self {
+        AlpacaProvider {
+            config,
+            agent: ureq::Agent::new(),
+        }
+    }
+
+    fn get_page(
+        &self,
+        params: &BarsRequestParams,
+        endpoint: Endpoint,
+        feed: Option<&str>,
+        page_token: Option<&str>,
+    ) -> Result<BarsPage, ProviderError> {
+        let url = match endpoint {
+            Endpoint::Equity => format!("{}/v2/stocks/bars", self.config.base_url),
+            Endpoint::Crypto => format!("{}/v1beta3/crypto/us/bars", self.config.base_url),
+        };
+        let headers = [
+            ("APCA-API-KEY-ID", self.config.api_key_id.as_str()),
+            ("APCA-API-SECRET-KEY", self.config.api_secret_key.as_str()),
+        ];
+        let mut query: Vec<(&str, String)> = vec![
+            ("symbols", params.symbols.join(",")),
+            ("timeframe", params.timeframe.to_string()),
+            ("start", params.start.to_rfc3339()),
+            ("end", params.end.to_rfc3339()),
+            ("limit", PAGE_LIMIT.to_string()),
+        ];
+        if let Some(feed) = feed {
+            query.push(("feed", feed.to_string()));
+        }
+        if let Some(token) = page_token {
+            query.push(("page_token", token.to_string()));
+        }
+        #[cfg(feature = "tracing")]
+        tracing::debug!(
+            request = %request_log_line(&url, &query, &headers),
+            "alpaca request"
+        );
+        let mut request = self.agent.get(&url);
+        for (name, value) in headers {
+            request = request.set(name, value);
+        }
+        for (name, value) in &query {
+            request = request.query(name, value);
+        }
+        let response = request.call().map_err(|e| match e {
+            ureq::Error::Status(status, response) => ProviderError::Http {
+                status,
+                body: response.into_string().unwrap_or_default(),
+            },
+            ureq::Error::Transport(t) => ProviderError::Transport(t.to_string()),
+        })?;
+        response
+            .into_json::<BarsPage>()
+            .map_err(|e| ProviderError::Transport(format!("reading response body: {e}")))
+    }
+}
+
+impl DataProvider for AlpacaProvider {
+    fn capabilities(&self) -> ProviderCapabilities {
+        ProviderCapabilities {
+            max_symbols_per_request: MAX_SYMBOLS_PER_REQUEST,
+            earliest_data: Some(earliest_data()),
+            max_bars_per_window: None,
+        }
+    }
+
+    fn fetch_bars(&self, params: &BarsRequestParams) -> Result<Vec<BarSeries>, ProviderError> {
+        self.fetch_bars_paged(params, None)
+    }
+
+    /// Lookup against the trading API's assets endpoint, which serves
+    /// equities and crypto pairs alike — `asset_class` is not needed to
+    /// route the request. A 404 is "no such listing", not a failure.
+    fn fetch_asset(
+        &self,
+        symbol: &str,
+        _asset_class: &str,
+    ) -> Result<Option<crate::models::asset::AssetInfo>, ProviderError> {
+        #[derive(Deserialize)]
+        struct AssetWire {
+            symbol: String,
+            name: String,
+            exchange: String,
+            tradable: bool,
+            status: String,
+        }
+        // Pair symbols carry their slash percent-encoded in the path.
+        let url = format!(
+            "{}/v2/assets/{}",
+            self.config.trading_base_url,
+            symbol.replace('/', "%2F")
+        );
+        let request = self
+            .agent
+            .get(&url)
+            .set("APCA-API-KEY-ID", &self.config.api_key_id)
+            .set("APCA-API-SECRET-KEY", &self.config.api_secret_key);
+        match request.call() {
+            Ok(response) => {
+                let wire: AssetWire = response
+                    .into_json()
+                    .map_err(|e| ProviderError::Transport(format!("reading response body: {e}")))?;
+                Ok(Some(crate::models::asset::AssetInfo {
+                    symbol: wire.symbol,
+                    name: wire.name,
+                    exchange: wire.exchange,
+                    tradable: wire.tradable,
+                    status: wire.status,
+                }))
+            }
+            Err(ureq::Error::Status(404, _)) => Ok(None),
+            Err(ureq::Error::Status(status, response)) => Err(ProviderError::Http {
+                status,
+                body: response.into_string().unwrap_or_default(),
+            }),
+            Err(ureq::Error::Transport(t)) => Err(ProviderError::Transport(t.to_string())),
+        }
+    }
+
+    /// Re-checks the token at every page boundary, so a long minute-bar
+    /// backfill aborts within one page of cancellation instead of running
+    /// to the end of the window.
+    fn fetch_bars_cancellable(
+        &self,
+        params: &BarsRequestParams,
+        cancel: &crate::providers::CancelToken,
+    ) -> Result<Vec<BarSeries>, ProviderError> {
+        self.fetch_bars_paged(params, Some(cancel))
+    }
+}
+
+impl AlpacaProvider {
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            skip(self, params, cancel),
+            fields(
+                symbols = params.symbols.len(),
+                timeframe = %params.timeframe,
+                pages = tracing::field::Empty,
+            )
+        )
+    )]
+    fn fetch_bars_paged(
+        &self,
+        params: &BarsRequestParams,
+        cancel: Option<&crate::providers::CancelToken>,
+    ) -> Result<Vec<BarSeries>, ProviderError> {
+        if params.symbols.is_empty() {
+            return Err(ProviderError::InvalidRequest(
+                "no symbols requested".to_string(),
+            ));
+        }
+        validate_date_range(params, Some(earliest_data()), self.config.plan.data_delay())?;
+        let endpoint = endpoint_for(&params.symbols)?;
+
+        // The feed selector only exists on the equity endpoint.
+        let feed = match endpoint {
+            Endpoint::Equity => self.config.feed.as_deref(),
+            Endpoint::Crypto => None,
+        };
+        let (merged, used_feed) = fetch_with_feed_fallback(feed, self.config.feed_fallback, |f| {
+            self.fetch_pages(params, endpoint, cancel, f)
+        })?;
+        let mut series = to_sorted_series(merged, params.timeframe);
+        if let Some(feed) = used_feed {
+            for s in &mut series {
+                s.source_feed = Some(feed.clone());
+            }
+        }
+        Ok(series)
+    }
+
+    /// Fetch every page for every symbol chunk. Symbol lists too long for
+    /// one URL ([`chunk_symbols_for_query`]) become several requests whose
+    /// pages merge into one result; the total-bar cap spans all of them.
+    fn fetch_pages(
+        &self,
+        params: &BarsRequestParams,
+        endpoint: Endpoint,
+        cancel: Option<&crate::providers::CancelToken>,
+        feed: Option<&str>,
+    ) -> Result<MergedBars, ProviderError> {
+        let mut merged: MergedBars = params
+            .symbols
+            .iter()
+            .map(|s| (s.clone(), Vec::new()))
+            .collect();
+        let fixed = fixed_query_len(params, feed);
+        let mut pages = 0u32;
+        let mut total = 0u64;
+        for symbols in chunk_symbols_for_query(&params.symbols, fixed, self.config.max_query_len) {
+            let chunk = BarsRequestParams {
+                symbols,
+                timeframe: params.timeframe,
+                start: params.start,
+                end: params.end,
+            };
+            let mut page_token: Option<String> = None;
+            loop {
+                if cancel.is_some_and(|c| c.is_cancelled()) {
+                    return Err(ProviderError::Aborted);
+                }
+                let page = self.get_page(&chunk, endpoint, feed, page_token.as_deref())?;
+                pages += 1;
+                total += page
+                    .bars
+                    .values()
+                    .map(|bars| bars.len() as u64)
+                    .sum::<u64>();
+                enforce_total_cap(total, self.config.max_total_bars)?;
+                merge_page(&mut merged, page.bars);
+                match page.next_page_token {
+                    Some(token) => page_token = Some(token),
+                    None => break,
+                }
+            }
+        }
+
+        #[cfg(feature = "tracing")]
+        tracing::Span::current().record("pages", pages);
+        #[cfg(not(feature = "tracing"))]
+        let _ = pages;
+
+        Ok(merged)
+    }
+}
+
+/// Bytes the non-symbol query params occupy on the wire, so the symbol
+/// budget in [`chunk_symbols_for_query`] accounts for them. Includes
+/// slack for the `page_token` param later pages append.
+fn fixed_query_len(params: &BarsRequestParams, feed: Option<&str>) -> usize {
+    let mut len = "symbols=".len()
+        + "&timeframe=".len()
+        + params.timeframe.to_string().len()
+        + "&start=".len()
+        + params.start.to_rfc3339().len()
+        + "&end=".len()
+        + params.end.to_rfc3339().len()
+        + "&limit=".len()
+        + PAGE_LIMIT.to_string().len();
+    if let Some(feed) = feed {
+        len += "&feed=".len() + feed.len();
+    }
+    len + 128
+}
+
+/// Split `symbols` into runs whose comma-joined form keeps the whole
+/// query string within `max_query_len`, `fixed_len` being what the other
+/// params already occupy. Order is preserved and every symbol lands in
+/// exactly one chunk; chunks also respect [`MAX_SYMBOLS_PER_REQUEST`]. A
+/// single symbol longer than the budget still gets its own chunk — the
+/// provider's rejection of it beats looping forever here.
+fn chunk_symbols_for_query(
+    symbols: &[String],
+    fixed_len: usize,
+    max_query_len: usize,
+) -> Vec<Vec<String>> {
+    let budget = max_query_len.saturating_sub(fixed_len).max(1);
+    let mut chunks: Vec<Vec<String>> = Vec::new();
+    let mut current: Vec<String> = Vec::new();
+    let mut current_len = 0usize;
+    for symbol in symbols {
+        let full = current_len + 1 + symbol.len(); // +1 for the comma
+        if !current.is_empty() && (full > budget || current.len() == MAX_SYMBOLS_PER_REQUEST) {
+            chunks.push(std::mem::take(&mut current));
+            current_len = 0;
+        }
+        current_len += symbol.len() + usize::from(!current.is_empty());
+        current.push(symbol.clone());
+    }
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+/// A 403 telling the account it is not entitled to the requested feed —
+/// the one failure where falling back to IEX makes sense. Everything else
+/// (bad credentials, rate limits) must surface untouched.
+fn is_entitlement_error(error: &ProviderError) -> bool {
+    match error {
+        ProviderError::Http { status: 403, body } => {
+            let body = body.to_lowercase();
+            body.contains("subscription") || body.contains("entitle")
+        }
+        _ => false,
+    }
+}
+
+/// Run `fetch` with the requested feed; if that was SIP, fallback is
+/// enabled and the failure is an entitlement rejection, retry once on
+/// IEX. Returns the merged bars and the feed that actually served them.
+fn fetch_with_feed_fallback<F>(
+    feed: Option<&str>,
+    fallback: bool,
+    mut fetch: F,
+) -> Result<(MergedBars, Option<String>), ProviderError>
+where
+    F: FnMut(Option<&str>) -> Result<MergedBars, ProviderError>,
+{
+    match fetch(feed) {
+        Ok(merged) => Ok((merged, feed.map(str::to_string))),
+        Err(e) if fallback && feed == Some("sip") && is_entitlement_error(&e) => {
+            let merged = fetch(Some("iex"))?;
+            Ok((merged, Some("iex".to_string())))
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Flatten the merged symbol map into series. Alpaca's JSON symbol map has
+/// no documented order, so the deterministic-output guarantee on
+/// [`DataProvider::fetch_bars`] is established here: series come out
+/// sorted by symbol (the `BTreeMap` order) and each series' bars by
+/// timestamp.
+fn to_sorted_series(
+    merged: MergedBars,
+    timeframe: crate::models::timeframe::TimeFrame,
+) -> Vec<BarSeries> {
+    merged
+        .into_iter()
+        .map(|(symbol, mut bars)| {
+            bars.sort_by_key(|bar| bar.timestamp);
+            // Page boundaries can repeat a bar; near-identical copies
+            // (float noise aside) are duplicates, not data.
+            bars.dedup_by(|a, b| a.approx_eq(b, crate::models::bar::PRICE_TOLERANCE));
+            BarSeries {
+                symbol,
+                timeframe,
+                bars,
+                source_feed: None,
+            }
+        })
+        .collect()
+}
+
+/// Stop a paged fetch that has outgrown the configured bar budget; the
+/// error tells the caller to narrow the window rather than retry.
+fn enforce_total_cap(total: u64, cap: Option<u64>) -> Result<(), ProviderError> {
+    match cap {
+        Some(cap) if total > cap => Err(ProviderError::InvalidRequest(format!(
+            "fetch produced {total} bars, over the configured max_total_bars of {cap}; \
+             narrow the request window"
+        ))),
+        _ => Ok(()),
+    }
+}
+
+/// One-line request description for debug logs: method, host, path and
+/// query, with every credential-looking header value replaced by `***`.
+/// Query values are assumed loggable (symbols, windows, page tokens);
+/// secrets travel only in headers, and those are redacted by name so a
+/// new `*-KEY-*` header can never leak by omission.
+#[cfg_attr(not(feature = "tracing"), allow(dead_code))]
+fn request_log_line(url: &str, query: &[(&str, String)], headers: &[(&str, &str)]) -> String {
+    let (host, path) = match url
+        .strip_prefix("https://")
+        .or_else(|| url.strip_prefix("http://"))
+    {
+        Some(rest) => match rest.split_once('/') {
+            Some((host, path)) => (host, format!("/{path}")),
+            None => (rest, "/".to_string()),
+        },
+        None => ("", url.to_string()),
+    };
+    let query: Vec<String> = query.iter().map(|(k, v)| format!("{k}={v}")).collect();
+    let headers: Vec<String> = headers
+        .iter()
+        .map(|(name, value)| {
+            let lower = name.to_ascii_lowercase();
+            if lower.contains("key") || lower.contains("secret") || lower.contains("token") {
+                format!("{name}=***")
+            } else {
+                format!("{name}={value}")
+            }
+        })
+        .collect();
+    format!(
+        "GET {host} {path}?{} [{}]",
+        query.join("&"),
+        headers.join(" ")
+    )
+}
+
+fn merge_page(merged: &mut MergedBars, page: MergedBars) {
+    for (symbol, bars) in page {
+        merged.entry(symbol).or_default().extend(bars);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stock_bars_params_round_trips_shared_fields() {
+        use crate::models::timeframe::{TimeFrame, TimeFrameUnit};
+        let unified = BarsRequestParams {
+            symbols: vec!["AAPL".to_string()],
+            timeframe: TimeFrame::new(5, TimeFrameUnit::Minute).unwrap(),
+            start: "2024-01-01T00:00:00Z".parse().unwrap(),
+            end: "2024-02-01T00:00:00Z".parse().unwrap(),
+        };
+        let legacy = StockBarsParams::from(unified.clone());
+        assert_eq!(legacy.symbol_or_symbols, unified.symbols);
+        assert_eq!(legacy.feed, None);
+        assert_eq!(BarsRequestParams::from(legacy), unified);
+    }
+
+    #[test]
+    fn date_range_respects_an_injected_floor() {
+        use crate::models::timeframe::{TimeFrame, TimeFrameUnit};
+        let params = BarsRequestParams {
+            symbols: vec!["AAPL".to_string()],
+            timeframe: TimeFrame::new(1, TimeFrameUnit::Day).unwrap(),
+            start: "2004-06-01T00:00:00Z".parse().unwrap(),
+            end: "2004-07-01T00:00:00Z".parse().unwrap(),
+        };
+        // Against Alpaca's own 2016 floor this window is too early...
+        let err = validate_date_range(&params, Some(earliest_data()), chrono::Duration::zero())
+            .unwrap_err();
+        assert!(err.to_string().contains("earliest data"));
+        // ...but a provider with history back to 2003 accepts it.
+        let floor_2003 = "2003-01-01T00:00:00Z".parse().unwrap();
+        validate_date_range(&params, Some(floor_2003), chrono::Duration::zero()).unwrap();
+    }
+
+    #[test]
+    fn basic_plan_embargoes_the_last_fifteen_minutes() {
+        use crate::models::timeframe::{TimeFrame, TimeFrameUnit};
+        assert_eq!(
+            AlpacaSubscriptionPlan::Basic.data_delay(),
+            chrono::Duration::minutes(15)
+        );
+        // Pin the clock so the boundary cases are exact, not relative to
+        // however long the test took to reach this line.
+        let now: chrono::DateTime<chrono::Utc> = "2024-06-03T12:00:00Z".parse().unwrap();
+        let mut params = BarsRequestParams {
+            symbols: vec!["AAPL".to_string()],
+            timeframe: TimeFrame::new(1, TimeFrameUnit::Minute).unwrap(),
+            start: "2024-06-03T11:00:00Z".parse().unwrap(),
+            end: now,
+        };
+        let delay = AlpacaSubscriptionPlan::Basic.data_delay();
+        let err = crate::providers::validate_date_range_at(&params, None, delay, now).unwrap_err();
+        assert!(err.to_string().contains("15-minute"));
+
+        // Ending exactly at the embargo edge is allowed; one second past
+        // it is not.
+        params.end = "2024-06-03T11:45:00Z".parse().unwrap();
+        crate::providers::validate_date_range_at(&params, None, delay, now).unwrap();
+        params.end = "2024-06-03T11:45:01Z".parse().unwrap();
+        crate::providers::validate_date_range_at(&params, None, delay, now).unwrap_err();
+
+        // The paid plan has no embargo.
+        params.end = "2024-06-03T11:59:59Z".parse().unwrap();
+        crate::providers::validate_date_range_at(
+            &params,
+            None,
+            AlpacaSubscriptionPlan::AlgoTraderPlus.data_delay(),
+            now,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn page_limit_clamps_to_the_documented_maximum() {
+        use crate::models::timeframe::{TimeFrame, TimeFrameUnit};
+        let mut params = StockBarsParams {
+            symbol_or_symbols: vec!["AAPL".to_string()],
+            timeframe: TimeFrame::new(1, TimeFrameUnit::Minute).unwrap(),
+            start: "2024-01-01T00:00:00Z".parse().unwrap(),
+            end: "2024-02-01T00:00:00Z".parse().unwrap(),
+            feed: None,
+            exchange: None,
+            limit: Some(50_000),
+        };
+        assert_eq!(params.page_limit(), PAGE_LIMIT);
+        params.limit = Some(500);
+        assert_eq!(params.page_limit(), 500);
+        params.limit = None;
+        assert_eq!(params.page_limit(), PAGE_LIMIT);
+    }
+
+    #[test]
+    fn series_come_out_sorted_by_symbol_and_timestamp() {
+        use crate::models::timeframe::{TimeFrame, TimeFrameUnit};
+        let bar = |ts: &str| Bar {
+            timestamp: ts.parse().unwrap(),
+            open: 1.0,
+            high: 2.0,
+            low: 0.5,
+            close: 1.5,
+            volume: 10.0,
+            trade_count: None,
+            vwap: None,
+        };
+        // Symbols inserted out of order, bars within each shuffled — as a
+        // paged response with no order guarantee could deliver them.
+        let mut merged = BTreeMap::new();
+        merged.insert(
+            "MSFT".to_string(),
+            vec![bar("2024-01-03T00:00:00Z"), bar("2024-01-02T00:00:00Z")],
+        );
+        merged.insert(
+            "AAPL".to_string(),
+            vec![
+                bar("2024-01-04T00:00:00Z"),
+                bar("2024-01-02T00:00:00Z"),
+                bar("2024-01-03T00:00:00Z"),
+            ],
+        );
+
+        let tf = TimeFrame::new(1, TimeFrameUnit::Day).unwrap();
+        let series = to_sorted_series(merged, tf);
+        assert_eq!(series[0].symbol, "AAPL");
+        assert_eq!(series[1].symbol, "MSFT");
+        for s in &series {
+            assert!(s.bars.is_sorted_by_key(|b| b.timestamp), "{}", s.symbol);
+        }
+    }
+
+    #[test]
+    fn request_logs_redact_credential_headers() {
+        let line = request_log_line(
+            "https://data.alpaca.markets/v2/stocks/bars",
+            &[("symbols", "AAPL".to_string()), ("limit", "5".to_string())],
+            &[
+                ("APCA-API-KEY-ID", "PK_NOT_A_REAL_KEY"),
+                ("APCA-API-SECRET-KEY", "sk_not_a_real_secret"),
+            ],
+        );
+        assert_eq!(
+            line,
+            "GET data.alpaca.markets /v2/stocks/bars?symbols=AAPL&limit=5 \
+             [APCA-API-KEY-ID=*** APCA-API-SECRET-KEY=***]"
+        );
+        assert!(!line.contains("PK_NOT_A_REAL_KEY"));
+        assert!(!line.contains("sk_not_a_real_secret"));
+    }
+
+    #[test]
+    fn overlapping_pages_dedup_near_identical_bars() {
+        use crate::models::timeframe::{TimeFrame, TimeFrameUnit};
+        let bar = Bar {
+            timestamp: "2024-01-02T00:00:00Z".parse().unwrap(),
+            open: 187.15,
+            high: 188.44,
+            low: 183.89,
+            close: 185.64,
+            volume: 82_488_674.0,
+            trade_count: Some(3),
+            vwap: Some(185.94),
+        };
+        // The same bar again, off by a last-ULP nudge — as a page overlap
+        // re-parsed through a different float path would deliver it.
+        let mut echo = bar.clone();
+        echo.close += 1e-12;
+        // And a genuinely different bar at the same timestamp.
+        let mut revised = bar.clone();
+        revised.close += 0.01;
+
+        let mut merged = BTreeMap::new();
+        merged.insert("AAPL".to_string(), vec![bar.clone(), echo, revised]);
+        let tf = TimeFrame::new(1, TimeFrameUnit::Day).unwrap();
+        let series = to_sorted_series(merged, tf);
+        assert_eq!(series[0].bars.len(), 2);
+        assert_eq!(series[0].bars[0], bar);
+    }
+
+    #[test]
+    fn total_bar_cap_stops_runaway_fetches() {
+        assert!(enforce_total_cap(10_000, None).is_ok());
+        assert!(enforce_total_cap(10_000, Some(10_000)).is_ok());
+        let err = enforce_total_cap(10_001, Some(10_000)).unwrap_err();
+        assert!(matches!(err, ProviderError::InvalidRequest(_)));
+        assert!(err.to_string().contains("max_total_bars"));
+    }
+
+    #[test]
+    fn crypto_symbols_route_to_the_crypto_endpoint() {
+        let crypto = vec!["BTC/USD".to_string(), "ETH/USD".to_string()];
+        assert_eq!(endpoint_for(&crypto).unwrap(), Endpoint::Crypto);
+        let equity = vec!["AAPL".to_string()];
+        assert_eq!(endpoint_for(&equity).unwrap(), Endpoint::Equity);
+        // Pair symbols pass the shared canonicalization too: the slash is
+        // part of the crypto charset, not an equity-only rejection.
+        assert_eq!(
+            crate::models::symbol::canonicalize("btc/usd").unwrap(),
+            "BTC/USD"
+        );
+
+        let mixed = vec!["AAPL".to_string(), "BTC/USD".to_string()];
+        assert!(matches!(
+            endpoint_for(&mixed),
+            Err(ProviderError::InvalidRequest(_))
+        ));
+    }
+
+    #[test]
+    fn equity_feed_is_rejected_on_a_crypto_request() {
+        use crate::models::timeframe::{TimeFrame, TimeFrameUnit};
+        let mut params = StockBarsParams {
+            symbol_or_symbols: vec!["BTC/USD".to_string()],
+            timeframe: TimeFrame::new(1, TimeFrameUnit::Hour).unwrap(),
+            start: "2024-01-01T00:00:00Z".parse().unwrap(),
+            end: "2024-02-01T00:00:00Z".parse().unwrap(),
+            feed: Some("iex".to_string()),
+            exchange: None,
+            limit: None,
+        };
+        let err = params.validate().unwrap_err();
+        assert!(matches!(err, ProviderError::InvalidRequest(_)));
+        assert!(err.to_string().contains("equity-only"));
+
+        params.feed = None;
+        params.exchange = Some("CBSE".to_string());
+        assert!(params.validate().is_ok());
+
+        // And the reverse: an exchange filter makes no sense for equities.
+        params.symbol_or_symbols = vec!["AAPL".to_string()];
+        assert!(params.validate().is_err());
+    }
+
+    #[test]
+    fn sip_entitlement_errors_fall_back_to_iex_when_opted_in() {
+        let bar = Bar {
+            timestamp: chrono::DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+            open: 1.0,
+            high: 2.0,
+            low: 0.5,
+            close: 1.5,
+            volume: 10.0,
+            trade_count: None,
+            vwap: None,
+        };
+        // The shape a Basic-plan account sees when it asks for SIP.
+        let entitlement = || ProviderError::Http {
+            status: 403,
+            body: "subscription does not permit querying recent SIP data".to_string(),
+        };
+        let mock = |feed: Option<&str>| match feed {
+            Some("sip") => Err(entitlement()),
+            Some("iex") => {
+                let mut merged = BTreeMap::new();
+                merged.insert("AAPL".to_string(), vec![bar.clone()]);
+                Ok(merged)
+            }
+            other => panic!("unexpected feed {other:?}"),
+        };
+
+        let (merged, used) = fetch_with_feed_fallback(Some("sip"), true, mock).unwrap();
+        assert_eq!(used.as_deref(), Some("iex"));
+        assert_eq!(merged["AAPL"].len(), 1);
+
+        // Without the opt-in the entitlement error surfaces untouched.
+        let err = fetch_with_feed_fallback(Some("sip"), false, mock).unwrap_err();
+        assert!(is_entitlement_error(&err));
+
+        // A 403 that isn't about entitlements never triggers a retry.
+        let auth_failure = |_: Option<&str>| {
+            Err(ProviderError::Http {
+                status: 403,
+                body: "forbidden".to_string(),
+            })
+        };
+        let err = fetch_with_feed_fallback(Some("sip"), true, auth_failure).unwrap_err();
+        assert!(!is_entitlement_error(&err));
+
+        // A successful request reports the feed it was sent on.
+        let ok = |_: Option<&str>| Ok(BTreeMap::new());
+        let (_, used) = fetch_with_feed_fallback(Some("sip"), true, ok).unwrap();
+        assert_eq!(used.as_deref(), Some("sip"));
+        let (_, used) = fetch_with_feed_fallback(None, true, ok).unwrap();
+        assert_eq!(used, None);
+    }
+
+    #[test]
+    fn page_deserializes_from_wire_format() {
+        let body = r#"{
+            "bars": {
+                "AAPL": [
+                    {"t": "2024-01-02T14:30:00Z", "o": 187.15, "h": 187.34,
+                     "l": 187.05, "c": 187.2, "v": 12345, "n": 210, "vw": 187.18}
+                ]
+            },
+            "next_page_token": "abc123"
+        }"#;
+        let page: BarsPage = serde_json::from_str(body).unwrap();
+        assert_eq!(page.next_page_token.as_deref(), Some("abc123"));
+        let bars = &page.bars["AAPL"];
+        assert_eq!(bars.len(), 1);
+        assert_eq!(bars[0].close, 187.2);
+        assert_eq!(bars[0].trade_count, Some(210));
+    }
+
+    #[test]
+    fn merge_appends_pages_per_symbol() {
+        let bar = |c: f64| Bar {
+            timestamp: chrono::DateTime::from_timestamp(1_700_000_000, 0).unwrap(),
+            open: c,
+            high: c,
+            low: c,
+            close: c,
+            volume: 1.0,
+            trade_count: None,
+            vwap: None,
+        };
+        let mut merged = BTreeMap::from([("AAPL".to_string(), vec![bar(1.0)])]);
+        merge_page(
+            &mut merged,
+            BTreeMap::from([("AAPL".to_string(), vec![bar(2.0)])]),
+        );
+        assert_eq!(merged["AAPL"].len(), 2);
+    }
+
+    #[test]
+    fn asset_lookup_parses_listings_and_maps_404_to_none() {
+        use std::io::{Read, Write};
+
+        // One-shot HTTP server: one canned response per connection, in
+        // order, capturing each request line for assertion.
+        fn serve(responses: Vec<String>) -> (String, std::thread::JoinHandle<Vec<String>>) {
+            let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+            let addr = listener.local_addr().unwrap();
+            let handle = std::thread::spawn(move || {
+                let mut seen = Vec::new();
+                for response in responses {
+                    let (mut stream, _) = listener.accept().unwrap();
+                    let mut buf = [0u8; 4096];
+                    let n = stream.read(&mut buf).unwrap();
+                    let request = String::from_utf8_lossy(&buf[..n]).to_string();
+                    seen.push(request.lines().next().unwrap_or_default().to_string());
+                    stream.write_all(response.as_bytes()).unwrap();
+                }
+                seen
+            });
+            (format!("http://{addr}"), handle)
+        }
+
+        let body = r#"{"symbol":"AAPL","name":"Apple Inc. Common Stock","exchange":"NASDAQ","tradable":true,"status":"active"}"#;
+        let ok = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n\
+             Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        );
+        let missing =
+            "HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_string();
+        let (base, handle) = serve(vec![ok, missing]);
+
+        let mut config = AlpacaConfig::new("key", "secret");
+        config.trading_base_url = base;
+        let provider = AlpacaProvider::new(config);
+
+        let info = provider.fetch_asset("AAPL", "us_equity").unwrap().unwrap();
+        assert_eq!(info.name, "Apple Inc. Common Stock");
+        assert_eq!(info.exchange, "NASDAQ");
+        assert!(info.tradable);
+        assert_eq!(info.status, "active");
+
+        assert_eq!(provider.fetch_asset("NOPE", "us_equity").unwrap(), None);
+
+        let seen = handle.join().unwrap();
+        assert_eq!(seen[0], "GET /v2/assets/AAPL HTTP/1.1");
+        assert_eq!(seen[1], "GET /v2/assets/NOPE HTTP/1.1");
+    }
+
+    #[test]
+    fn long_symbol_lists_chunk_under_the_query_length_limit() {
+        let symbols: Vec<String> = (0..1000).map(|i| format!("S{i:03}")).collect();
+        let fixed = 150;
+        let max_query_len = 1024;
+        let chunks = chunk_symbols_for_query(&symbols, fixed, max_query_len);
+
+        assert!(chunks.len() > 1, "1000 symbols cannot fit one URL");
+        for chunk in &chunks {
+            assert!(!chunk.is_empty());
+            assert!(chunk.len() <= MAX_SYMBOLS_PER_REQUEST);
+            assert!(fixed + chunk.join(",").len() <= max_query_len);
+        }
+        // Every symbol appears exactly once, in the original order.
+        let flattened: Vec<String> = chunks.into_iter().flatten().collect();
+        assert_eq!(flattened, symbols);
+
+        // A symbol that can never fit still comes through as its own
+        // chunk rather than being dropped or looping.
+        let oversized = vec!["X".repeat(2 * max_query_len)];
+        let chunks = chunk_symbols_for_query(&oversized, fixed, max_query_len);
+        assert_eq!(chunks, vec![oversized]);
+    }
+}
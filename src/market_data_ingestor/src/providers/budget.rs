@@ -0,0 +1,187 @@
+//! A hard request budget and the [`DataProvider`] decorator enforcing it.
+//!
+//! Rate limiting spreads requests out; a budget caps how many happen at
+//! all. Metered plans bill per request, so once the cap is spent the
+//! right move is to refuse locally — [`ProviderError::RateLimited`]
+//! before any API call — rather than let a long backfill quietly run up
+//! the bill.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use chrono::{DateTime, Duration, Utc};
+
+use crate::models::bar::BarSeries;
+use crate::models::request_params::BarsRequestParams;
+use crate::providers::{DataProvider, ProviderCapabilities, ProviderError};
+
+/// Wraps any provider and refuses fetches once `max_requests` have been
+/// issued within the rolling `window`. Spent slots free again as their
+/// timestamps age out, so a daily cap recovers on its own.
+///
+/// The ledger is in-memory; a process that should honor its budget across
+/// restarts can persist [`BudgetedProvider::history`] and rebuild with
+/// [`BudgetedProvider::with_history`].
+pub struct BudgetedProvider<P> {
+    inner: P,
+    max_requests: usize,
+    window: Duration,
+    history: Mutex<VecDeque<DateTime<Utc>>>,
+}
+
+impl<P> BudgetedProvider<P> {
+    /// Budget of `max_requests` fetches per rolling `window`.
+    pub fn new(inner: P, max_requests: usize, window: Duration) -> Self {
+        Self::with_history(inner, max_requests, window, Vec::new())
+    }
+
+    /// [`BudgetedProvider::new`] seeded with request timestamps from an
+    /// earlier run, so a restart does not reset the meter.
+    pub fn with_history(
+        inner: P,
+        max_requests: usize,
+        window: Duration,
+        history: Vec<DateTime<Utc>>,
+    ) -> Self {
+        BudgetedProvider {
+            inner,
+            max_requests,
+            window,
+            history: Mutex::new(history.into()),
+        }
+    }
+
+    /// Fetches still allowed right now.
+    pub fn remaining(&self) -> usize {
+        self.remaining_at(Utc::now())
+    }
+
+    /// [`BudgetedProvider::remaining`] with the clock injected.
+    pub fn remaining_at(&self, now: DateTime<Utc>) -> usize {
+        let mut history = self.history.lock().expect("budget ledger poisoned");
+        Self::expire(&mut history, now, self.window);
+        self.max_requests.saturating_sub(history.len())
+    }
+
+    /// Timestamps of the requests still counted against the budget, for
+    /// callers that persist the ledger across runs.
+    pub fn history(&self) -> Vec<DateTime<Utc>> {
+        self.history
+            .lock()
+            .expect("budget ledger poisoned")
+            .iter()
+            .copied()
+            .collect()
+    }
+
+    fn expire(history: &mut VecDeque<DateTime<Utc>>, now: DateTime<Utc>, window: Duration) {
+        while history.front().is_some_and(|&t| t <= now - window) {
+            history.pop_front();
+        }
+    }
+
+    /// Record one request at `now`, or refuse if the window is full.
+    fn spend(&self, now: DateTime<Utc>) -> Result<(), ProviderError> {
+        let mut history = self.history.lock().expect("budget ledger poisoned");
+        Self::expire(&mut history, now, self.window);
+        if history.len() >= self.max_requests {
+            let frees = history
+                .front()
+                .map(|&t| (t + self.window).to_rfc3339())
+                .unwrap_or_default();
+            return Err(ProviderError::RateLimited(format!(
+                "request budget of {} per {} spent; next slot frees at {frees}",
+                self.max_requests, self.window
+            )));
+        }
+        history.push_back(now);
+        Ok(())
+    }
+}
+
+impl<P: DataProvider> DataProvider for BudgetedProvider<P> {
+    fn capabilities(&self) -> ProviderCapabilities {
+        self.inner.capabilities()
+    }
+
+    fn fetch_bars(&self, params: &BarsRequestParams) -> Result<Vec<BarSeries>, ProviderError> {
+        self.spend(Utc::now())?;
+        self.inner.fetch_bars(params)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use super::*;
+    use crate::models::timeframe::{TimeFrame, TimeFrameUnit};
+
+    struct CountingProvider {
+        calls: AtomicU32,
+    }
+
+    impl DataProvider for CountingProvider {
+        fn capabilities(&self) -> ProviderCapabilities {
+            ProviderCapabilities {
+                max_symbols_per_request: 1,
+                earliest_data: None,
+                max_bars_per_window: None,
+            }
+        }
+
+        fn fetch_bars(&self, _params: &BarsRequestParams) -> Result<Vec<BarSeries>, ProviderError> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            Ok(Vec::new())
+        }
+    }
+
+    fn params() -> BarsRequestParams {
+        BarsRequestParams {
+            symbols: vec!["AAPL".to_string()],
+            timeframe: TimeFrame::new(1, TimeFrameUnit::Day).unwrap(),
+            start: "2024-01-02T00:00:00Z".parse().unwrap(),
+            end: "2024-01-03T00:00:00Z".parse().unwrap(),
+        }
+    }
+
+    #[test]
+    fn the_request_over_budget_is_refused_before_the_inner_provider() {
+        let provider = BudgetedProvider::new(
+            CountingProvider {
+                calls: AtomicU32::new(0),
+            },
+            2,
+            Duration::hours(24),
+        );
+        assert_eq!(provider.remaining(), 2);
+        provider.fetch_bars(&params()).unwrap();
+        provider.fetch_bars(&params()).unwrap();
+        assert_eq!(provider.remaining(), 0);
+
+        let err = provider.fetch_bars(&params()).unwrap_err();
+        assert!(matches!(err, ProviderError::RateLimited(_)), "{err}");
+        assert!(err.to_string().contains("budget of 2"), "{err}");
+        // The refusal happened locally: the API was never called a third
+        // time.
+        assert_eq!(provider.inner.calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn spent_slots_free_as_they_age_out_of_the_window() {
+        let old = Utc::now() - Duration::hours(25);
+        let provider = BudgetedProvider::with_history(
+            CountingProvider {
+                calls: AtomicU32::new(0),
+            },
+            2,
+            Duration::hours(24),
+            vec![old, old + Duration::minutes(1)],
+        );
+        // Both seeded requests predate the window, so the budget is whole
+        // again.
+        assert_eq!(provider.remaining(), 2);
+        provider.fetch_bars(&params()).unwrap();
+        assert_eq!(provider.history().len(), 1);
+    }
+}
@@ -0,0 +1,149 @@
+//! Response capture for bug reports: a [`DataProvider`] decorator that
+//! writes what a provider returned to disk before handing it on.
+//!
+//! When a provider returns unexpected data, "it returned garbage" is not
+//! actionable; the capture file is. [`Bar`](crate::models::bar::Bar)
+//! serializes under its wire names (`t`/`o`/`h`/`l`/…), so the artifact
+//! mirrors the provider's JSON shape even though it is written from the
+//! decoded response. Capture is off unless a directory is set, so the
+//! decorator can stay in place permanently.
+
+use std::path::PathBuf;
+
+use crate::models::bar::BarSeries;
+use crate::models::request_params::BarsRequestParams;
+use crate::providers::{DataProvider, ProviderCapabilities, ProviderError};
+
+/// Wraps any provider; with a capture directory set, every fetched series
+/// is also written to `{symbol}_{timeframe}_{start}.json` there.
+pub struct CapturingProvider<P> {
+    inner: P,
+    capture_dir: Option<PathBuf>,
+}
+
+impl<P> CapturingProvider<P> {
+    /// Pass-through decorator with capture disabled.
+    pub fn new(inner: P) -> Self {
+        CapturingProvider {
+            inner,
+            capture_dir: None,
+        }
+    }
+
+    /// Decorator that captures every response under `dir`.
+    pub fn with_capture_dir(inner: P, dir: impl Into<PathBuf>) -> Self {
+        CapturingProvider {
+            inner,
+            capture_dir: Some(dir.into()),
+        }
+    }
+}
+
+impl<P: DataProvider> DataProvider for CapturingProvider<P> {
+    fn capabilities(&self) -> ProviderCapabilities {
+        self.inner.capabilities()
+    }
+
+    fn fetch_bars(&self, params: &BarsRequestParams) -> Result<Vec<BarSeries>, ProviderError> {
+        let series = self.inner.fetch_bars(params)?;
+        if let Some(dir) = &self.capture_dir {
+            capture(dir, params, &series)
+                .map_err(|e| ProviderError::Transport(format!("writing capture file: {e}")))?;
+        }
+        Ok(series)
+    }
+}
+
+fn capture(
+    dir: &std::path::Path,
+    params: &BarsRequestParams,
+    series: &[BarSeries],
+) -> std::io::Result<()> {
+    std::fs::create_dir_all(dir)?;
+    for s in series {
+        let name = format!(
+            "{}_{}_{}.json",
+            s.symbol.replace('/', "_"),
+            params.timeframe,
+            params.start.format("%Y%m%dT%H%M%SZ"),
+        );
+        let json = serde_json::to_vec_pretty(s).map_err(std::io::Error::other)?;
+        std::fs::write(dir.join(name), json)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::bar::Bar;
+    use crate::models::timeframe::{TimeFrame, TimeFrameUnit};
+
+    struct StubProvider;
+
+    impl DataProvider for StubProvider {
+        fn capabilities(&self) -> ProviderCapabilities {
+            ProviderCapabilities {
+                max_symbols_per_request: 10,
+                earliest_data: None,
+                max_bars_per_window: None,
+            }
+        }
+
+        fn fetch_bars(&self, params: &BarsRequestParams) -> Result<Vec<BarSeries>, ProviderError> {
+            Ok(params
+                .symbols
+                .iter()
+                .map(|symbol| BarSeries {
+                    symbol: symbol.clone(),
+                    timeframe: params.timeframe,
+                    bars: vec![Bar {
+                        timestamp: params.start,
+                        open: 1.0,
+                        high: 2.0,
+                        low: 0.5,
+                        close: 1.5,
+                        volume: 10.0,
+                        trade_count: Some(3),
+                        vwap: Some(1.2),
+                    }],
+                    source_feed: None,
+                })
+                .collect())
+        }
+    }
+
+    fn params() -> BarsRequestParams {
+        BarsRequestParams {
+            symbols: vec!["AAPL".to_string(), "BTC/USD".to_string()],
+            timeframe: TimeFrame::new(1, TimeFrameUnit::Hour).unwrap(),
+            start: "2024-01-02T14:00:00Z".parse().unwrap(),
+            end: "2024-01-02T16:00:00Z".parse().unwrap(),
+        }
+    }
+
+    #[test]
+    fn capture_writes_one_wire_shaped_file_per_symbol() {
+        let dir = tempfile::tempdir().unwrap();
+        let provider = CapturingProvider::with_capture_dir(StubProvider, dir.path());
+        let series = provider.fetch_bars(&params()).unwrap();
+        assert_eq!(series.len(), 2);
+
+        let aapl = dir.path().join("AAPL_1Hour_20240102T140000Z.json");
+        let btc = dir.path().join("BTC_USD_1Hour_20240102T140000Z.json");
+        assert!(aapl.exists());
+        assert!(btc.exists());
+        // Wire field names survive into the artifact.
+        let text = std::fs::read_to_string(&aapl).unwrap();
+        assert!(text.contains("\"t\""), "{text}");
+        assert!(text.contains("\"vw\""), "{text}");
+    }
+
+    #[test]
+    fn capture_is_off_by_default() {
+        let dir = tempfile::tempdir().unwrap();
+        let provider = CapturingProvider::new(StubProvider);
+        provider.fetch_bars(&params()).unwrap();
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+}
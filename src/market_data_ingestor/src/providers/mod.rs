@@ -0,0 +1,559 @@
+//! The [`DataProvider`] abstraction and concrete provider clients.
+
+pub mod alpaca;
+pub mod budget;
+pub mod capture;
+#[cfg(feature = "delta")]
+pub mod delta;
+pub mod python;
+pub mod retry;
+
+use chrono::{DateTime, Duration, Utc};
+use thiserror::Error;
+
+use crate::models::bar::BarSeries;
+use crate::models::request_params::BarsRequestParams;
+use crate::models::timeframe::{TimeFrame, TimeFrameUnit};
+
+#[derive(Debug, Error)]
+pub enum ProviderError {
+    #[error("request rejected: {0}")]
+    InvalidRequest(String),
+    #[error("HTTP {status} from provider: {body}")]
+    Http { status: u16, body: String },
+    #[error("transport error: {0}")]
+    Transport(String),
+    #[error("rate limited: {0}")]
+    RateLimited(String),
+    #[error("failed to decode provider response: {0}")]
+    Decode(#[from] serde_json::Error),
+    #[error("fetch aborted by cancellation")]
+    Aborted,
+}
+
+/// Shared flag a worker flips to abort in-flight fetches, e.g. when it
+/// loses its gap lease or the process catches SIGINT. Clones observe the
+/// same flag; cancellation is sticky.
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(std::sync::Arc<std::sync::atomic::AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, std::sync::atomic::Ordering::SeqCst);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(std::sync::atomic::Ordering::SeqCst)
+    }
+}
+
+/// Request-shaping limits a provider imposes; the planner uses these to
+/// size its fetch chunks.
+#[derive(Debug, Clone)]
+pub struct ProviderCapabilities {
+    pub max_symbols_per_request: usize,
+    /// No data exists before this date; `None` means unbounded history
+    /// (e.g. local storage). Alpaca's floor is 2016, Polygon's differs.
+    pub earliest_data: Option<DateTime<Utc>>,
+    /// Most bars one request window may return. `None` for providers
+    /// with response cursors, which page past any cap on their own;
+    /// providers without cursors set it so [`fetch_bars_windowed`] can
+    /// split wide windows client-side.
+    pub max_bars_per_window: Option<u64>,
+}
+
+/// Check a request window against a provider's history floor and its
+/// recent-data embargo (e.g. the 15-minute delay on Alpaca's Basic plan).
+/// The floor is a parameter, not a constant, so each provider passes its
+/// own and tests can inject one.
+pub fn validate_date_range(
+    params: &BarsRequestParams,
+    earliest: Option<DateTime<Utc>>,
+    delay: Duration,
+) -> Result<(), ProviderError> {
+    validate_date_range_at(params, earliest, delay, Utc::now())
+}
+
+/// [`validate_date_range`] with the clock injected: `now` anchors the
+/// recent-data embargo, so tests can pin it instead of racing the wall
+/// clock.
+pub fn validate_date_range_at(
+    params: &BarsRequestParams,
+    earliest: Option<DateTime<Utc>>,
+    delay: Duration,
+    now: DateTime<Utc>,
+) -> Result<(), ProviderError> {
+    if params.start >= params.end {
+        return Err(ProviderError::InvalidRequest(
+            "start must precede end".to_string(),
+        ));
+    }
+    if let Some(earliest) = earliest
+        && params.start < earliest
+    {
+        return Err(ProviderError::InvalidRequest(format!(
+            "start {} predates the provider's earliest data ({})",
+            params.start.to_rfc3339(),
+            earliest.to_rfc3339()
+        )));
+    }
+    let freshest = now - delay;
+    if params.end > freshest {
+        return Err(ProviderError::InvalidRequest(format!(
+            "end {} is within the plan's {}-minute data delay",
+            params.end.to_rfc3339(),
+            delay.num_minutes()
+        )));
+    }
+    Ok(())
+}
+
+/// Per-request outcome of [`fetch_bars_many`], in input order.
+pub type BatchFetchResult = Vec<Result<Vec<BarSeries>, ProviderError>>;
+
+/// Run a batch of requests against `provider` with at most `concurrency`
+/// in flight at once, returning results aligned to the input order.
+///
+/// The bound is the caller's rate-limit budget: pick it so `concurrency`
+/// simultaneous requests cannot blow the plan's requests-per-minute cap.
+/// A failed request only fails its own slot, mirroring
+/// `fetch_bars_batch_partial` on the Python bridge.
+pub fn fetch_bars_many<P: DataProvider + Sync>(
+    provider: &P,
+    batch: &[BarsRequestParams],
+    concurrency: usize,
+) -> BatchFetchResult {
+    use std::sync::Mutex;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    let workers = concurrency.clamp(1, batch.len().max(1));
+    let next = AtomicUsize::new(0);
+    let results = Mutex::new(
+        batch
+            .iter()
+            .map(|_| None::<Result<Vec<BarSeries>, ProviderError>>)
+            .collect::<Vec<_>>(),
+    );
+    std::thread::scope(|scope| {
+        for _ in 0..workers {
+            scope.spawn(|| {
+                loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    if i >= batch.len() {
+                        break;
+                    }
+                    let result = provider.fetch_bars(&batch[i]);
+                    results.lock().expect("no panics hold the lock")[i] = Some(result);
+                }
+            });
+        }
+    });
+    results
+        .into_inner()
+        .expect("no panics hold the lock")
+        .into_iter()
+        .map(|slot| slot.expect("every slot is filled before the scope ends"))
+        .collect()
+}
+
+/// Fetch a window that may exceed the provider's per-window bar cap by
+/// splitting it client-side.
+///
+/// Providers with response cursors page past any cap themselves; for the
+/// rest ([`ProviderCapabilities::max_bars_per_window`]), a year of minute
+/// bars has to be requested as many smaller windows. The split is sized
+/// from the timeframe's nominal span — worst case, every bucket has a
+/// bar — so a sub-window can never exceed the cap, and the per-symbol
+/// results are stitched back together with [`BarSeries::merge`].
+pub fn fetch_bars_windowed<P: DataProvider>(
+    provider: &P,
+    params: &BarsRequestParams,
+) -> Result<Vec<BarSeries>, ProviderError> {
+    let Some(cap) = provider
+        .capabilities()
+        .max_bars_per_window
+        .filter(|&c| c > 0)
+    else {
+        return provider.fetch_bars(params);
+    };
+    let span_secs = (params.end - params.start).num_seconds().max(1) as u64;
+    let bar_secs = nominal_bar_seconds(&params.timeframe);
+    let worst_case_bars = span_secs.div_ceil(bar_secs) * params.symbols.len().max(1) as u64;
+    let windows = worst_case_bars.div_ceil(cap).max(1);
+    if windows == 1 {
+        return provider.fetch_bars(params);
+    }
+
+    let step = Duration::seconds(span_secs.div_ceil(windows).max(bar_secs) as i64);
+    let mut merged: std::collections::BTreeMap<String, BarSeries> =
+        std::collections::BTreeMap::new();
+    let mut start = params.start;
+    while start < params.end {
+        let end = (start + step).min(params.end);
+        let sub = BarsRequestParams {
+            symbols: params.symbols.clone(),
+            timeframe: params.timeframe,
+            start,
+            end,
+        };
+        for series in provider.fetch_bars(&sub)? {
+            match merged.entry(series.symbol.clone()) {
+                std::collections::btree_map::Entry::Occupied(mut e) => e.get_mut().merge(series),
+                std::collections::btree_map::Entry::Vacant(v) => {
+                    v.insert(series);
+                }
+            }
+        }
+        start = end;
+    }
+    Ok(merged.into_values().collect())
+}
+
+/// Nominal wall-clock span of one bar, for sizing sub-windows. Week and
+/// month use their shortest realization so the estimate can only round
+/// the window count up, never under it.
+fn nominal_bar_seconds(timeframe: &TimeFrame) -> u64 {
+    let amount = u64::from(timeframe.amount());
+    match timeframe.unit() {
+        TimeFrameUnit::Minute => 60 * amount,
+        TimeFrameUnit::Hour => 3_600 * amount,
+        TimeFrameUnit::Day => 86_400 * amount,
+        TimeFrameUnit::Week => 7 * 86_400 * amount,
+        TimeFrameUnit::Month => 28 * 86_400 * amount,
+    }
+}
+
+/// A source of historical bars.
+pub trait DataProvider {
+    fn capabilities(&self) -> ProviderCapabilities;
+
+    /// Fetch all bars described by `params`, one series per requested
+    /// symbol. Symbols with no bars in the window yield an empty series.
+    ///
+    /// Output order is deterministic: series sorted by symbol, bars within
+    /// a series sorted by timestamp. Callers (comparison tests, coverage
+    /// bucketization) rely on this instead of re-sorting.
+    fn fetch_bars(&self, params: &BarsRequestParams) -> Result<Vec<BarSeries>, ProviderError>;
+
+    /// [`DataProvider::fetch_bars`] that gives up with
+    /// [`ProviderError::Aborted`] once `cancel` fires. The default checks
+    /// only before starting; providers that page (where the real waiting
+    /// happens) should override this and re-check between pages so a
+    /// multi-page fetch stops at the next page boundary.
+    fn fetch_bars_cancellable(
+        &self,
+        params: &BarsRequestParams,
+        cancel: &CancelToken,
+    ) -> Result<Vec<BarSeries>, ProviderError> {
+        if cancel.is_cancelled() {
+            return Err(ProviderError::Aborted);
+        }
+        self.fetch_bars(params)
+    }
+
+    /// Look up listing metadata for one symbol; `Ok(None)` means the
+    /// provider does not know it. The default refuses — not every source
+    /// of bars (capture files, local tables) carries listing metadata,
+    /// and pretending "unknown symbol" there would mislead the caller.
+    fn fetch_asset(
+        &self,
+        symbol: &str,
+        asset_class: &str,
+    ) -> Result<Option<crate::models::asset::AssetInfo>, ProviderError> {
+        let _ = (symbol, asset_class);
+        Err(ProviderError::InvalidRequest(
+            "this provider has no asset metadata".to_string(),
+        ))
+    }
+
+    /// Cheap credentials probe: fetch one daily bar of a liquid symbol
+    /// over a fixed historical day. A 401/403 comes back with a
+    /// "credentials rejected" prefix so operators get a clear "your API
+    /// key is wrong" before a backfill run, instead of deep inside the
+    /// fetch loop.
+    fn health_check(&self) -> Result<(), ProviderError> {
+        let params = BarsRequestParams {
+            symbols: vec!["AAPL".to_string()],
+            timeframe: TimeFrame::new(1, TimeFrameUnit::Day).expect("1 day is a valid timeframe"),
+            start: "2024-01-02T00:00:00Z".parse().expect("static timestamp"),
+            end: "2024-01-03T00:00:00Z".parse().expect("static timestamp"),
+        };
+        match self.fetch_bars(&params) {
+            Ok(_) => Ok(()),
+            Err(ProviderError::Http {
+                status: status @ (401 | 403),
+                body,
+            }) => Err(ProviderError::Http {
+                status,
+                body: format!("credentials rejected: {body}"),
+            }),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fails every fetch with a fixed HTTP status.
+    struct FailingProvider(u16);
+
+    impl DataProvider for FailingProvider {
+        fn capabilities(&self) -> ProviderCapabilities {
+            ProviderCapabilities {
+                max_symbols_per_request: 1,
+                earliest_data: None,
+                max_bars_per_window: None,
+            }
+        }
+
+        fn fetch_bars(&self, _params: &BarsRequestParams) -> Result<Vec<BarSeries>, ProviderError> {
+            Err(ProviderError::Http {
+                status: self.0,
+                body: "nope".to_string(),
+            })
+        }
+    }
+
+    struct OkProvider;
+
+    impl DataProvider for OkProvider {
+        fn capabilities(&self) -> ProviderCapabilities {
+            ProviderCapabilities {
+                max_symbols_per_request: 1,
+                earliest_data: None,
+                max_bars_per_window: None,
+            }
+        }
+
+        fn fetch_bars(&self, _params: &BarsRequestParams) -> Result<Vec<BarSeries>, ProviderError> {
+            Ok(Vec::new())
+        }
+    }
+
+    /// Tracks how many fetches overlap, to observe the concurrency bound.
+    struct CountingProvider {
+        in_flight: std::sync::atomic::AtomicUsize,
+        max_in_flight: std::sync::atomic::AtomicUsize,
+    }
+
+    impl DataProvider for CountingProvider {
+        fn capabilities(&self) -> ProviderCapabilities {
+            ProviderCapabilities {
+                max_symbols_per_request: 1,
+                earliest_data: None,
+                max_bars_per_window: None,
+            }
+        }
+
+        fn fetch_bars(&self, params: &BarsRequestParams) -> Result<Vec<BarSeries>, ProviderError> {
+            use std::sync::atomic::Ordering;
+            let now = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_in_flight.fetch_max(now, Ordering::SeqCst);
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+            Ok(vec![BarSeries {
+                symbol: params.symbols[0].clone(),
+                timeframe: params.timeframe,
+                bars: Vec::new(),
+                source_feed: None,
+            }])
+        }
+    }
+
+    #[test]
+    fn batch_fetch_bounds_in_flight_requests_and_aligns_results() {
+        let provider = CountingProvider {
+            in_flight: std::sync::atomic::AtomicUsize::new(0),
+            max_in_flight: std::sync::atomic::AtomicUsize::new(0),
+        };
+        let batch: Vec<BarsRequestParams> = (0..8)
+            .map(|i| BarsRequestParams {
+                symbols: vec![format!("SYM{i}")],
+                timeframe: TimeFrame::new(1, TimeFrameUnit::Day).unwrap(),
+                start: "2024-01-02T00:00:00Z".parse().unwrap(),
+                end: "2024-01-03T00:00:00Z".parse().unwrap(),
+            })
+            .collect();
+
+        let results = fetch_bars_many(&provider, &batch, 3);
+        assert_eq!(results.len(), batch.len());
+        for (i, result) in results.iter().enumerate() {
+            assert_eq!(result.as_ref().unwrap()[0].symbol, format!("SYM{i}"));
+        }
+        let peak = provider
+            .max_in_flight
+            .load(std::sync::atomic::Ordering::SeqCst);
+        assert!(peak >= 2, "workers never overlapped (peak {peak})");
+        assert!(peak <= 3, "more than 3 requests in flight (peak {peak})");
+    }
+
+    /// Pages forever in small sleeps, honoring the token between pages —
+    /// the shape of a long multi-page backfill.
+    struct SlowPagingProvider;
+
+    impl DataProvider for SlowPagingProvider {
+        fn capabilities(&self) -> ProviderCapabilities {
+            ProviderCapabilities {
+                max_symbols_per_request: 1,
+                earliest_data: None,
+                max_bars_per_window: None,
+            }
+        }
+
+        fn fetch_bars(&self, _params: &BarsRequestParams) -> Result<Vec<BarSeries>, ProviderError> {
+            self.fetch_bars_cancellable(_params, &CancelToken::new())
+        }
+
+        fn fetch_bars_cancellable(
+            &self,
+            _params: &BarsRequestParams,
+            cancel: &CancelToken,
+        ) -> Result<Vec<BarSeries>, ProviderError> {
+            for _page in 0..1_000 {
+                if cancel.is_cancelled() {
+                    return Err(ProviderError::Aborted);
+                }
+                std::thread::sleep(std::time::Duration::from_millis(5));
+            }
+            Ok(Vec::new())
+        }
+    }
+
+    #[test]
+    fn cancellation_aborts_a_paging_fetch_mid_flight() {
+        let params = BarsRequestParams {
+            symbols: vec!["AAPL".to_string()],
+            timeframe: TimeFrame::new(1, TimeFrameUnit::Day).unwrap(),
+            start: "2024-01-02T00:00:00Z".parse().unwrap(),
+            end: "2024-01-03T00:00:00Z".parse().unwrap(),
+        };
+        let cancel = CancelToken::new();
+        let result = std::thread::scope(|scope| {
+            let fetch = scope.spawn(|| SlowPagingProvider.fetch_bars_cancellable(&params, &cancel));
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            cancel.cancel();
+            fetch.join().expect("fetch thread does not panic")
+        });
+        assert!(matches!(result.unwrap_err(), ProviderError::Aborted));
+
+        // The default implementation checks the token before starting.
+        let pre_cancelled = CancelToken::new();
+        pre_cancelled.cancel();
+        assert!(matches!(
+            OkProvider
+                .fetch_bars_cancellable(&params, &pre_cancelled)
+                .unwrap_err(),
+            ProviderError::Aborted
+        ));
+        assert!(
+            OkProvider
+                .fetch_bars_cancellable(&params, &CancelToken::new())
+                .is_ok()
+        );
+    }
+
+    /// Serves one minute bar per minute of the requested window and
+    /// records each window it was asked for, but caps a single window at
+    /// 60 bars — the shape of a cursorless provider.
+    struct CappedMinuteProvider {
+        windows: std::sync::Mutex<Vec<(DateTime<Utc>, DateTime<Utc>)>>,
+    }
+
+    impl DataProvider for CappedMinuteProvider {
+        fn capabilities(&self) -> ProviderCapabilities {
+            ProviderCapabilities {
+                max_symbols_per_request: 1,
+                earliest_data: None,
+                max_bars_per_window: Some(60),
+            }
+        }
+
+        fn fetch_bars(&self, params: &BarsRequestParams) -> Result<Vec<BarSeries>, ProviderError> {
+            let minutes = (params.end - params.start).num_minutes() as u64;
+            assert!(minutes <= 60, "window of {minutes} bars exceeds the cap");
+            self.windows
+                .lock()
+                .unwrap()
+                .push((params.start, params.end));
+            let bars = (0..minutes)
+                .map(|i| crate::models::bar::Bar {
+                    timestamp: params.start + Duration::minutes(i as i64),
+                    open: 1.0,
+                    high: 2.0,
+                    low: 0.5,
+                    close: 1.5,
+                    volume: 10.0,
+                    trade_count: None,
+                    vwap: None,
+                })
+                .collect();
+            Ok(vec![BarSeries {
+                symbol: params.symbols[0].clone(),
+                timeframe: params.timeframe,
+                bars,
+                source_feed: None,
+            }])
+        }
+    }
+
+    #[test]
+    fn wide_windows_split_under_the_bar_cap_and_stitch_back_together() {
+        let provider = CappedMinuteProvider {
+            windows: std::sync::Mutex::new(Vec::new()),
+        };
+        let params = BarsRequestParams {
+            symbols: vec!["AAPL".to_string()],
+            timeframe: TimeFrame::new(1, TimeFrameUnit::Minute).unwrap(),
+            start: "2024-01-02T00:00:00Z".parse().unwrap(),
+            end: "2024-01-02T03:00:00Z".parse().unwrap(),
+        };
+
+        let series = fetch_bars_windowed(&provider, &params).unwrap();
+        let windows = provider.windows.lock().unwrap();
+        assert_eq!(windows.len(), 3);
+        assert_eq!(windows[0].1, windows[1].0, "sub-windows must abut");
+        assert_eq!(windows.last().unwrap().1, params.end);
+
+        // One stitched series covering the whole window, no seams.
+        assert_eq!(series.len(), 1);
+        assert_eq!(series[0].bars.len(), 180);
+        assert!(series[0].bars.is_sorted_by_key(|b| b.timestamp));
+
+        // A window already under the cap goes out as one fetch.
+        drop(windows);
+        provider.windows.lock().unwrap().clear();
+        let narrow = BarsRequestParams {
+            end: "2024-01-02T01:00:00Z".parse().unwrap(),
+            ..params
+        };
+        fetch_bars_windowed(&provider, &narrow).unwrap();
+        assert_eq!(provider.windows.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn health_check_flags_credential_failures() {
+        assert!(OkProvider.health_check().is_ok());
+
+        match FailingProvider(401).health_check().unwrap_err() {
+            ProviderError::Http { status, body } => {
+                assert_eq!(status, 401);
+                assert!(body.starts_with("credentials rejected"), "{body}");
+            }
+            other => panic!("expected Http, got {other:?}"),
+        }
+        // Other statuses pass through untouched.
+        match FailingProvider(500).health_check().unwrap_err() {
+            ProviderError::Http { status, body } => {
+                assert_eq!(status, 500);
+                assert_eq!(body, "nope");
+            }
+            other => panic!("expected Http, got {other:?}"),
+        }
+    }
+}
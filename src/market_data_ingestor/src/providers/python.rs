@@ -0,0 +1,581 @@
+//! The legacy `StockBarData` fetch surface and its migration off Python.
+//!
+//! The Python scripts under `src/data_fetching/Python` predate this crate
+//! and write their artifact (a feather/parquet file) to disk, printing its
+//! path as the final stdout line. They also chat on stdout — notably an
+//! `Alpaca version:` banner at import time — which must not leak into our
+//! machine-parseable output: callers rely on stdout carrying paths only,
+//! with everything human-facing on stderr. The bridge therefore runs the
+//! script with captured stdout/stderr, forwards every diagnostic line to
+//! `tracing` at debug level, and hands back just the artifact path.
+//!
+//! [`StockBarData::new_native`] keeps the same artifact-path contract but
+//! fetches through [`AlpacaProvider`] in-process, so callers who only want
+//! REST need no Python venv. The subprocess path stays behind the `python`
+//! feature.
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use thiserror::Error;
+
+use crate::models::bar::BarSeries;
+use crate::providers::alpaca::{AlpacaConfig, AlpacaProvider, StockBarsParams};
+use crate::providers::{DataProvider, ProviderError};
+
+#[derive(Debug, Error)]
+pub enum PythonBridgeError {
+    #[error("failed to run {interpreter}: {source}")]
+    Spawn {
+        interpreter: String,
+        source: std::io::Error,
+    },
+    #[error("python exited with {status}: {stderr}")]
+    Script { status: String, stderr: String },
+    #[error("python produced no artifact path on stdout")]
+    NoArtifact,
+    #[error(transparent)]
+    Provider(#[from] ProviderError),
+    #[error("cannot write artifact: {0}")]
+    Artifact(#[from] std::io::Error),
+    #[error("incomplete environment config: {0}")]
+    Env(String),
+}
+
+/// Points [`StockBarData::from_env`] at a Python venv for the legacy
+/// backend; the interpreter is `<venv>/bin/python`.
+pub const ENV_PYTHON_VENV: &str = "ALPACA_PYTHON_VENV";
+/// The fetch script the venv's interpreter should run.
+pub const ENV_FETCH_SCRIPT: &str = "ALPACA_FETCH_SCRIPT";
+/// Where the native backend writes artifacts; defaults to `artifacts`.
+pub const ENV_OUTPUT_DIR: &str = "ALPACA_OUTPUT_DIR";
+
+enum Backend {
+    #[cfg(feature = "python")]
+    Python {
+        interpreter: PathBuf,
+        script: PathBuf,
+    },
+    Native {
+        provider: AlpacaProvider,
+        output_dir: PathBuf,
+    },
+}
+
+/// Handle on the legacy stock-bars fetch surface. The original backend
+/// shells out to the alpaca-py script; [`StockBarData::new_native`] serves
+/// the same calls from the Rust provider.
+pub struct StockBarData {
+    backend: Backend,
+}
+
+impl StockBarData {
+    /// Bridge to the legacy Python fetcher: an interpreter (normally the
+    /// venv's `python`) plus the fetch script it should run.
+    #[cfg(feature = "python")]
+    pub fn new(interpreter: impl Into<PathBuf>, script: impl Into<PathBuf>) -> Self {
+        StockBarData {
+            backend: Backend::Python {
+                interpreter: interpreter.into(),
+                script: script.into(),
+            },
+        }
+    }
+
+    /// Pure-Rust backend: fetches through [`AlpacaProvider`] and writes
+    /// artifacts (JSON-serialized series) under `output_dir`. Needs no
+    /// Python installation.
+    pub fn new_native(config: AlpacaConfig, output_dir: impl Into<PathBuf>) -> Self {
+        StockBarData {
+            backend: Backend::Native {
+                provider: AlpacaProvider::new(config),
+                output_dir: output_dir.into(),
+            },
+        }
+    }
+
+    /// Build from the environment instead of explicit paths — what CI
+    /// and containers want, where mounting a config file is friction.
+    /// With [`ENV_PYTHON_VENV`] set (and the `python` feature compiled
+    /// in), the backend is the venv's interpreter running
+    /// [`ENV_FETCH_SCRIPT`]; otherwise the native backend, with
+    /// credentials from `APCA_API_KEY_ID`/`APCA_API_SECRET_KEY` and
+    /// artifacts under [`ENV_OUTPUT_DIR`].
+    pub fn from_env() -> Result<Self, PythonBridgeError> {
+        Self::from_env_with(|name| std::env::var(name).ok())
+    }
+
+    /// [`StockBarData::from_env`] with the lookup injected, so tests
+    /// supply an environment instead of mutating the process's (unsafe
+    /// once threads exist).
+    fn from_env_with(var: impl Fn(&str) -> Option<String>) -> Result<Self, PythonBridgeError> {
+        if let Some(venv) = var(ENV_PYTHON_VENV) {
+            #[cfg(feature = "python")]
+            {
+                let script = var(ENV_FETCH_SCRIPT).ok_or_else(|| {
+                    PythonBridgeError::Env(format!(
+                        "{ENV_PYTHON_VENV} is set but {ENV_FETCH_SCRIPT} is not"
+                    ))
+                })?;
+                return Ok(Self::new(
+                    Path::new(&venv).join("bin").join("python"),
+                    script,
+                ));
+            }
+            #[cfg(not(feature = "python"))]
+            {
+                let _ = venv;
+                return Err(PythonBridgeError::Env(format!(
+                    "{ENV_PYTHON_VENV} is set but this build lacks the `python` feature"
+                )));
+            }
+        }
+        let api_key_id = var("APCA_API_KEY_ID")
+            .ok_or_else(|| PythonBridgeError::Env("APCA_API_KEY_ID is not set".to_string()))?;
+        let api_secret_key = var("APCA_API_SECRET_KEY")
+            .ok_or_else(|| PythonBridgeError::Env("APCA_API_SECRET_KEY is not set".to_string()))?;
+        let output_dir = var(ENV_OUTPUT_DIR).unwrap_or_else(|| "artifacts".to_string());
+        Ok(Self::new_native(
+            AlpacaConfig::new(api_key_id, api_secret_key),
+            output_dir,
+        ))
+    }
+
+    /// Run one request and return the path of the artifact holding its
+    /// bars. On the Python backend, everything the script prints besides
+    /// that path — version banners, progress chatter, stderr — is
+    /// forwarded to `tracing` at debug level instead of reaching our
+    /// stdout.
+    pub fn fetch_historical_bars(
+        &self,
+        params: &StockBarsParams,
+    ) -> Result<PathBuf, PythonBridgeError> {
+        self.fetch_historical_bars_suffixed(params, None)
+    }
+
+    /// [`StockBarData::fetch_historical_bars`] with an extra tag spliced
+    /// into the artifact name, so batch drivers can stamp each file with
+    /// its entry for later correlation. The Python script names its own
+    /// output, so that backend ignores the suffix.
+    pub fn fetch_historical_bars_suffixed(
+        &self,
+        params: &StockBarsParams,
+        suffix: Option<&str>,
+    ) -> Result<PathBuf, PythonBridgeError> {
+        match &self.backend {
+            #[cfg(feature = "python")]
+            Backend::Python {
+                interpreter,
+                script,
+            } => run_python_fetch(interpreter, script, params),
+            Backend::Native {
+                provider,
+                output_dir,
+            } => {
+                let series = provider.fetch_bars(&params.clone().into())?;
+                write_series_artifact(output_dir, params, &series, suffix)
+            }
+        }
+    }
+
+    /// Like [`StockBarData::fetch_historical_bars`], but one artifact per
+    /// symbol, keyed by it. A multi-symbol request otherwise lands in a
+    /// single combined file, which downstream per-symbol loaders then
+    /// misattribute. The legacy Python script cannot split its output, so
+    /// that backend returns its one combined artifact under the joined
+    /// symbol list.
+    pub fn fetch_historical_bars_per_symbol(
+        &self,
+        params: &StockBarsParams,
+    ) -> Result<Vec<(String, PathBuf)>, PythonBridgeError> {
+        match &self.backend {
+            #[cfg(feature = "python")]
+            Backend::Python {
+                interpreter,
+                script,
+            } => {
+                let path = run_python_fetch(interpreter, script, params)?;
+                Ok(vec![(params.symbol_or_symbols.join(","), path)])
+            }
+            Backend::Native {
+                provider,
+                output_dir,
+            } => {
+                let series = provider.fetch_bars(&params.clone().into())?;
+                write_series_artifacts_per_symbol(output_dir, params, &series)
+            }
+        }
+    }
+
+    /// Run every request in `batch`, returning one result per entry so a
+    /// failed fetch does not discard its neighbours' artifacts. Artifacts
+    /// carry the entry's position as a suffix, so two entries asking for
+    /// the same window still land in files a reader can tell apart.
+    pub fn fetch_bars_batch_partial(
+        &self,
+        batch: &[StockBarsParams],
+    ) -> Vec<Result<PathBuf, PythonBridgeError>> {
+        batch
+            .iter()
+            .enumerate()
+            .map(|(i, params)| self.fetch_historical_bars_suffixed(params, Some(&format!("e{i}"))))
+            .collect()
+    }
+}
+
+#[cfg(feature = "python")]
+fn run_python_fetch(
+    interpreter: &Path,
+    script: &Path,
+    params: &StockBarsParams,
+) -> Result<PathBuf, PythonBridgeError> {
+    let mut command = std::process::Command::new(interpreter);
+    command
+        .arg(script)
+        .arg("--symbols")
+        .arg(params.symbol_or_symbols.join(","))
+        .arg("--timeframe")
+        .arg(params.timeframe.to_string())
+        .arg("--start")
+        .arg(params.start.to_rfc3339())
+        .arg("--end")
+        .arg(params.end.to_rfc3339());
+    if let Some(feed) = &params.feed {
+        command.arg("--feed").arg(feed);
+    }
+    let output = command.output().map_err(|e| PythonBridgeError::Spawn {
+        interpreter: interpreter.display().to_string(),
+        source: e,
+    })?;
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    forward_diagnostics("stderr", stderr.lines());
+    if !output.status.success() {
+        return Err(PythonBridgeError::Script {
+            status: output.status.to_string(),
+            stderr: stderr.trim_end().to_string(),
+        });
+    }
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let (path, chatter) = split_artifact_path(&stdout);
+    forward_diagnostics("stdout", chatter.into_iter());
+    path.map(PathBuf::from).ok_or(PythonBridgeError::NoArtifact)
+}
+
+/// Per-process artifact counter; see [`unique_artifact_tag`].
+static ARTIFACT_SEQ: AtomicU64 = AtomicU64::new(0);
+
+/// A tag no other artifact on this host carries: pid and a process-wide
+/// counter make it unique among live processes, nanos since the epoch
+/// guard against a recycled pid colliding with a leftover file.
+fn unique_artifact_tag() -> String {
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    format!(
+        "{}-{nanos}-{}",
+        std::process::id(),
+        ARTIFACT_SEQ.fetch_add(1, Ordering::Relaxed)
+    )
+}
+
+/// Create `bars_{stem}_{timeframe}_{start}_{end}[_{suffix}]_{tag}.json`
+/// under `output_dir`, retrying with a fresh tag until the name is new —
+/// `create_new` makes the uniqueness a guarantee rather than a
+/// probability, so concurrent fetches of the same request never race on
+/// one path.
+fn create_unique_artifact(
+    output_dir: &Path,
+    stem: &str,
+    params: &StockBarsParams,
+    suffix: Option<&str>,
+) -> Result<(std::fs::File, PathBuf), PythonBridgeError> {
+    std::fs::create_dir_all(output_dir)?;
+    let suffix = suffix.map(|s| format!("_{s}")).unwrap_or_default();
+    loop {
+        let name = format!(
+            "bars_{}_{}_{}_{}{suffix}_{}.json",
+            stem.replace('/', "_"),
+            params.timeframe,
+            params.start.format("%Y%m%dT%H%M%SZ"),
+            params.end.format("%Y%m%dT%H%M%SZ"),
+            unique_artifact_tag(),
+        );
+        let path = output_dir.join(name);
+        match std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+        {
+            Ok(file) => return Ok((file, path)),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+/// Serialize fetched series to one JSON artifact per request. Names carry
+/// a [`unique_artifact_tag`], so parallel runs of the same window each get
+/// their own file instead of overwriting each other mid-write; cleaning up
+/// superseded artifacts is the caller's business.
+fn write_series_artifact(
+    output_dir: &Path,
+    params: &StockBarsParams,
+    series: &[BarSeries],
+    suffix: Option<&str>,
+) -> Result<PathBuf, PythonBridgeError> {
+    let stem = params.symbol_or_symbols.join("-");
+    let (mut file, path) = create_unique_artifact(output_dir, &stem, params, suffix)?;
+    let json = serde_json::to_vec_pretty(series).map_err(ProviderError::Decode)?;
+    std::io::Write::write_all(&mut file, &json)?;
+    Ok(path)
+}
+
+/// One JSON artifact per series, named after its own symbol rather than
+/// the request's joined symbol list, so each file holds exactly the symbol
+/// its name claims.
+fn write_series_artifacts_per_symbol(
+    output_dir: &Path,
+    params: &StockBarsParams,
+    series: &[BarSeries],
+) -> Result<Vec<(String, PathBuf)>, PythonBridgeError> {
+    let mut artifacts = Vec::with_capacity(series.len());
+    for s in series {
+        let (mut file, path) = create_unique_artifact(output_dir, &s.symbol, params, None)?;
+        let json =
+            serde_json::to_vec_pretty(std::slice::from_ref(s)).map_err(ProviderError::Decode)?;
+        std::io::Write::write_all(&mut file, &json)?;
+        artifacts.push((s.symbol.clone(), path));
+    }
+    Ok(artifacts)
+}
+
+/// The artifact path is the last non-empty stdout line; everything before
+/// it is diagnostic chatter to be forwarded, not emitted.
+#[cfg_attr(not(feature = "python"), allow(dead_code))]
+fn split_artifact_path(stdout: &str) -> (Option<&Path>, Vec<&str>) {
+    let mut lines: Vec<&str> = stdout
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .collect();
+    let path = lines.pop().map(Path::new);
+    (path, lines)
+}
+
+#[cfg_attr(not(feature = "python"), allow(dead_code))]
+#[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+fn forward_diagnostics<'a>(stream: &'static str, lines: impl Iterator<Item = &'a str>) {
+    for line in lines {
+        #[cfg(feature = "tracing")]
+        tracing::debug!(stream, line, "python bridge output");
+        #[cfg(not(feature = "tracing"))]
+        let _ = line;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::bar::Bar;
+    use crate::models::timeframe::{TimeFrame, TimeFrameUnit};
+
+    fn day_params() -> StockBarsParams {
+        StockBarsParams {
+            symbol_or_symbols: vec!["AAPL".to_string()],
+            timeframe: TimeFrame::new(1, TimeFrameUnit::Day).unwrap(),
+            start: "2024-01-01T00:00:00Z".parse().unwrap(),
+            end: "2024-02-01T00:00:00Z".parse().unwrap(),
+            feed: None,
+            exchange: None,
+            limit: None,
+        }
+    }
+
+    #[test]
+    fn version_banner_does_not_precede_the_artifact_path() {
+        let stdout = "Alpaca version: 0.21.0\nfetched 3 pages\n/tmp/bars/AAPL.feather\n";
+        let (path, chatter) = split_artifact_path(stdout);
+        assert_eq!(path, Some(Path::new("/tmp/bars/AAPL.feather")));
+        assert_eq!(chatter, vec!["Alpaca version: 0.21.0", "fetched 3 pages"]);
+    }
+
+    #[test]
+    fn empty_stdout_yields_no_artifact() {
+        let (path, chatter) = split_artifact_path("\n  \n");
+        assert_eq!(path, None);
+        assert!(chatter.is_empty());
+    }
+
+    #[cfg(feature = "python")]
+    #[test]
+    fn bridge_returns_only_the_path_from_a_chatty_script() {
+        use std::io::Write;
+
+        let dir = tempfile::tempdir().unwrap();
+        let script = dir.path().join("fake_fetch.py");
+        let mut f = std::fs::File::create(&script).unwrap();
+        writeln!(f, "print('Alpaca version: 0.21.0')").unwrap();
+        writeln!(f, "import sys; print('progress', file=sys.stderr)").unwrap();
+        writeln!(f, "print('/tmp/bars/AAPL.feather')").unwrap();
+        drop(f);
+
+        let bridge = StockBarData::new("python3", &script);
+        let path = bridge.fetch_historical_bars(&day_params()).unwrap();
+        assert_eq!(path, PathBuf::from("/tmp/bars/AAPL.feather"));
+    }
+
+    #[cfg(feature = "python")]
+    #[test]
+    fn env_with_a_venv_selects_the_python_backend() {
+        let env = |name: &str| match name {
+            ENV_PYTHON_VENV => Some("/opt/venvs/alpaca".to_string()),
+            ENV_FETCH_SCRIPT => Some("/opt/scripts/fetch.py".to_string()),
+            _ => None,
+        };
+        let data = StockBarData::from_env_with(env).unwrap();
+        match data.backend {
+            Backend::Python {
+                interpreter,
+                script,
+            } => {
+                assert_eq!(interpreter, PathBuf::from("/opt/venvs/alpaca/bin/python"));
+                assert_eq!(script, PathBuf::from("/opt/scripts/fetch.py"));
+            }
+            #[allow(unreachable_patterns)]
+            _ => panic!("expected the python backend"),
+        }
+
+        // The venv without a script is a config error, not a fallback.
+        let missing_script = StockBarData::from_env_with(|name| {
+            (name == ENV_PYTHON_VENV).then(|| "/opt/venvs/alpaca".to_string())
+        });
+        match missing_script {
+            Err(err) => assert!(err.to_string().contains(ENV_FETCH_SCRIPT), "{err}"),
+            Ok(_) => panic!("expected a missing-script error"),
+        }
+    }
+
+    #[test]
+    fn env_without_a_venv_falls_back_to_the_native_backend() {
+        let env = |name: &str| match name {
+            "APCA_API_KEY_ID" => Some("key".to_string()),
+            "APCA_API_SECRET_KEY" => Some("secret".to_string()),
+            ENV_OUTPUT_DIR => Some("/tmp/bars".to_string()),
+            _ => None,
+        };
+        let data = StockBarData::from_env_with(env).unwrap();
+        match data.backend {
+            Backend::Native { output_dir, .. } => {
+                assert_eq!(output_dir, PathBuf::from("/tmp/bars"));
+            }
+            #[allow(unreachable_patterns)]
+            _ => panic!("expected the native backend"),
+        }
+
+        let missing_secret = StockBarData::from_env_with(|name| {
+            (name == "APCA_API_KEY_ID").then(|| "key".to_string())
+        });
+        match missing_secret {
+            Err(err) => assert!(err.to_string().contains("APCA_API_SECRET_KEY"), "{err}"),
+            Ok(_) => panic!("expected a missing-secret error"),
+        }
+    }
+
+    #[test]
+    fn native_artifact_round_trips_series() {
+        let dir = tempfile::tempdir().unwrap();
+        let params = day_params();
+        let series = vec![BarSeries {
+            symbol: "AAPL".to_string(),
+            timeframe: params.timeframe,
+            bars: vec![Bar {
+                timestamp: "2024-01-02T05:00:00Z".parse().unwrap(),
+                open: 187.15,
+                high: 188.44,
+                low: 183.89,
+                close: 185.64,
+                volume: 82_488_674.0,
+                trade_count: Some(1_009_074),
+                vwap: Some(185.94),
+            }],
+            source_feed: None,
+        }];
+        let path = write_series_artifact(dir.path(), &params, &series, None).unwrap();
+        let name = path.file_name().unwrap().to_str().unwrap();
+        assert!(
+            name.starts_with("bars_AAPL_1Day_20240101T000000Z_20240201T000000Z_"),
+            "{name}"
+        );
+        assert!(name.ends_with(".json"), "{name}");
+        let read: Vec<BarSeries> = serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        assert_eq!(read, series);
+
+        let suffixed = write_series_artifact(dir.path(), &params, &series, Some("e7")).unwrap();
+        let name = suffixed.file_name().unwrap().to_str().unwrap();
+        assert!(name.contains("_20240201T000000Z_e7_"), "{name}");
+    }
+
+    #[test]
+    fn concurrent_writes_of_one_request_get_distinct_artifacts() {
+        let dir = tempfile::tempdir().unwrap();
+        let params = day_params();
+        let series: Vec<BarSeries> = Vec::new();
+        let paths: Vec<PathBuf> = std::thread::scope(|scope| {
+            let handles: Vec<_> = (0..2)
+                .map(|_| scope.spawn(|| write_series_artifact(dir.path(), &params, &series, None)))
+                .collect();
+            handles
+                .into_iter()
+                .map(|h| h.join().unwrap().unwrap())
+                .collect()
+        });
+        assert_ne!(paths[0], paths[1]);
+        assert!(paths.iter().all(|p| p.is_file()));
+    }
+
+    #[test]
+    fn per_symbol_artifacts_split_a_two_symbol_fetch() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut params = day_params();
+        params.symbol_or_symbols = vec!["AAPL".to_string(), "MSFT".to_string()];
+        let bar = Bar {
+            timestamp: "2024-01-02T05:00:00Z".parse().unwrap(),
+            open: 1.0,
+            high: 2.0,
+            low: 0.5,
+            close: 1.5,
+            volume: 10.0,
+            trade_count: Some(3),
+            vwap: Some(1.2),
+        };
+        let series: Vec<BarSeries> = params
+            .symbol_or_symbols
+            .iter()
+            .map(|symbol| BarSeries {
+                symbol: symbol.clone(),
+                timeframe: params.timeframe,
+                bars: vec![bar.clone()],
+                source_feed: None,
+            })
+            .collect();
+
+        let artifacts = write_series_artifacts_per_symbol(dir.path(), &params, &series).unwrap();
+        assert_eq!(artifacts.len(), 2);
+        for ((symbol, path), expected) in artifacts.iter().zip(&series) {
+            assert_eq!(symbol, &expected.symbol);
+            let name = path.file_name().unwrap().to_str().unwrap();
+            assert!(
+                name.starts_with(&format!(
+                    "bars_{symbol}_1Day_20240101T000000Z_20240201T000000Z_"
+                )),
+                "{name}"
+            );
+            let read: Vec<BarSeries> =
+                serde_json::from_slice(&std::fs::read(path).unwrap()).unwrap();
+            assert_eq!(read, vec![expected.clone()]);
+        }
+    }
+}
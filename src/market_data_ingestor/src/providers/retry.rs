@@ -0,0 +1,185 @@
+//! Retry policy and a [`DataProvider`] decorator applying it.
+//!
+//! The legacy Python batch runner hardcoded which error messages count as
+//! transient. The policy lives here instead, as data: a list of
+//! substrings matched against the error text, so a provider with
+//! different transient wording is a configuration change, not a code
+//! change — and the classification is testable on its own.
+
+use std::time::Duration;
+
+use crate::models::bar::BarSeries;
+use crate::models::request_params::BarsRequestParams;
+use crate::providers::{DataProvider, ProviderCapabilities, ProviderError};
+
+/// When and how often a failed fetch is retried.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// A failure retries only if its message contains one of these
+    /// (case-insensitive).
+    pub retryable_substrings: Vec<String>,
+    /// Additional attempts after the first failure.
+    pub max_retries: u32,
+    /// Sleep before the first retry; doubles per subsequent attempt.
+    pub base_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    /// The classification the Python batch runner hardcoded.
+    fn default() -> Self {
+        RetryPolicy {
+            retryable_substrings: ["internal", "503", "timeout", "rate limit"]
+                .map(str::to_string)
+                .to_vec(),
+            max_retries: 3,
+            base_delay: Duration::from_millis(250),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Whether `error`'s message marks it transient under this policy.
+    pub fn is_retryable(&self, error: &ProviderError) -> bool {
+        let message = error.to_string().to_lowercase();
+        self.retryable_substrings
+            .iter()
+            .any(|s| message.contains(&s.to_lowercase()))
+    }
+
+    /// Backoff before retry attempt `attempt` (1-based), doubling each
+    /// time.
+    fn delay_for(&self, attempt: u32) -> Duration {
+        self.base_delay * 2u32.saturating_pow(attempt.saturating_sub(1))
+    }
+}
+
+/// Wraps any provider and re-issues fetches that fail transiently under
+/// the configured [`RetryPolicy`].
+pub struct RetryingProvider<P> {
+    inner: P,
+    policy: RetryPolicy,
+}
+
+impl<P> RetryingProvider<P> {
+    pub fn new(inner: P, policy: RetryPolicy) -> Self {
+        RetryingProvider { inner, policy }
+    }
+}
+
+impl<P: DataProvider> DataProvider for RetryingProvider<P> {
+    fn capabilities(&self) -> ProviderCapabilities {
+        self.inner.capabilities()
+    }
+
+    fn fetch_bars(&self, params: &BarsRequestParams) -> Result<Vec<BarSeries>, ProviderError> {
+        let mut attempt = 0u32;
+        loop {
+            match self.inner.fetch_bars(params) {
+                Ok(series) => return Ok(series),
+                Err(e) if attempt < self.policy.max_retries && self.policy.is_retryable(&e) => {
+                    attempt += 1;
+                    std::thread::sleep(self.policy.delay_for(attempt));
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use super::*;
+    use crate::models::timeframe::{TimeFrame, TimeFrameUnit};
+
+    /// Fails with `message` until `failures` fetches have happened.
+    struct FlakyProvider {
+        message: &'static str,
+        failures: u32,
+        calls: AtomicU32,
+    }
+
+    impl DataProvider for FlakyProvider {
+        fn capabilities(&self) -> ProviderCapabilities {
+            ProviderCapabilities {
+                max_symbols_per_request: 1,
+                earliest_data: None,
+                max_bars_per_window: None,
+            }
+        }
+
+        fn fetch_bars(&self, _params: &BarsRequestParams) -> Result<Vec<BarSeries>, ProviderError> {
+            if self.calls.fetch_add(1, Ordering::SeqCst) < self.failures {
+                Err(ProviderError::Transport(self.message.to_string()))
+            } else {
+                Ok(Vec::new())
+            }
+        }
+    }
+
+    fn params() -> BarsRequestParams {
+        BarsRequestParams {
+            symbols: vec!["AAPL".to_string()],
+            timeframe: TimeFrame::new(1, TimeFrameUnit::Day).unwrap(),
+            start: "2024-01-02T00:00:00Z".parse().unwrap(),
+            end: "2024-01-03T00:00:00Z".parse().unwrap(),
+        }
+    }
+
+    fn fast_policy(substrings: &[&str]) -> RetryPolicy {
+        RetryPolicy {
+            retryable_substrings: substrings.iter().map(|s| s.to_string()).collect(),
+            max_retries: 3,
+            base_delay: Duration::from_millis(1),
+        }
+    }
+
+    #[test]
+    fn custom_substring_triggers_retry() {
+        let inner = FlakyProvider {
+            message: "connection reset by peer",
+            failures: 2,
+            calls: AtomicU32::new(0),
+        };
+        let provider = RetryingProvider::new(inner, fast_policy(&["connection reset"]));
+        provider.fetch_bars(&params()).unwrap();
+        assert_eq!(provider.inner.calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn unlisted_errors_fail_immediately() {
+        let inner = FlakyProvider {
+            message: "connection reset by peer",
+            failures: 2,
+            calls: AtomicU32::new(0),
+        };
+        let provider = RetryingProvider::new(inner, fast_policy(&["rate limit"]));
+        provider.fetch_bars(&params()).unwrap_err();
+        assert_eq!(provider.inner.calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn retries_stop_at_the_configured_maximum() {
+        let inner = FlakyProvider {
+            message: "rate limit exceeded",
+            failures: u32::MAX,
+            calls: AtomicU32::new(0),
+        };
+        let provider = RetryingProvider::new(inner, fast_policy(&["rate limit"]));
+        provider.fetch_bars(&params()).unwrap_err();
+        // One initial attempt plus max_retries.
+        assert_eq!(provider.inner.calls.load(Ordering::SeqCst), 4);
+    }
+
+    #[test]
+    fn default_policy_matches_the_legacy_classification() {
+        let policy = RetryPolicy::default();
+        assert!(policy.is_retryable(&ProviderError::Transport("Rate Limit hit".to_string())));
+        assert!(policy.is_retryable(&ProviderError::Http {
+            status: 503,
+            body: "unavailable".to_string(),
+        }));
+        assert!(!policy.is_retryable(&ProviderError::InvalidRequest("bad symbol".to_string())));
+    }
+}
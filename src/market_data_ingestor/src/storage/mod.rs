@@ -0,0 +1,4 @@
+//! Durable bar storage backends.
+
+#[cfg(feature = "delta")]
+pub mod delta;